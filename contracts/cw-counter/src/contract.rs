@@ -94,55 +94,24 @@ pub mod query {
 
 #[cfg(test)]
 mod tests {
-    use crate::msg::GetUserCountResponse;
-
     use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary};
+    use contract_scenarios::{mock_contract, Scenario};
+
+    fn run(name: &str) {
+        let path = format!("{}/scenarios/{}.yaml", env!("CARGO_MANIFEST_DIR"), name);
+        let mut contract = mock_contract!(instantiate, execute, query);
+        if let Err(err) = Scenario::load(path).and_then(|s| s.run(&mut contract)) {
+            panic!("{}", err);
+        }
+    }
 
     #[test]
     fn proper_initialization() {
-        let mut deps = mock_dependencies();
-
-        let msg = InstantiateMsg { count: 17 };
-        let info = mock_info("creator", &coins(1000, "earth"));
-
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
-
-        // it worked, let's query the state
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: GetCountResponse = from_binary(&res).unwrap();
-        assert_eq!(17, value.count);
+        run("proper_initialization");
     }
 
     #[test]
     fn increment() {
-        let mut deps = mock_dependencies();
-
-        let msg = InstantiateMsg { count: 17 };
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-
-        // beneficiary can release it
-        let info = mock_info("secret_user", &coins(2, "token"));
-        let msg = ExecuteMsg::Increment {};
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-
-        // should increase counter by 1
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: GetCountResponse = from_binary(&res).unwrap();
-        assert_eq!(18, value.count);
-
-        // should increase counter by 1
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::GetUserCount { addr: info.sender },
-        )
-        .unwrap();
-        let value: GetUserCountResponse = from_binary(&res).unwrap();
-        assert_eq!(1, value.count);
+        run("increment");
     }
 }
@@ -3,6 +3,8 @@ mod error;
 pub mod helpers;
 pub mod integration_tests;
 pub mod msg;
+#[cfg(test)]
+mod proptests;
 pub mod state;
 
 pub use crate::error::ContractError;
@@ -0,0 +1,41 @@
+//! Random sequences of increments from several senders, checked against the
+//! counters' invariants after every message.
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{from_binary, Addr};
+use proptest::prelude::*;
+
+use crate::contract::{execute, instantiate, query};
+use crate::msg::{ExecuteMsg, GetCountResponse, GetUserCountResponse, InstantiateMsg, QueryMsg};
+
+const SENDERS: [&str; 4] = ["alice", "bob", "carol", "dave"];
+
+proptest! {
+    #[test]
+    fn count_is_the_sum_of_user_counts(
+        initial in -1000i32..1000,
+        senders in prop::collection::vec(0..SENDERS.len(), 0..48),
+    ) {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg { count: initial }).unwrap();
+
+        for sender in senders {
+            let info = mock_info(SENDERS[sender], &[]);
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Increment {}).unwrap();
+
+            let mut sum = 0;
+            for user in SENDERS {
+                let msg = QueryMsg::GetUserCount { addr: Addr::unchecked(user) };
+                // users who never incremented have no count at all
+                if let Ok(res) = query(deps.as_ref(), mock_env(), msg) {
+                    let count = from_binary::<GetUserCountResponse>(&res).unwrap().count;
+                    prop_assert!(count > 0, "{} has count {}", user, count);
+                    sum += count;
+                }
+            }
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+            prop_assert_eq!(initial + sum, from_binary::<GetCountResponse>(&res).unwrap().count);
+        }
+    }
+}
@@ -0,0 +1,16 @@
+#![no_main]
+
+use cosmwasm_std::from_slice;
+use cosmwasm_std::testing::{mock_env, mock_info};
+use libfuzzer_sys::fuzz_target;
+use secret_counter::contract::execute;
+use secret_counter::msg::ExecuteMsg;
+use secret_counter_fuzz::{counter, user};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(msg) = from_slice::<ExecuteMsg>(data) {
+        let mut deps = counter();
+        let info = mock_info(&user().address(), &[]);
+        let _ = execute(deps.as_mut(), mock_env(), info, msg);
+    }
+});
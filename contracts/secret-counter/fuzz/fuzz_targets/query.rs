@@ -0,0 +1,14 @@
+#![no_main]
+
+use cosmwasm_std::from_slice;
+use cosmwasm_std::testing::mock_env;
+use libfuzzer_sys::fuzz_target;
+use secret_counter::contract::query;
+use secret_counter::msg::QueryMsg;
+use secret_counter_fuzz::counter;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(msg) = from_slice::<QueryMsg>(data) {
+        let _ = query(counter().as_ref(), mock_env(), msg);
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use cosmwasm_std::from_slice;
+use cosmwasm_std::testing::mock_env;
+use libfuzzer_sys::fuzz_target;
+use secret_counter::contract::query;
+use secret_counter::msg::{QueryMsg, QueryWithPermit};
+use secret_counter_fuzz::{counter, permit};
+
+// The permit is valid, so every fuzzed query gets past the signature check.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(query_msg) = from_slice::<QueryWithPermit>(data) {
+        let msg = QueryMsg::WithPermit {
+            permit: permit().clone(),
+            query: query_msg,
+        };
+        let _ = query(counter().as_ref(), mock_env(), msg);
+    }
+});
@@ -0,0 +1,70 @@
+//! A secret-counter with some state for the fuzz targets to run messages against.
+
+use std::sync::OnceLock;
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier};
+use cosmwasm_std::{Binary, MemoryStorage, OwnedDeps};
+use secret_counter::contract::{execute, instantiate};
+use secret_counter::msg::{CounterContractPermissions, ExecuteMsg, InstantiateMsg};
+use secret_permit_signer::PermitSigner;
+use secret_toolkit::permit::{Permit, PermitParams};
+
+pub type Deps = OwnedDeps<MemoryStorage, MockApi, MockQuerier>;
+
+/// Viewing key `user` sets, so fuzzed `GetUserCount` queries can get past
+/// the key check.
+pub const VIEWING_KEY: &str = "key";
+
+pub fn user() -> &'static PermitSigner {
+    static USER: OnceLock<PermitSigner> = OnceLock::new();
+    USER.get_or_init(|| PermitSigner::from_seed(b"user"))
+}
+
+/// A contract `user` has incremented and set [`VIEWING_KEY`] on.
+pub fn counter() -> Deps {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        count: 1,
+        prng_seed: Binary::from(b"seed"),
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+    let info = mock_info(&user().address(), &[]);
+    execute(deps.as_mut(), mock_env(), info.clone(), ExecuteMsg::Increment {}).unwrap();
+    let msg = ExecuteMsg::SetViewingKey {
+        key: VIEWING_KEY.to_string(),
+    };
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    deps
+}
+
+/// A valid permit of `user` for the mock contract, signed once.
+pub fn permit() -> &'static Permit<CounterContractPermissions> {
+    static PERMIT: OnceLock<Permit<CounterContractPermissions>> = OnceLock::new();
+    PERMIT.get_or_init(|| {
+        user().sign(PermitParams {
+            allowed_tokens: vec![mock_env().contract.address.to_string()],
+            permit_name: "fuzz".to_string(),
+            chain_id: "secret-4".to_string(),
+            permissions: vec![CounterContractPermissions::UserCount],
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::from_slice;
+    use secret_counter::contract::query;
+    use secret_counter::msg::QueryMsg;
+
+    #[test]
+    fn user_count_seed_gets_past_the_viewing_key() {
+        let seed = include_bytes!("../seeds/query/get_user_count.json");
+        let msg: QueryMsg = from_slice(seed).unwrap();
+        assert!(
+            matches!(&msg, QueryMsg::GetUserCount { addr, .. } if addr.as_str() == user().address())
+        );
+        query(counter().as_ref(), mock_env(), msg).unwrap();
+    }
+}
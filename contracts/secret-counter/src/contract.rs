@@ -197,183 +197,50 @@ pub mod query {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::msg::{CounterContractPermissions, GetUserCountResponse};
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary, Addr, StdError};
-    use secret_toolkit::permit::{validate, PermitParams, PermitSignature, PubKey};
+    use crate::msg::CounterContractPermissions;
+    use contract_scenarios::{mock_contract, Scenario};
+    use cosmwasm_std::testing::mock_env;
+    use secret_permit_signer::PermitSigner;
+    use secret_toolkit::permit::PermitParams;
+
+    fn scenario(name: &str) -> Scenario {
+        let path = format!("{}/scenarios/{}.yaml", env!("CARGO_MANIFEST_DIR"), name);
+        Scenario::load(path).unwrap()
+    }
+
+    fn run(scenario: Scenario) {
+        let mut contract = mock_contract!(instantiate, execute, query);
+        if let Err(err) = scenario.run(&mut contract) {
+            panic!("{}", err);
+        }
+    }
 
     #[test]
     fn proper_initialization() {
-        let mut deps = mock_dependencies();
-
-        let msg = InstantiateMsg {
-            count: 17,
-            prng_seed: Binary::default(),
-        };
-        let info = mock_info("creator", &coins(1000, "earth"));
-
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
-
-        // it worked, let's query the state
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: GetCountResponse = from_binary(&res).unwrap();
-        assert_eq!(17, value.count);
+        run(scenario("proper_initialization"));
     }
 
     #[test]
     fn increment() {
-        let mut deps = mock_dependencies();
-
-        let msg = InstantiateMsg {
-            count: 17,
-            prng_seed: Binary::default(),
-        };
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-
-        // beneficiary can release it
-        let info = mock_info("secret_user", &coins(2, "token"));
-        let msg = ExecuteMsg::Increment {};
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-
-        // should increase counter by 1
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: GetCountResponse = from_binary(&res).unwrap();
-        assert_eq!(18, value.count);
+        run(scenario("increment"));
     }
 
     #[test]
     fn query_with_vk() {
-        let mut deps = mock_dependencies();
-
-        let msg = InstantiateMsg {
-            count: 17,
-            prng_seed: Binary::default(),
-        };
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-
-        // beneficiary can release it
-        let info = mock_info("secret_user", &coins(2, "token"));
-        let msg = ExecuteMsg::Increment {};
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-
-        // should increase counter by 1
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: GetCountResponse = from_binary(&res).unwrap();
-        assert_eq!(18, value.count);
-
-        //Using Viewing key to check the counter.
-
-        //Setting viewing_key first
-        let info = mock_info("secret_user", &coins(2, "token"));
-        let msg = ExecuteMsg::SetViewingKey {
-            key: "vk_1".to_string(),
-        };
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-
-        //Querying with wrong address
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::GetUserCount {
-                addr: Addr::unchecked("not_secret_user"),
-                key: "vk_1".to_string(),
-            },
-        );
-        assert_eq!(
-            res.unwrap_err(),
-            StdError::GenericErr {
-                msg: "Wrong viewing key for this address or viewing key not set".to_string(),
-            }
-        );
-
-        //Querying with correct address
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::GetUserCount {
-                addr: Addr::unchecked("secret_user"),
-                key: "vk_1".to_string(),
-            },
-        )
-        .unwrap();
-        let value: GetUserCountResponse = from_binary(&res).unwrap();
-        assert_eq!(1, value.count);
+        run(scenario("query_with_vk"));
     }
 
     #[test]
-
     fn query_with_permit() {
-        const USER: &str = "secret12eqwlqwcu2cundyg8ytnuhjnr29fh3zgt68602";
-        const CONTRACT_ADDRESS: &str = "cosmos2contract";
-        const PERMIT_NAME: &str = "CounterPermit";
-        const CHAIN_ID: &str = "secret-4";
-
-        let mut deps = mock_dependencies();
-
-        let msg = InstantiateMsg {
-            count: 17,
-            prng_seed: Binary::default(),
-        };
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        // beneficiary can release it
-        let info = mock_info(USER, &coins(2, "token"));
-        let msg = ExecuteMsg::Increment {};
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-
-        // should increase counter by 1
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        let value: GetCountResponse = from_binary(&res).unwrap();
-        assert_eq!(18, value.count);
-
-        //Using Permit to check the counter.
-
-        //1) Checking signature validity
-        let permit: Permit<CounterContractPermissions> = Permit{
-                    params: PermitParams {
-                        allowed_tokens: vec![CONTRACT_ADDRESS.to_string()],
-                        permit_name: PERMIT_NAME.to_string(),
-                        chain_id: CHAIN_ID.to_string(),
-                        permissions: vec![CounterContractPermissions::UserCount]
-                    },
-                    signature: PermitSignature {
-                        pub_key: PubKey {
-                            r#type: "tendermint/PubKeySecp256k1".to_string(),
-                            value: Binary::from_base64("Ar1oSw8miosG4fJqucfb8c+HpHfr5dSvyGC5kQG9hIUy").unwrap(),
-                        },
-                        signature: Binary::from_base64("2pZXHXJZJw8s5q3ju0z9d5Njh77GtgEgLzdXcNHVg91H93MpuTHWPy99MQCUaw5O2dRE44G2GWQDqCGTwlO47w==").unwrap()
-                    }
-                };
-
-        let address = validate::<CounterContractPermissions>(
-            deps.as_ref(),
-            PREFIX_REVOKED_PERMITS,
-            &permit,
-            CONTRACT_ADDRESS.to_string(),
-            None,
-        )
-        .unwrap();
-
-        assert_eq!(
-            address,
-            "secret12eqwlqwcu2cundyg8ytnuhjnr29fh3zgt68602".to_string()
-        );
-
-        //Querying with correct address
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::WithPermit {
-                permit: permit,
-                query: crate::msg::QueryWithPermit::GetUserCount {},
-            },
-        )
-        .unwrap();
-        let value: GetUserCountResponse = from_binary(&res).unwrap();
-        assert_eq!(1, value.count);
+        let signer = PermitSigner::from_seed(b"user");
+        let permit = signer.sign(PermitParams {
+            allowed_tokens: vec![mock_env().contract.address.to_string()],
+            permit_name: "CounterPermit".to_string(),
+            chain_id: "secret-4".to_string(),
+            permissions: vec![CounterContractPermissions::UserCount],
+        });
+        run(scenario("query_with_permit")
+            .bind("user", signer.address())
+            .bind("permit", permit));
     }
 }
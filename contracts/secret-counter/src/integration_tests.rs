@@ -1,9 +1,10 @@
 #[cfg(test)]
 mod tests {
-    use crate::helpers::CwTemplateContract;
-    use crate::msg::InstantiateMsg;
-    use cosmwasm_std::{Addr, Coin, Empty, Uint128};
-    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+    use crate::msg::{
+        ExecuteAnswer, ExecuteMsg, GetCountResponse, GetUserCountResponse, InstantiateMsg, QueryMsg,
+    };
+    use cosmwasm_std::{from_binary, Addr, Binary, Coin, ContractInfo, Empty, Uint128};
+    use secret_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
 
     pub fn contract_template() -> Box<dyn Contract<Empty>> {
         let contract = ContractWrapper::new(
@@ -34,38 +35,80 @@ mod tests {
         })
     }
 
-    fn proper_instantiate() -> (App, CwTemplateContract) {
+    fn proper_instantiate() -> (App, ContractInfo) {
         let mut app = mock_app();
-        let cw_template_id = app.store_code(contract_template());
+        let cw_template_code = app.store_code(contract_template());
 
-        let msg = InstantiateMsg { count: 1i32 };
-        let cw_template_contract_addr = app
-            .instantiate_contract(
-                cw_template_id,
-                Addr::unchecked(ADMIN),
-                &msg,
-                &[],
-                "test",
-                None,
-            )
+        let msg = InstantiateMsg {
+            count: 1i32,
+            prng_seed: Binary::from(b"seed"),
+        };
+        let cw_template_contract = app
+            .instantiate_contract(&cw_template_code, Addr::unchecked(ADMIN), &msg, &[], "test")
             .unwrap();
 
-        let cw_template_contract = CwTemplateContract(cw_template_contract_addr);
-
         (app, cw_template_contract)
     }
 
     mod count {
         use super::*;
-        use crate::msg::ExecuteMsg;
 
         #[test]
         fn count() {
             let (mut app, cw_template_contract) = proper_instantiate();
 
             let msg = ExecuteMsg::Increment {};
-            let cosmos_msg = cw_template_contract.call(msg).unwrap();
-            app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+            app.execute_contract(Addr::unchecked(USER), &cw_template_contract, &msg, &[])
+                .unwrap();
+
+            let res: GetCountResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    &cw_template_contract.code_hash,
+                    &cw_template_contract.address,
+                    &QueryMsg::GetCount {},
+                )
+                .unwrap();
+            assert_eq!(2, res.count);
+        }
+
+        #[test]
+        fn user_count_needs_a_viewing_key() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+            // queries validate the address, and the mock API only takes lowercase ones
+            let user = Addr::unchecked("secret_user");
+
+            app.execute_contract(
+                user.clone(),
+                &cw_template_contract,
+                &ExecuteMsg::Increment {},
+                &[],
+            )
+            .unwrap();
+            let res = app
+                .execute_contract(
+                    user.clone(),
+                    &cw_template_contract,
+                    &ExecuteMsg::CreateViewingKey {
+                        entropy: "entropy".to_string(),
+                    },
+                    &[],
+                )
+                .unwrap();
+            let ExecuteAnswer::CreateViewingKey { key } = from_binary(&res.data.unwrap()).unwrap();
+
+            let query = |key: &str| {
+                app.wrap().query_wasm_smart::<GetUserCountResponse>(
+                    &cw_template_contract.code_hash,
+                    &cw_template_contract.address,
+                    &QueryMsg::GetUserCount {
+                        addr: user.clone(),
+                        key: key.to_string(),
+                    },
+                )
+            };
+            assert_eq!(1, query(&key).unwrap().count);
+            query("wrong key").unwrap_err();
         }
     }
 }
@@ -1,6 +1,11 @@
 pub mod contract;
 mod error;
+mod integration_tests;
 pub mod msg;
+#[cfg(test)]
+mod proptests;
 pub mod state;
+#[cfg(test)]
+mod storage_leakage;
 
 pub use crate::error::ContractError;
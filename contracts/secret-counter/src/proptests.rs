@@ -0,0 +1,121 @@
+//! Random sequences of increments and viewing key changes from several
+//! senders, checked against the counters' invariants after every message.
+
+use std::collections::BTreeMap;
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{from_binary, Addr, Binary};
+use proptest::prelude::*;
+use secret_permit_signer::PermitSigner;
+use secret_toolkit::permit::PermitParams;
+
+use crate::contract::{execute, instantiate, query};
+use crate::msg::{
+    CounterContractPermissions, ExecuteAnswer, ExecuteMsg, GetCountResponse, GetUserCountResponse,
+    InstantiateMsg, QueryMsg, QueryWithPermit,
+};
+
+const SENDERS: usize = 4;
+
+#[derive(Clone, Debug)]
+enum Step {
+    Increment(usize),
+    SetViewingKey(usize, String),
+    CreateViewingKey(usize, String),
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    let sender = 0..SENDERS;
+    prop_oneof![
+        3 => sender.clone().prop_map(Step::Increment),
+        // a small alphabet so that users end up sharing keys
+        1 => (sender.clone(), "[ab]{1,2}").prop_map(|(s, key)| Step::SetViewingKey(s, key)),
+        1 => (sender, "[a-z]{0,8}").prop_map(|(s, entropy)| Step::CreateViewingKey(s, entropy)),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn counts_stay_consistent_and_private(
+        initial in -1000i32..1000,
+        steps in prop::collection::vec(step(), 0..32),
+    ) {
+        let signers: Vec<_> = (0..SENDERS)
+            .map(|i| PermitSigner::from_seed(&[i as u8]))
+            .collect();
+        let users: Vec<_> = signers.iter().map(|s| Addr::unchecked(s.address())).collect();
+        let env = mock_env();
+        let permits: Vec<_> = signers
+            .iter()
+            .map(|signer| {
+                signer.sign(PermitParams {
+                    allowed_tokens: vec![env.contract.address.to_string()],
+                    permit_name: "counter".to_string(),
+                    chain_id: "secret-4".to_string(),
+                    permissions: vec![CounterContractPermissions::UserCount],
+                })
+            })
+            .collect();
+
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg { count: initial, prng_seed: Binary::from(b"seed") };
+        instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+        let mut keys = BTreeMap::new();
+
+        for step in steps {
+            match step {
+                Step::Increment(s) => {
+                    let info = mock_info(users[s].as_str(), &[]);
+                    execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Increment {}).unwrap();
+                }
+                Step::SetViewingKey(s, key) => {
+                    let info = mock_info(users[s].as_str(), &[]);
+                    let msg = ExecuteMsg::SetViewingKey { key: key.clone() };
+                    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+                    keys.insert(s, key);
+                }
+                Step::CreateViewingKey(s, entropy) => {
+                    let info = mock_info(users[s].as_str(), &[]);
+                    let msg = ExecuteMsg::CreateViewingKey { entropy };
+                    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+                    let ExecuteAnswer::CreateViewingKey { key } =
+                        from_binary(&res.data.unwrap()).unwrap();
+                    keys.insert(s, key);
+                }
+            }
+
+            // each permit reads its signer's own count
+            let mut counts = vec![];
+            for permit in &permits {
+                let msg = QueryMsg::WithPermit {
+                    permit: permit.clone(),
+                    query: QueryWithPermit::GetUserCount {},
+                };
+                let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+                let count = from_binary::<GetUserCountResponse>(&res).unwrap().count;
+                prop_assert!(count >= 0);
+                counts.push(count);
+            }
+            let res = query(deps.as_ref(), env.clone(), QueryMsg::GetCount {}).unwrap();
+            let count = from_binary::<GetCountResponse>(&res).unwrap().count;
+            prop_assert_eq!(initial + counts.iter().sum::<i32>(), count);
+
+            // a viewing key opens an address only if it is that address' key
+            for key in keys.values() {
+                for (target, addr) in users.iter().enumerate() {
+                    let msg = QueryMsg::GetUserCount { addr: addr.clone(), key: key.clone() };
+                    match query(deps.as_ref(), env.clone(), msg) {
+                        Ok(res) => {
+                            prop_assert_eq!(Some(key), keys.get(&target));
+                            let count = from_binary::<GetUserCountResponse>(&res).unwrap().count;
+                            prop_assert_eq!(counts[target], count);
+                        }
+                        Err(_) => prop_assert_ne!(Some(key), keys.get(&target)),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! What secret-counter writes to and reads from storage, checked with
+//! `secret-storage-inspector`.
+
+use std::collections::BTreeSet;
+
+use cosmwasm_std::testing::{mock_env, mock_info};
+use cosmwasm_std::{Addr, Binary};
+use secret_permit_signer::PermitSigner;
+use secret_storage_inspector::Inspector;
+use secret_toolkit::permit::PermitParams;
+
+use crate::contract::{execute, instantiate, query};
+use crate::msg::{
+    CounterContractPermissions, ExecuteMsg, InstantiateMsg, QueryMsg, QueryWithPermit,
+};
+
+// far from any length prefix or address character, so a match is a real leak
+const INITIAL: i32 = 5_550_001;
+
+/// Runs every message type as two users, and returns the inspector with the
+/// secrets to look for: both addresses and every count.
+fn scenario() -> (Inspector, Vec<(String, Vec<u8>)>) {
+    let (alice, bob) = (
+        PermitSigner::from_seed(b"alice"),
+        PermitSigner::from_seed(b"bob"),
+    );
+    let (alice_addr, bob_addr) = (alice.address(), bob.address());
+    let env = mock_env();
+    let mut inspector = Inspector::new();
+
+    inspector.run("instantiate", |deps| {
+        let msg = InstantiateMsg {
+            count: INITIAL,
+            prng_seed: Binary::from(b"seed"),
+        };
+        instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap()
+    });
+    for sender in [&alice_addr, &alice_addr, &bob_addr] {
+        inspector.run("increment", |deps| {
+            let info = mock_info(sender, &[]);
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Increment {}).unwrap()
+        });
+    }
+    inspector.run("create_viewing_key", |deps| {
+        let msg = ExecuteMsg::CreateViewingKey {
+            entropy: "entropy".to_string(),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info(&alice_addr, &[]), msg).unwrap()
+    });
+    inspector.run("set_viewing_key", |deps| {
+        let msg = ExecuteMsg::SetViewingKey {
+            key: "key".to_string(),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info(&bob_addr, &[]), msg).unwrap()
+    });
+    inspector.run("get_count", |deps| {
+        query(deps.as_ref(), env.clone(), QueryMsg::GetCount {}).unwrap()
+    });
+    inspector.run("get_user_count", |deps| {
+        let msg = QueryMsg::GetUserCount {
+            addr: Addr::unchecked(&bob_addr),
+            key: "key".to_string(),
+        };
+        query(deps.as_ref(), env.clone(), msg).unwrap()
+    });
+    inspector.run("with_permit", |deps| {
+        let permit = alice.sign(PermitParams {
+            allowed_tokens: vec![env.contract.address.to_string()],
+            permit_name: "counter".to_string(),
+            chain_id: "secret-4".to_string(),
+            permissions: vec![CounterContractPermissions::UserCount],
+        });
+        let query_msg = QueryWithPermit::GetUserCount {};
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::WithPermit {
+                permit,
+                query: query_msg,
+            },
+        )
+        .unwrap()
+    });
+
+    let mut secrets = vec![
+        ("alice".to_string(), alice_addr.into_bytes()),
+        ("bob".to_string(), bob_addr.into_bytes()),
+    ];
+    for count in INITIAL..=INITIAL + 3 {
+        let name = format!("count {}", count);
+        secrets.push((name.clone(), count.to_string().into_bytes()));
+        secrets.push((name.clone(), count.to_be_bytes().to_vec()));
+        secrets.push((name, count.to_le_bytes().to_vec()));
+    }
+    (inspector, secrets)
+}
+
+fn borrowed(secrets: &[(String, Vec<u8>)]) -> Vec<(&str, &[u8])> {
+    secrets
+        .iter()
+        .map(|(name, bytes)| (name.as_str(), bytes.as_slice()))
+        .collect()
+}
+
+#[test]
+fn counts_never_appear_in_keys() {
+    let (inspector, secrets) = scenario();
+    let secrets = borrowed(&secrets);
+    let leaks = inspector.leaks(&secrets);
+    assert!(
+        leaks.iter().all(|l| !l.secret.starts_with("count")),
+        "{:?}",
+        leaks
+    );
+}
+
+// Each user's count (`USER_STATE`), viewing key and revoked permits (both kept
+// by secret-toolkit) sit under a key that holds their raw address. Secret
+// encrypts keys, but the same address gives the same ciphertext, so which user
+// a transaction touches can be linked. This pins down which keys do, per
+// message, until they're hashed.
+#[test]
+fn only_address_keyed_entries_hold_addresses() {
+    let (inspector, secrets) = scenario();
+    let secrets = borrowed(&secrets);
+    let families: BTreeSet<_> = inspector
+        .leaks(&secrets)
+        .into_iter()
+        .map(|leak| {
+            let family = ["user_state", "viewing_keys", "prefix_revoked_permits"]
+                .into_iter()
+                .find(|family| leak.key.contains(family))
+                .unwrap_or_else(|| panic!("unexpected address key {:?}", leak));
+            (leak.message, family)
+        })
+        .collect();
+    let expected: BTreeSet<_> = [
+        ("create_viewing_key", "viewing_keys"),
+        ("get_user_count", "user_state"),
+        ("get_user_count", "viewing_keys"),
+        ("increment", "user_state"),
+        ("set_viewing_key", "viewing_keys"),
+        ("with_permit", "prefix_revoked_permits"),
+        ("with_permit", "user_state"),
+    ]
+    .into_iter()
+    .map(|(message, family)| (message.to_string(), family))
+    .collect();
+    assert_eq!(expected, families);
+}
+
+#[test]
+#[ignore = "user_state, viewing_keys and revoked permits are keyed by raw address"]
+fn addresses_never_appear_in_keys() {
+    let (inspector, secrets) = scenario();
+    let secrets = borrowed(&secrets);
+    let leaks = inspector.leaks(&secrets);
+    assert!(
+        leaks.is_empty(),
+        "raw addresses in keys:\n{}",
+        inspector.report(&secrets)
+    );
+}
@@ -0,0 +1,120 @@
+//! Recorded gas per message, and the check that keeps new numbers close to
+//! it.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Measurement;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    /// How far above its baseline a message's gas may go, in percent.
+    pub threshold_percent: u64,
+    /// Gas by contract, then message.
+    pub gas: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+/// How measurements differ from a baseline by more than its threshold.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Comparison {
+    /// Messages that got more expensive, or have no baseline.
+    pub regressions: Vec<String>,
+    /// Messages that got cheaper: the baseline is out of date.
+    pub improvements: Vec<String>,
+}
+
+impl Baseline {
+    pub fn record(measured: &[Measurement], threshold_percent: u64) -> Self {
+        let mut gas: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+        for m in measured {
+            gas.entry(m.contract.to_string())
+                .or_default()
+                .insert(m.message.to_string(), m.cost.gas);
+        }
+        Baseline {
+            threshold_percent,
+            gas,
+        }
+    }
+
+    pub fn from_json(text: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(text)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("baseline is always serializable") + "\n"
+    }
+
+    pub fn compare(&self, measured: &[Measurement]) -> Comparison {
+        let mut comparison = Comparison::default();
+        for m in measured {
+            let name = format!("{} {}", m.contract, m.message);
+            let Some(&baseline) = self.gas.get(m.contract).and_then(|g| g.get(m.message)) else {
+                comparison
+                    .regressions
+                    .push(format!("{}: {} gas, no baseline", name, m.cost.gas));
+                continue;
+            };
+            let change = format!(
+                "{}: {} gas, baseline {} ({:+.1}%)",
+                name,
+                m.cost.gas,
+                baseline,
+                (m.cost.gas as f64 - baseline as f64) * 100.0 / baseline.max(1) as f64
+            );
+            let margin = baseline * self.threshold_percent / 100;
+            if m.cost.gas > baseline + margin {
+                comparison.regressions.push(change);
+            } else if m.cost.gas + margin < baseline {
+                comparison.improvements.push(change);
+            }
+        }
+        comparison
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cost;
+
+    fn measurement(message: &'static str, gas: u64) -> Measurement {
+        Measurement {
+            contract: "counter",
+            message,
+            cost: Cost {
+                gas,
+                ..Cost::default()
+            },
+        }
+    }
+
+    #[test]
+    fn flags_changes_past_the_threshold() {
+        let baseline = Baseline::record(
+            &[measurement("increment", 1000), measurement("query", 1000)],
+            10,
+        );
+        assert_eq!(baseline, Baseline::from_json(&baseline.to_json()).unwrap());
+
+        let within = [measurement("increment", 1100), measurement("query", 900)];
+        assert_eq!(Comparison::default(), baseline.compare(&within));
+
+        let changed = [
+            measurement("increment", 1101),
+            measurement("query", 899),
+            measurement("new", 5),
+        ];
+        assert_eq!(
+            Comparison {
+                regressions: vec![
+                    "counter increment: 1101 gas, baseline 1000 (+10.1%)".to_string(),
+                    "counter new: 5 gas, no baseline".to_string(),
+                ],
+                improvements: vec!["counter query: 899 gas, baseline 1000 (-10.1%)".to_string()],
+            },
+            baseline.compare(&changed)
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! cw-counter's messages on metered mock dependencies.
+
+use std::marker::PhantomData;
+
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockQuerier};
+use cosmwasm_std::{Addr, Empty, OwnedDeps};
+use cw_counter::contract::{execute, instantiate, query};
+use cw_counter::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+
+use crate::{account, Measurement, MeteredStorage};
+
+type Deps = OwnedDeps<MeteredStorage, MockApi, MockQuerier, Empty>;
+
+pub fn measure() -> Vec<Measurement> {
+    let creator = account("creator").address();
+    let user = account("user").address();
+    let mut deps = Deps {
+        storage: MeteredStorage::default(),
+        api: MockApi::default(),
+        querier: MockQuerier::new(&[]),
+        custom_query_type: PhantomData,
+    };
+    let mut measured = vec![];
+    let mut measure = |message: &'static str, deps: &mut Deps, run: &dyn Fn(&mut Deps)| {
+        deps.storage.take_cost();
+        run(deps);
+        measured.push(Measurement {
+            contract: "cw-counter",
+            message,
+            cost: deps.storage.take_cost(),
+        });
+    };
+
+    measure("instantiate", &mut deps, &|deps| {
+        let msg = InstantiateMsg { count: 0 };
+        instantiate(deps.as_mut(), mock_env(), mock_info(&creator, &[]), msg).unwrap();
+    });
+    let increment = |deps: &mut Deps| {
+        let info = mock_info(&user, &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Increment {}).unwrap();
+    };
+    measure("increment (first)", &mut deps, &increment);
+    measure("increment", &mut deps, &increment);
+    measure("get_count", &mut deps, &|deps| {
+        query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+    });
+    measure("get_user_count", &mut deps, &|deps| {
+        let msg = QueryMsg::GetUserCount {
+            addr: Addr::unchecked(&user),
+        };
+        query(deps.as_ref(), mock_env(), msg).unwrap();
+    });
+    measured
+}
@@ -0,0 +1,75 @@
+//! What privacy costs in gas: runs each message type of cw-counter and
+//! secret-counter on a storage that charges Cosmos SDK KV store gas, and
+//! compares the result with a stored baseline.
+//!
+//! Only storage is metered. Wasm execution, signature checks (permit
+//! queries) and hashing (viewing keys) are charged by the chain's VM, which
+//! doesn't run here for Secret contracts, so real costs are higher, most of
+//! all for the authenticated queries. Secret also encrypts keys and values,
+//! which makes what it stores longer than what the contract writes.
+
+mod baseline;
+mod cw;
+mod secret;
+mod storage;
+
+use std::fmt::Write;
+
+use secret_permit_signer::PermitSigner;
+
+pub use crate::baseline::{Baseline, Comparison};
+pub use crate::storage::{Cost, MeteredStorage};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Measurement {
+    pub contract: &'static str,
+    pub message: &'static str,
+    pub cost: Cost,
+}
+
+/// Every message type of both contracts, each run once after the ones
+/// before it: `increment (first)` is a user's first, which adds their entry.
+pub fn measure() -> Vec<Measurement> {
+    let mut measured = cw::measure();
+    measured.extend(secret::measure());
+    measured
+}
+
+/// A table per contract of the gas and storage each message took.
+pub fn report(measured: &[Measurement]) -> String {
+    let mut out = String::new();
+    let mut contract = "";
+    for m in measured {
+        if m.contract != contract {
+            contract = m.contract;
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            writeln!(
+                out,
+                "{:<30} {:>8} {:>6} {:>7} {:>8} {:>8}",
+                contract, "gas", "reads", "writes", "written", "growth"
+            )
+            .unwrap();
+        }
+        let c = &m.cost;
+        writeln!(
+            out,
+            "  {:<28} {:>8} {:>6} {:>7} {:>8} {:>8}",
+            m.message,
+            c.gas,
+            c.reads,
+            c.writes + c.removes,
+            c.bytes_written,
+            c.growth
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// An account with a real address: key and value sizes cost gas, so the
+/// addresses are as long as on chain.
+fn account(name: &str) -> PermitSigner {
+    PermitSigner::from_seed(name.as_bytes())
+}
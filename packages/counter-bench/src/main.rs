@@ -0,0 +1,5 @@
+//! Prints the gas and storage each message of both counters takes.
+
+fn main() {
+    print!("{}", counter_bench::report(&counter_bench::measure()));
+}
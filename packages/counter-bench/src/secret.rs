@@ -0,0 +1,91 @@
+//! secret-counter's messages on metered mock dependencies.
+
+use std::marker::PhantomData;
+
+use secret_cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockQuerier};
+use secret_cosmwasm_std::{Addr, Binary, Empty, OwnedDeps};
+use secret_counter::contract::{execute, instantiate, query};
+use secret_counter::msg::{
+    CounterContractPermissions, ExecuteMsg, InstantiateMsg, QueryMsg, QueryWithPermit,
+};
+use secret_toolkit::permit::PermitParams;
+
+use crate::{account, Measurement, MeteredStorage};
+
+type Deps = OwnedDeps<MeteredStorage, MockApi, MockQuerier, Empty>;
+
+/// A viewing key as long as the ones `CreateViewingKey` hands out.
+const VIEWING_KEY: &str = "api_key_FSgnmz9nS1wWMPZvPqVMuvJ5qn8M4vAJYiBcWMSVkCM=";
+
+pub fn measure() -> Vec<Measurement> {
+    let creator = account("creator").address();
+    let signer = account("user");
+    let user = signer.address();
+    let other = account("other").address();
+    let mut deps = Deps {
+        storage: MeteredStorage::default(),
+        api: MockApi::default(),
+        querier: MockQuerier::new(&[]),
+        custom_query_type: PhantomData,
+    };
+    let mut measured = vec![];
+    let mut measure = |message: &'static str, deps: &mut Deps, run: &dyn Fn(&mut Deps)| {
+        deps.storage.take_cost();
+        run(deps);
+        measured.push(Measurement {
+            contract: "secret-counter",
+            message,
+            cost: deps.storage.take_cost(),
+        });
+    };
+
+    measure("instantiate", &mut deps, &|deps| {
+        let msg = InstantiateMsg {
+            count: 0,
+            prng_seed: Binary::from(b"seed"),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info(&creator, &[]), msg).unwrap();
+    });
+    let increment = |deps: &mut Deps| {
+        let info = mock_info(&user, &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Increment {}).unwrap();
+    };
+    measure("increment (first)", &mut deps, &increment);
+    measure("increment", &mut deps, &increment);
+    measure("set_viewing_key", &mut deps, &|deps| {
+        let msg = ExecuteMsg::SetViewingKey {
+            key: VIEWING_KEY.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(&user, &[]), msg).unwrap();
+    });
+    measure("create_viewing_key", &mut deps, &|deps| {
+        let msg = ExecuteMsg::CreateViewingKey {
+            entropy: "entropy".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(&other, &[]), msg).unwrap();
+    });
+    measure("get_count", &mut deps, &|deps| {
+        query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+    });
+    measure("get_user_count (viewing key)", &mut deps, &|deps| {
+        let msg = QueryMsg::GetUserCount {
+            addr: Addr::unchecked(&user),
+            key: VIEWING_KEY.to_string(),
+        };
+        query(deps.as_ref(), mock_env(), msg).unwrap();
+    });
+    let permit = signer.sign(PermitParams {
+        allowed_tokens: vec![mock_env().contract.address.to_string()],
+        permit_name: "counter".to_string(),
+        chain_id: "secret-4".to_string(),
+        permissions: vec![CounterContractPermissions::UserCount],
+    });
+    measure("get_user_count (permit)", &mut deps, &|deps| {
+        let msg = QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::GetUserCount {},
+        };
+        query(deps.as_ref(), mock_env(), msg).unwrap();
+    });
+    measured
+}
@@ -0,0 +1,183 @@
+//! A mock storage that charges Cosmos SDK KV store gas for every access.
+//!
+//! Both contracts' `cosmwasm-std` have a `Storage` trait of their own, and
+//! [`MeteredStorage`] implements both, so the two counters are measured with
+//! the same meter.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use secret_cosmwasm_std as secret_std;
+
+/// `KVGasConfig` of the Cosmos SDK (`store/types/gas.go`), the gas every
+/// chain charges for a contract's storage reads and writes.
+pub const READ_COST_FLAT: u64 = 1000;
+pub const READ_COST_PER_BYTE: u64 = 3;
+pub const WRITE_COST_FLAT: u64 = 2000;
+pub const WRITE_COST_PER_BYTE: u64 = 30;
+pub const DELETE_COST: u64 = 1000;
+pub const ITER_NEXT_COST_FLAT: u64 = 30;
+
+/// What a run of storage accesses cost.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cost {
+    pub gas: u64,
+    pub reads: u32,
+    pub writes: u32,
+    pub removes: u32,
+    /// Key and value bytes written.
+    pub bytes_written: u64,
+    /// Change in the bytes of keys and values stored.
+    pub growth: i64,
+}
+
+#[derive(Default)]
+pub struct MeteredStorage {
+    data: BTreeMap<Vec<u8>, Vec<u8>>,
+    cost: RefCell<Cost>,
+}
+
+impl MeteredStorage {
+    /// The cost since the last call.
+    pub fn take_cost(&self) -> Cost {
+        self.cost.take()
+    }
+
+    fn read(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let value = self.data.get(key).cloned();
+        let mut cost = self.cost.borrow_mut();
+        cost.reads += 1;
+        cost.gas += READ_COST_FLAT
+            + READ_COST_PER_BYTE * (key.len() + value.as_ref().map_or(0, Vec::len)) as u64;
+        value
+    }
+
+    fn write(&mut self, key: &[u8], value: &[u8]) {
+        let old = self.data.insert(key.to_vec(), value.to_vec());
+        let cost = self.cost.get_mut();
+        cost.writes += 1;
+        cost.bytes_written += (key.len() + value.len()) as u64;
+        cost.gas += WRITE_COST_FLAT + WRITE_COST_PER_BYTE * (key.len() + value.len()) as u64;
+        cost.growth += match old {
+            Some(old) => value.len() as i64 - old.len() as i64,
+            None => (key.len() + value.len()) as i64,
+        };
+    }
+
+    fn delete(&mut self, key: &[u8]) {
+        let old = self.data.remove(key);
+        let cost = self.cost.get_mut();
+        cost.removes += 1;
+        cost.gas += DELETE_COST;
+        if let Some(old) = old {
+            cost.growth -= (key.len() + old.len()) as i64;
+        }
+    }
+
+    fn iterate<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        ascending: bool,
+    ) -> Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + 'a> {
+        let start = start.map_or(Bound::Unbounded, |s| Bound::Included(s.to_vec()));
+        let end = end.map_or(Bound::Unbounded, |e| Bound::Excluded(e.to_vec()));
+        let range = self.data.range((start, end));
+        let items: Box<dyn Iterator<Item = _>> = if ascending {
+            Box::new(range)
+        } else {
+            Box::new(range.rev())
+        };
+        Box::new(items.map(move |(key, value)| {
+            let mut cost = self.cost.borrow_mut();
+            cost.reads += 1;
+            cost.gas += ITER_NEXT_COST_FLAT + READ_COST_PER_BYTE * (key.len() + value.len()) as u64;
+            (key.clone(), value.clone())
+        }))
+    }
+}
+
+impl cosmwasm_std::Storage for MeteredStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.read(key)
+    }
+
+    fn range<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: cosmwasm_std::Order,
+    ) -> Box<dyn Iterator<Item = cosmwasm_std::Record> + 'a> {
+        self.iterate(start, end, matches!(order, cosmwasm_std::Order::Ascending))
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.write(key, value)
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.delete(key)
+    }
+}
+
+impl secret_std::Storage for MeteredStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.read(key)
+    }
+
+    fn range<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: secret_std::Order,
+    ) -> Box<dyn Iterator<Item = secret_std::Record> + 'a> {
+        self.iterate(start, end, matches!(order, secret_std::Order::Ascending))
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.write(key, value)
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.delete(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{Order, Storage};
+
+    #[test]
+    fn charges_kv_store_gas() {
+        let mut storage = MeteredStorage::default();
+        storage.set(b"key", b"value");
+        storage.set(b"key", b"longer value");
+        assert_eq!(None, storage.get(b"other"));
+        assert_eq!(
+            Cost {
+                gas: 2 * WRITE_COST_FLAT + 30 * (8 + 15) + READ_COST_FLAT + 3 * 5,
+                reads: 1,
+                writes: 2,
+                removes: 0,
+                bytes_written: 23,
+                growth: 15,
+            },
+            storage.take_cost()
+        );
+
+        assert_eq!(1, storage.range(None, None, Order::Descending).count());
+        storage.remove(b"key");
+        assert_eq!(
+            Cost {
+                gas: ITER_NEXT_COST_FLAT + 3 * 15 + DELETE_COST,
+                reads: 1,
+                removes: 1,
+                growth: -15,
+                ..Cost::default()
+            },
+            storage.take_cost()
+        );
+    }
+}
@@ -0,0 +1,35 @@
+//! Fails when a message's gas grows past the threshold in `baseline.json`.
+//! After an intended change, record new numbers with
+//! `UPDATE_GAS_BASELINE=1 cargo test -p counter-bench`.
+
+use std::{env, fs};
+
+use counter_bench::{measure, report, Baseline};
+
+const BASELINE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/baseline.json");
+
+#[test]
+fn gas_stays_within_the_baseline() {
+    let measured = measure();
+    println!("{}", report(&measured));
+
+    let baseline = Baseline::from_json(&fs::read_to_string(BASELINE).unwrap()).unwrap();
+    if env::var_os("UPDATE_GAS_BASELINE").is_some() {
+        let recorded = Baseline::record(&measured, baseline.threshold_percent);
+        fs::write(BASELINE, recorded.to_json()).unwrap();
+        return;
+    }
+
+    let comparison = baseline.compare(&measured);
+    for improvement in &comparison.improvements {
+        println!(
+            "cheaper than the baseline, consider recording it: {}",
+            improvement
+        );
+    }
+    assert!(
+        comparison.regressions.is_empty(),
+        "gas above the baseline:\n{}\nrecord new numbers with UPDATE_GAS_BASELINE=1 if intended",
+        comparison.regressions.join("\n")
+    );
+}
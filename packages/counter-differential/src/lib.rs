@@ -0,0 +1,257 @@
+//! Drives cw-counter and secret-counter through the same calls, so a test can
+//! check that the port answers the way the original does.
+//!
+//! The port is meant to differ only where privacy requires it:
+//!
+//! - cw-counter answers `GetUserCount` for any address. secret-counter tells
+//!   users their own count only, given a viewing key or a permit.
+//!   [`SecretCounter`] signs in as the user both ways.
+//! - `GetUserCount` for an address that never incremented fails in cw-counter
+//!   (`USER_STATE` has no entry) and returns 0 in secret-counter, which
+//!   doesn't reveal whether an address has used the contract.
+//! - secret-counter's `InstantiateMsg` also takes the `prng_seed` for viewing
+//!   keys, and cw-counter records its version with cw2.
+//!
+//! Errors are compared by whether a call fails, not by their text: the two
+//! contracts store state with different libraries that word errors
+//! differently.
+
+use std::collections::BTreeMap;
+
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+};
+use cosmwasm_std::{from_binary, Addr, Env, OwnedDeps};
+use secret_cosmwasm_std as secret_std;
+use secret_permit_signer::PermitSigner;
+use secret_toolkit::permit::{Permit, PermitParams};
+
+/// The parts of a `Response` the two contracts' `cosmwasm-std` have in common.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reply {
+    pub attributes: Vec<(String, String)>,
+    pub messages: usize,
+    pub data: Option<Vec<u8>>,
+}
+
+impl From<cosmwasm_std::Response> for Reply {
+    fn from(res: cosmwasm_std::Response) -> Self {
+        Reply {
+            attributes: res
+                .attributes
+                .into_iter()
+                .map(|attr| (attr.key, attr.value))
+                .collect(),
+            messages: res.messages.len(),
+            data: res.data.map(|data| data.0),
+        }
+    }
+}
+
+impl From<secret_std::Response> for Reply {
+    fn from(res: secret_std::Response) -> Self {
+        Reply {
+            attributes: res
+                .attributes
+                .into_iter()
+                .map(|attr| (attr.key, attr.value))
+                .collect(),
+            messages: res.messages.len(),
+            data: res.data.map(|data| data.0),
+        }
+    }
+}
+
+/// cw-counter on mock dependencies.
+pub struct CwCounter {
+    deps: OwnedDeps<MockStorage, MockApi, MockQuerier>,
+    env: Env,
+}
+
+impl Default for CwCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CwCounter {
+    pub fn new() -> Self {
+        CwCounter {
+            deps: mock_dependencies(),
+            env: mock_env(),
+        }
+    }
+
+    pub fn instantiate(&mut self, sender: &str, count: i32) -> Result<Reply, String> {
+        use cw_counter::msg::InstantiateMsg;
+
+        let info = mock_info(sender, &[]);
+        let msg = InstantiateMsg { count };
+        cw_counter::contract::instantiate(self.deps.as_mut(), self.env.clone(), info, msg)
+            .map(Reply::from)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn increment(&mut self, sender: &str) -> Result<Reply, String> {
+        use cw_counter::msg::ExecuteMsg;
+
+        let info = mock_info(sender, &[]);
+        let msg = ExecuteMsg::Increment {};
+        cw_counter::contract::execute(self.deps.as_mut(), self.env.clone(), info, msg)
+            .map(Reply::from)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn count(&self) -> Result<i32, String> {
+        use cw_counter::msg::{GetCountResponse, QueryMsg};
+
+        let res = cw_counter::contract::query(
+            self.deps.as_ref(),
+            self.env.clone(),
+            QueryMsg::GetCount {},
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(from_binary::<GetCountResponse>(&res).unwrap().count)
+    }
+
+    pub fn user_count(&self, user: &str) -> Result<i32, String> {
+        use cw_counter::msg::{GetUserCountResponse, QueryMsg};
+
+        let msg = QueryMsg::GetUserCount {
+            addr: Addr::unchecked(user),
+        };
+        let res = cw_counter::contract::query(self.deps.as_ref(), self.env.clone(), msg)
+            .map_err(|e| e.to_string())?;
+        Ok(from_binary::<GetUserCountResponse>(&res).unwrap().count)
+    }
+}
+
+struct Account {
+    viewing_key: String,
+    permit: Permit<secret_counter::msg::CounterContractPermissions>,
+}
+
+/// secret-counter on mock dependencies, with a viewing key and a permit for
+/// each of its users.
+pub struct SecretCounter {
+    deps: secret_std::OwnedDeps<
+        secret_std::testing::MockStorage,
+        secret_std::testing::MockApi,
+        secret_std::testing::MockQuerier,
+    >,
+    env: secret_std::Env,
+    accounts: BTreeMap<String, Account>,
+}
+
+impl SecretCounter {
+    /// Sets a viewing key for each of `users` before anything else runs, and
+    /// signs each a permit for the contract.
+    pub fn new(users: &[PermitSigner]) -> Self {
+        use secret_counter::msg::{CounterContractPermissions, ExecuteMsg};
+
+        let mut counter = SecretCounter {
+            deps: secret_std::testing::mock_dependencies(),
+            env: secret_std::testing::mock_env(),
+            accounts: BTreeMap::new(),
+        };
+        for (i, user) in users.iter().enumerate() {
+            let viewing_key = format!("key-{}", i);
+            let info = secret_std::testing::mock_info(&user.address(), &[]);
+            let msg = ExecuteMsg::SetViewingKey {
+                key: viewing_key.clone(),
+            };
+            secret_counter::contract::execute(
+                counter.deps.as_mut(),
+                counter.env.clone(),
+                info,
+                msg,
+            )
+            .unwrap();
+            let permit = user.sign(PermitParams {
+                allowed_tokens: vec![counter.env.contract.address.to_string()],
+                permit_name: "counter".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![CounterContractPermissions::UserCount],
+            });
+            counter.accounts.insert(
+                user.address(),
+                Account {
+                    viewing_key,
+                    permit,
+                },
+            );
+        }
+        counter
+    }
+
+    pub fn instantiate(&mut self, sender: &str, count: i32) -> Result<Reply, String> {
+        use secret_counter::msg::InstantiateMsg;
+
+        let info = secret_std::testing::mock_info(sender, &[]);
+        let msg = InstantiateMsg {
+            count,
+            prng_seed: secret_std::Binary::from(b"seed"),
+        };
+        secret_counter::contract::instantiate(self.deps.as_mut(), self.env.clone(), info, msg)
+            .map(Reply::from)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn increment(&mut self, sender: &str) -> Result<Reply, String> {
+        use secret_counter::msg::ExecuteMsg;
+
+        let info = secret_std::testing::mock_info(sender, &[]);
+        let msg = ExecuteMsg::Increment {};
+        secret_counter::contract::execute(self.deps.as_mut(), self.env.clone(), info, msg)
+            .map(Reply::from)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn count(&self) -> Result<i32, String> {
+        use secret_counter::msg::{GetCountResponse, QueryMsg};
+
+        let res = self.query(QueryMsg::GetCount {})?;
+        Ok(secret_std::from_binary::<GetCountResponse>(&res)
+            .unwrap()
+            .count)
+    }
+
+    /// `user`'s count, read with their permit.
+    pub fn user_count(&self, user: &str) -> Result<i32, String> {
+        use secret_counter::msg::{GetUserCountResponse, QueryMsg, QueryWithPermit};
+
+        let msg = QueryMsg::WithPermit {
+            permit: self.account(user)?.permit.clone(),
+            query: QueryWithPermit::GetUserCount {},
+        };
+        let res = self.query(msg)?;
+        Ok(secret_std::from_binary::<GetUserCountResponse>(&res)
+            .unwrap()
+            .count)
+    }
+
+    /// `user`'s count, read with their viewing key.
+    pub fn user_count_with_viewing_key(&self, user: &str) -> Result<i32, String> {
+        use secret_counter::msg::{GetUserCountResponse, QueryMsg};
+
+        let msg = QueryMsg::GetUserCount {
+            addr: secret_std::Addr::unchecked(user),
+            key: self.account(user)?.viewing_key.clone(),
+        };
+        let res = self.query(msg)?;
+        Ok(secret_std::from_binary::<GetUserCountResponse>(&res)
+            .unwrap()
+            .count)
+    }
+
+    fn account(&self, user: &str) -> Result<&Account, String> {
+        self.accounts
+            .get(user)
+            .ok_or_else(|| format!("{} is not one of the counter's users", user))
+    }
+
+    fn query(&self, msg: secret_counter::msg::QueryMsg) -> Result<secret_std::Binary, String> {
+        secret_counter::contract::query(self.deps.as_ref(), self.env.clone(), msg)
+            .map_err(|e| e.to_string())
+    }
+}
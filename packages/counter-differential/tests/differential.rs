@@ -0,0 +1,67 @@
+//! Random sequences of instantiate, increment and query calls, run against
+//! both counters. Every answer must match, apart from the differences the
+//! crate docs list.
+
+use counter_differential::{CwCounter, SecretCounter};
+use proptest::prelude::*;
+use secret_permit_signer::PermitSigner;
+
+const USERS: usize = 4;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Instantiate(usize, i32),
+    Increment(usize),
+    Count,
+    UserCount(usize),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let user = 0..USERS;
+    prop_oneof![
+        1 => (user.clone(), -1000i32..1000).prop_map(|(u, count)| Op::Instantiate(u, count)),
+        4 => user.clone().prop_map(Op::Increment),
+        1 => Just(Op::Count),
+        2 => user.prop_map(Op::UserCount),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    // sequences may query or increment before instantiating, and instantiate
+    // again: both contracts should fail, or reset, the same way
+    #[test]
+    fn secret_counter_answers_like_cw_counter(ops in prop::collection::vec(op(), 0..48)) {
+        let signers: Vec<_> = (0..USERS)
+            .map(|i| PermitSigner::from_seed(&[i as u8]))
+            .collect();
+        let users: Vec<_> = signers.iter().map(PermitSigner::address).collect();
+        let mut cw = CwCounter::new();
+        let mut secret = SecretCounter::new(&signers);
+
+        for op in ops {
+            match op {
+                Op::Instantiate(u, count) => prop_assert_eq!(
+                    cw.instantiate(&users[u], count).ok(),
+                    secret.instantiate(&users[u], count).ok()
+                ),
+                Op::Increment(u) => prop_assert_eq!(
+                    cw.increment(&users[u]).ok(),
+                    secret.increment(&users[u]).ok()
+                ),
+                Op::Count => prop_assert_eq!(cw.count().ok(), secret.count().ok()),
+                Op::UserCount(u) => {
+                    let count = secret.user_count(&users[u]);
+                    prop_assert_eq!(&count, &secret.user_count_with_viewing_key(&users[u]));
+                    match cw.user_count(&users[u]) {
+                        Ok(expected) => prop_assert_eq!(Ok(expected), count),
+                        // cw-counter has no entry for users who never
+                        // incremented, secret-counter reports 0
+                        Err(_) => prop_assert_eq!(Ok(0), count),
+                    }
+                }
+            }
+        }
+    }
+}
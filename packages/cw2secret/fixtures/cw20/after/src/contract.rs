@@ -0,0 +1,173 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    from_binary, to_binary, Binary, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Response,
+    StdResult, Uint128,
+};
+use cw20::Cw20Coin;
+use secret_toolkit::snip20::{register_receive_msg, send_msg, transfer_msg};
+
+use crate::error::ContractError;
+use crate::msg::{
+    BalanceResponse, ExecuteMsg, InstantiateMsg, QueryMsg, ReceiveMsg, Snip20ReceiveMsg,
+};
+use crate::state::{Config, BALANCES, CONFIG};
+
+/// SNIP-20 messages are padded to a multiple of this many bytes, so their
+/// length doesn't give away which one was sent.
+const BLOCK_SIZE: usize = 256;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let register = register_receive_msg(
+        env.contract.code_hash.clone(),
+        None,
+        BLOCK_SIZE,
+        msg.token_code_hash.clone(),
+        msg.token.clone(),
+    )?;
+    let config = Config {
+        token: deps.api.addr_validate(&msg.token)?,
+        token_code_hash: msg.token_code_hash.clone(),
+    };
+    CONFIG.save(deps.storage, &config)?;
+    Ok(Response::new().add_message(register).add_attribute("method", "instantiate"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::Receive(wrapper) => receive(deps, info, wrapper),
+        ExecuteMsg::Withdraw { amount } => withdraw(deps, info, amount),
+    }
+}
+
+pub fn receive(
+    deps: DepsMut,
+    info: MessageInfo,
+    wrapper: Snip20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.token {
+        return Err(ContractError::Unauthorized {});
+    }
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    match from_binary(&wrapper.msg.unwrap_or_default())? {
+        ReceiveMsg::Deposit {} => {
+            BALANCES.update(deps.storage, &sender, |balance| -> StdResult<_> {
+                Ok(balance.unwrap_or_default() + wrapper.amount)
+            })?;
+        }
+    }
+    Ok(Response::new().add_attribute("action", "deposit"))
+}
+
+pub fn withdraw(
+    deps: DepsMut,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    BALANCES.update(deps.storage, &info.sender, |balance| {
+        balance
+            .unwrap_or_default()
+            .checked_sub(amount)
+            .map_err(|_| ContractError::InsufficientFunds {})
+    })?;
+
+    let transfer = transfer_msg(
+        info.sender.to_string(),
+        amount,
+        None,
+        None,
+        BLOCK_SIZE,
+        config.token_code_hash.clone(),
+        config.token.to_string(),
+    )?;
+    Ok(Response::new()
+        .add_message(transfer)
+        .add_attribute("action", "withdraw"))
+}
+
+/// Sends tokens the contract holds on to another contract.
+pub fn forward(
+    token: String,
+    token_code_hash: String,
+    contract: String,
+    amount: Uint128,
+    msg: Binary,
+) -> StdResult<CosmosMsg> {
+    let send = send_msg(
+        contract,
+        amount,
+        Some(msg),
+        None,
+        None,
+        BLOCK_SIZE,
+        token_code_hash,
+        token,
+    )?;
+    Ok(send)
+}
+
+/// The balances to seed a fresh token with.
+pub fn initial_balances(deps: Deps) -> StdResult<Vec<Cw20Coin>> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(vec![Cw20Coin {
+        address: config.token.to_string(),
+        amount: Uint128::zero(),
+    }])
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Balance { address } => {
+            let address = deps.api.addr_validate(&address)?;
+            let balance = BALANCES
+                .may_load(deps.storage, &address)?
+                .unwrap_or_default();
+            to_binary(&BalanceResponse { balance })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+    #[test]
+    fn deposit_and_withdraw() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            token: "token".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let receive = ExecuteMsg::Receive(Snip20ReceiveMsg {
+            from: "alice".to_string(),
+            sender: "alice".to_string(),
+            amount: Uint128::new(100),
+            memo: None,
+            msg: Some(to_binary(&ReceiveMsg::Deposit {}).unwrap()),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("token", &[]), receive).unwrap();
+
+        let withdraw = ExecuteMsg::Withdraw {
+            amount: Uint128::new(40),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), withdraw).unwrap();
+        assert_eq!(1, res.messages.len());
+    }
+}
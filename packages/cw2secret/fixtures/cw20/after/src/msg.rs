@@ -0,0 +1,47 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Binary, Uint128};
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    /// The CW20 token the vault holds.
+    pub token: String,
+    pub token_code_hash: String,
+}
+
+#[cw_serde]
+pub enum ExecuteMsg {
+    /// Deposits the tokens sent with a CW20 `Send`.
+    Receive(Snip20ReceiveMsg),
+    Withdraw {
+        amount: Uint128,
+    },
+}
+
+/// What a SNIP-20 token sends with `Send` and `SendFrom`: `sender` sent the tokens,
+/// from the account of `from`.
+#[cw_serde]
+pub struct Snip20ReceiveMsg {
+    pub sender: String,
+    pub from: String,
+    pub amount: Uint128,
+    pub memo: Option<String>,
+    pub msg: Option<Binary>,
+}
+
+/// Messages sent along with the tokens.
+#[cw_serde]
+pub enum ReceiveMsg {
+    Deposit {},
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    #[returns(BalanceResponse)]
+    Balance { address: String },
+}
+
+#[cw_serde]
+pub struct BalanceResponse {
+    pub balance: Uint128,
+}
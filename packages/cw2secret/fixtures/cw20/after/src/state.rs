@@ -0,0 +1,12 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Uint128};
+use cw_storage_plus::{Item, Map};
+
+#[cw_serde]
+pub struct Config {
+    pub token: Addr,
+    pub token_code_hash: String,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+pub const BALANCES: Map<&Addr, Uint128> = Map::new("balances");
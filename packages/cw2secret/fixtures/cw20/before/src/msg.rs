@@ -0,0 +1,36 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::Uint128;
+use cw20::Cw20ReceiveMsg;
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    /// The CW20 token the vault holds.
+    pub token: String,
+}
+
+#[cw_serde]
+pub enum ExecuteMsg {
+    /// Deposits the tokens sent with a CW20 `Send`.
+    Receive(Cw20ReceiveMsg),
+    Withdraw {
+        amount: Uint128,
+    },
+}
+
+/// Messages sent along with the tokens.
+#[cw_serde]
+pub enum ReceiveMsg {
+    Deposit {},
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    #[returns(BalanceResponse)]
+    Balance { address: String },
+}
+
+#[cw_serde]
+pub struct BalanceResponse {
+    pub balance: Uint128,
+}
@@ -0,0 +1,1346 @@
+//! Generates viewing-key and query-permit support for a contract.
+//!
+//! Given the `QueryMsg` variants to protect, this emits what steps 5b and 5c of
+//! the migration pathway write by hand: the `SetViewingKey`/`CreateViewingKey`
+//! messages and `ExecuteAnswer`, a `key` on every protected query, the
+//! `WithPermit` query with its `QueryWithPermit` mirror and permissions enum,
+//! `get_validation_params`, the `contract.rs` dispatch and handlers, and a unit
+//! test per protected query.
+
+use std::collections::HashSet;
+
+use syn::visit::{self, Visit};
+use syn::{Expr, Fields, Item, ItemEnum, ItemFn, ItemMod, Pat};
+
+use crate::error::MigrateError;
+use crate::imports::{end_of_uses, extend_use, find_use, imported_names, indentation, local_names};
+use crate::source::{
+    apply_edits, line_of, range_of, with_leading_whitespace, CrateSources, Edit, SourceFile,
+};
+use crate::transform::{Migration, Recorder};
+
+const RULE: &str = "auth";
+
+const WRONG_KEY: &str = "Wrong viewing key for this address or viewing key not set";
+
+/// What to protect and how to name the generated items.
+#[derive(Clone, Debug)]
+pub struct AuthOptions {
+    /// `QueryMsg` variants to protect, e.g. `GetUserCount`.
+    pub queries: Vec<String>,
+    /// Field of each protected query holding the address whose data it returns.
+    pub addr_field: String,
+    /// Name of the generated permissions enum, e.g. `CounterContractPermissions`.
+    pub permissions: String,
+}
+
+impl AuthOptions {
+    /// Options with the permissions enum named after the crate, so `cw-counter`
+    /// gets `CounterContractPermissions`.
+    pub fn new(queries: Vec<String>, crate_name: &str) -> Self {
+        AuthOptions {
+            queries,
+            addr_field: "addr".to_string(),
+            permissions: permissions_name(crate_name),
+        }
+    }
+}
+
+/// `cw-counter` → `CounterContractPermissions`.
+pub fn permissions_name(crate_name: &str) -> String {
+    let mut words: Vec<&str> = crate_name
+        .split(['-', '_'])
+        .filter(|w| !w.is_empty())
+        .collect();
+    if matches!(words.first(), Some(&"cw") | Some(&"secret")) && words.len() > 1 {
+        words.remove(0);
+    }
+    if words.last() != Some(&"contract") {
+        words.push("contract");
+    }
+    words.push("permissions");
+    words.iter().map(|w| capitalize(w)).collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// `GetUserCount` → `UserCount`.
+fn permission_variant(query: &str) -> String {
+    match query.strip_prefix("Get") {
+        Some(rest) if rest.starts_with(char::is_uppercase) => rest.to_string(),
+        _ => query.to_string(),
+    }
+}
+
+/// `UserCount` → `user count`.
+fn words(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push(' ');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+/// `GetUserCount` → `get_user_count`.
+fn snake(name: &str) -> String {
+    words(name).replace(' ', "_")
+}
+
+/// A `QueryMsg` variant being put behind authentication.
+struct Protected {
+    name: String,
+    /// `#[returns(..)]` attribute of the variant, if `QueryMsg` derives `QueryResponses`.
+    returns: Option<String>,
+    /// Fields other than the address, e.g. `start_after: Option<u32>`.
+    other_fields: Vec<String>,
+    other_names: Vec<String>,
+}
+
+pub fn scaffold_auth(
+    krate: &CrateSources,
+    options: &AuthOptions,
+) -> Result<Migration, MigrateError> {
+    let mut rec = Recorder::new(RULE);
+
+    let (msg_file, query_msg) =
+        find_enum(krate, "QueryMsg").ok_or_else(|| scaffold_error("no `QueryMsg` enum found"))?;
+    let contract = krate
+        .files
+        .iter()
+        .find(|f| find_fn(&f.ast.items, "query").is_some())
+        .ok_or_else(|| scaffold_error("no `query` entry point found"))?;
+
+    let protected = protected_queries(msg_file, query_msg, options)?;
+    let names: HashSet<&str> = protected.iter().map(|p| p.name.as_str()).collect();
+
+    scaffold_msg(msg_file, query_msg, &protected, options, &mut rec)?;
+    scaffold_contract(contract, &protected, options, &mut rec)?;
+
+    for file in &krate.files {
+        let mut literals = Literals {
+            protected: &names,
+            instantiate: vec![],
+            queries: vec![],
+        };
+        literals.visit_file(&file.ast);
+        let binary = if local_names(&file.ast).contains("Binary") {
+            "Binary"
+        } else {
+            "cosmwasm_std::Binary"
+        };
+        for literal in literals.instantiate {
+            if let Some(edit) = prng_seed_edit(file, literal, binary) {
+                rec.edit(&file.path, edit);
+                rec.applied(
+                    &file.path,
+                    line_of(literal),
+                    "`InstantiateMsg` literal given a `prng_seed`",
+                );
+            }
+        }
+        for (line, name) in literals.queries {
+            rec.skipped(
+                &file.path,
+                line,
+                format!("`QueryMsg::{}` built without a viewing key", name),
+                "set a viewing key for the address and pass it as `key`",
+            );
+        }
+    }
+
+    rec.skipped(
+        &contract.path,
+        1,
+        "unit test for permit queries",
+        "permits have to be signed; add a test with a signed `Permit` by hand",
+    );
+    Ok(rec.finish(krate))
+}
+
+fn scaffold_error(message: impl Into<String>) -> MigrateError {
+    MigrateError::Scaffold(message.into())
+}
+
+pub(crate) fn find_enum<'a>(
+    krate: &'a CrateSources,
+    name: &str,
+) -> Option<(&'a SourceFile, &'a ItemEnum)> {
+    krate.files.iter().find_map(|file| {
+        file.ast.items.iter().find_map(|item| match item {
+            Item::Enum(e) if e.ident == name => Some((file, e)),
+            _ => None,
+        })
+    })
+}
+
+fn find_fn<'a>(items: &'a [Item], name: &str) -> Option<&'a ItemFn> {
+    items.iter().find_map(|item| match item {
+        Item::Fn(f) if f.sig.ident == name => Some(f),
+        _ => None,
+    })
+}
+
+fn find_mod<'a>(items: &'a [Item], name: &str) -> Option<&'a ItemMod> {
+    items.iter().find_map(|item| match item {
+        Item::Mod(m) if m.ident == name && m.content.is_some() => Some(m),
+        _ => None,
+    })
+}
+
+fn protected_queries(
+    file: &SourceFile,
+    query_msg: &ItemEnum,
+    options: &AuthOptions,
+) -> Result<Vec<Protected>, MigrateError> {
+    if options.queries.is_empty() {
+        return Err(scaffold_error("no queries to protect"));
+    }
+    options
+        .queries
+        .iter()
+        .map(|name| {
+            let variant = query_msg
+                .variants
+                .iter()
+                .find(|v| v.ident == name)
+                .ok_or_else(|| scaffold_error(format!("`QueryMsg::{}` not found", name)))?;
+            let Fields::Named(fields) = &variant.fields else {
+                return Err(scaffold_error(format!(
+                    "`QueryMsg::{}` needs named fields",
+                    name
+                )));
+            };
+            if !fields
+                .named
+                .iter()
+                .any(|f| f.ident.as_ref().is_some_and(|i| i == &options.addr_field))
+            {
+                return Err(scaffold_error(format!(
+                    "`QueryMsg::{}` has no `{}` field",
+                    name, options.addr_field
+                )));
+            }
+            let others: Vec<_> = fields
+                .named
+                .iter()
+                .filter(|f| f.ident.as_ref().is_some_and(|i| i != &options.addr_field))
+                .collect();
+            Ok(Protected {
+                name: name.clone(),
+                returns: variant
+                    .attrs
+                    .iter()
+                    .find(|a| a.path().is_ident("returns"))
+                    .map(|a| file.text_of(a).to_string()),
+                other_fields: others
+                    .iter()
+                    .map(|f| file.text_of(*f).to_string())
+                    .collect(),
+                other_names: others
+                    .iter()
+                    .filter_map(|f| f.ident.as_ref().map(|i| i.to_string()))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Prefixes every non-empty line of `block` with `indent`.
+fn indent_lines(block: &str, indent: &str) -> String {
+    block
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", indent, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A braced, comma-separated list: struct fields, enum variants or match arms.
+struct List {
+    open: usize,
+    close: usize,
+    /// End of the last element, including its trailing comma if any.
+    last_end: Option<usize>,
+    /// Start of the first element.
+    first_start: Option<usize>,
+    /// Whether a comma has to be added after the last element.
+    needs_comma: bool,
+}
+
+impl List {
+    fn new(braces: &syn::token::Brace) -> Self {
+        List {
+            open: braces.span.open().byte_range().end,
+            close: braces.span.close().byte_range().start,
+            last_end: None,
+            first_start: None,
+            needs_comma: false,
+        }
+    }
+
+    fn of<T: syn::spanned::Spanned, P: syn::spanned::Spanned>(
+        braces: &syn::token::Brace,
+        items: &syn::punctuated::Punctuated<T, P>,
+    ) -> Self {
+        let mut list = List::new(braces);
+        list.first_start = items.first().map(|i| range_of(i).start);
+        list.last_end = items.pairs().last().map(|pair| match pair.punct() {
+            Some(punct) => range_of(*punct).end,
+            None => range_of(*pair.value()).end,
+        });
+        list.needs_comma = !items.empty_or_trailing();
+        list
+    }
+
+    /// Appends `items`, each carrying its own trailing comma if it needs one.
+    /// One-line lists such as `{ addr: Addr }` stay on one line.
+    fn append(&self, text: &str, items: &[String]) -> Edit {
+        let inner = &text[self.open..self.close];
+        let single_line = !inner.contains('\n') && items.iter().all(|i| !i.contains('\n'));
+        if single_line {
+            let joined = items
+                .iter()
+                .map(|i| i.trim_end_matches(','))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return match self.last_end {
+                Some(end) if self.needs_comma => Edit::insert(end, format!(", {}", joined)),
+                Some(end) => Edit::insert(end, format!(" {}", joined)),
+                None => Edit::replace(self.open..self.close, format!(" {} ", joined)),
+            };
+        }
+
+        let indent = match self.first_start {
+            Some(start) => indentation(text, start).to_string(),
+            None => format!("{}    ", indentation(text, self.close)),
+        };
+        let mut out = String::new();
+        if self.needs_comma {
+            out.push(',');
+        }
+        for item in items {
+            out.push('\n');
+            out.push_str(indent_lines(item, &indent).trim_start());
+        }
+        let at = self.last_end.unwrap_or(self.open);
+        Edit::insert(at, out)
+    }
+}
+
+fn scaffold_msg(
+    file: &SourceFile,
+    query_msg: &ItemEnum,
+    protected: &[Protected],
+    options: &AuthOptions,
+    rec: &mut Recorder,
+) -> Result<(), MigrateError> {
+    let path = &file.path;
+    let items = &file.ast.items;
+    let permissions = &options.permissions;
+
+    // use cosmwasm_std::{Addr, Binary, Deps, StdResult};
+    // use secret_toolkit::permit::Permit;
+    let std_names = ["Addr", "Binary", "Deps", "StdResult"];
+    match find_use(items, "cosmwasm_std") {
+        Some(std) => {
+            if let Some(edit) = extend_use(std, &std_names) {
+                rec.edit(path, edit);
+            }
+            rec.edit(
+                path,
+                Edit::insert(range_of(std).end, "\nuse secret_toolkit::permit::Permit;"),
+            );
+        }
+        None => rec.edit(
+            path,
+            Edit::insert(
+                end_of_uses(items).unwrap_or(0),
+                "\nuse cosmwasm_std::{Addr, Binary, Deps, StdResult};\nuse secret_toolkit::permit::Permit;",
+            ),
+        ),
+    }
+
+    // InstantiateMsg { .., prng_seed: Binary }
+    let instantiate = items
+        .iter()
+        .find_map(|item| match item {
+            Item::Struct(s) if s.ident == "InstantiateMsg" => Some(s),
+            _ => None,
+        })
+        .ok_or_else(|| scaffold_error("no `InstantiateMsg` struct found"))?;
+    let Fields::Named(fields) = &instantiate.fields else {
+        return Err(scaffold_error("`InstantiateMsg` needs named fields"));
+    };
+    rec.edit(
+        path,
+        List::of(&fields.brace_token, &fields.named)
+            .append(&file.text, &["pub prng_seed: Binary,".to_string()]),
+    );
+    rec.applied(
+        path,
+        line_of(&instantiate.ident),
+        "`InstantiateMsg.prng_seed` for viewing key generation",
+    );
+
+    // ExecuteMsg::{CreateViewingKey, SetViewingKey} and ExecuteAnswer
+    let execute_msg = items
+        .iter()
+        .find_map(|item| match item {
+            Item::Enum(e) if e.ident == "ExecuteMsg" => Some(e),
+            _ => None,
+        })
+        .ok_or_else(|| scaffold_error("no `ExecuteMsg` enum found"))?;
+    rec.edit(
+        path,
+        List::of(&execute_msg.brace_token, &execute_msg.variants).append(
+            &file.text,
+            &[
+                "CreateViewingKey { entropy: String },".to_string(),
+                "SetViewingKey { key: String },".to_string(),
+            ],
+        ),
+    );
+    let serde_attrs = attrs_text(file, &execute_msg.attrs);
+    rec.edit(
+        path,
+        Edit::insert(
+            range_of(execute_msg).end,
+            format!(
+                "\n\n{}\npub enum ExecuteAnswer {{\n    // Native\n    CreateViewingKey {{ key: String }},\n}}",
+                serde_attrs
+            ),
+        ),
+    );
+    rec.applied(
+        path,
+        line_of(&execute_msg.ident),
+        "`ExecuteMsg::{CreateViewingKey, SetViewingKey}` and `ExecuteAnswer`",
+    );
+
+    // QueryMsg: a key on every protected query, plus WithPermit
+    for variant in query_msg
+        .variants
+        .iter()
+        .filter(|v| protected.iter().any(|p| v.ident == p.name))
+    {
+        if let Fields::Named(fields) = &variant.fields {
+            rec.edit(
+                path,
+                List::of(&fields.brace_token, &fields.named)
+                    .append(&file.text, &["key: String".to_string()]),
+            );
+        }
+        rec.applied(
+            path,
+            line_of(&variant.ident),
+            format!("`QueryMsg::{}` takes a viewing key", variant.ident),
+        );
+    }
+    let returns = if protected.iter().any(|p| p.returns.is_some()) {
+        "#[returns(QueryWithPermit)]\n"
+    } else {
+        ""
+    };
+    rec.edit(
+        path,
+        List::of(&query_msg.brace_token, &query_msg.variants).append(
+            &file.text,
+            &[format!(
+                "{}WithPermit {{\n    permit: Permit<{}>,\n    query: QueryWithPermit,\n}},",
+                returns, permissions
+            )],
+        ),
+    );
+
+    // QueryWithPermit mirrors the protected queries without address and key
+    let mut mirror = String::new();
+    for query in protected {
+        if let Some(returns) = &query.returns {
+            mirror.push_str(&format!("    {}\n", returns));
+        }
+        let fields = if query.other_fields.is_empty() {
+            "{}".to_string()
+        } else {
+            format!("{{ {} }}", query.other_fields.join(", "))
+        };
+        mirror.push_str(&format!("    {} {},\n", query.name, fields));
+    }
+    rec.edit(
+        path,
+        Edit::insert(
+            range_of(query_msg).end,
+            format!(
+                "\n\n{}\npub enum QueryWithPermit {{\n{}}}",
+                attrs_text(file, &query_msg.attrs),
+                mirror
+            ),
+        ),
+    );
+    rec.applied(
+        path,
+        line_of(&query_msg.ident),
+        "`QueryMsg::WithPermit` and `QueryWithPermit`",
+    );
+
+    // get_validation_params and the permissions enum
+    let mut arms = String::new();
+    for query in protected {
+        let rest = if query.other_names.is_empty() {
+            ""
+        } else {
+            ", .."
+        };
+        arms.push_str(&format!(
+            "            Self::{} {{ {addr}, key{} }} => {{\n                let address = deps.api.addr_validate({addr}.as_str())?;\n                Ok((vec![address], key.clone()))\n            }}\n",
+            query.name,
+            rest,
+            addr = options.addr_field
+        ));
+    }
+    let mut variants: Vec<String> = protected
+        .iter()
+        .map(|p| permission_variant(&p.name))
+        .collect();
+    variants.push("Owner".to_string());
+    let variants: String = variants.iter().map(|v| format!("    {},\n", v)).collect();
+    rec.edit(
+        path,
+        Edit::insert(
+            file.text.len(),
+            format!(
+                "\nimpl QueryMsg {{\n    pub fn get_validation_params(&self, deps: Deps) -> StdResult<(Vec<Addr>, String)> {{\n        match self {{\n{}            _ => panic!(\"This query type does not require authentication\"),\n        }}\n    }}\n}}\n\n{}\npub enum {} {{\n{}}}\n",
+                arms, serde_attrs, permissions, variants
+            ),
+        ),
+    );
+    rec.applied(
+        path,
+        file.text.lines().count(),
+        format!("`QueryMsg::get_validation_params` and `{}`", permissions),
+    );
+    Ok(())
+}
+
+/// Attributes other than `derive`s added by this module, one per line.
+fn attrs_text(file: &SourceFile, attrs: &[syn::Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|a| !matches!(a.style, syn::AttrStyle::Inner(_)))
+        .filter(|a| !a.path().is_ident("doc"))
+        .map(|a| file.text_of(a))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The named parameter of `sig` with type `ty`, renamed if it starts with `_`.
+fn param(file: &SourceFile, sig: &syn::Signature, ty: &str, rec: &mut Recorder) -> Option<String> {
+    sig.inputs.iter().find_map(|input| {
+        let syn::FnArg::Typed(typed) = input else {
+            return None;
+        };
+        let syn::Type::Path(path) = &*typed.ty else {
+            return None;
+        };
+        if path.path.segments.last().is_none_or(|s| s.ident != ty) {
+            return None;
+        }
+        let Pat::Ident(pat) = &*typed.pat else {
+            return None;
+        };
+        let name = pat.ident.to_string();
+        let used = name.trim_start_matches('_').to_string();
+        if used != name {
+            rec.edit(
+                &file.path,
+                Edit::replace(range_of(&pat.ident), used.clone()),
+            );
+        }
+        Some(used)
+    })
+}
+
+/// The `match <msg> { .. }` dispatching on a handler's message parameter.
+fn dispatch<'a>(func: &'a ItemFn, msg: &str) -> Option<&'a syn::ExprMatch> {
+    func.block.stmts.iter().find_map(|stmt| match stmt {
+        syn::Stmt::Expr(Expr::Match(m), _) => match &*m.expr {
+            Expr::Path(p) if p.path.is_ident(msg) => Some(m),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn match_list(m: &syn::ExprMatch) -> List {
+    let mut list = List::new(&m.brace_token);
+    list.first_start = m.arms.first().map(|a| range_of(a).start);
+    list.last_end = m.arms.last().map(|arm| match &arm.comma {
+        Some(comma) => range_of(comma).end,
+        None => range_of(arm).end,
+    });
+    list.needs_comma = m
+        .arms
+        .last()
+        .is_some_and(|arm| arm.comma.is_none() && !matches!(&*arm.body, Expr::Block(_)));
+    list
+}
+
+/// Inserts functions into `mod <name>` after its imports, or after `anchor` at
+/// the top level when the contract doesn't group its handlers in modules.
+/// Returns the path prefix handlers are called with.
+fn insert_handlers(
+    file: &SourceFile,
+    module: Option<&ItemMod>,
+    anchor: &ItemFn,
+    imports: &[String],
+    functions: &[String],
+    rec: &mut Recorder,
+) -> String {
+    let (at, indent, prefix) = match module {
+        Some(m) => {
+            let items = &m.content.as_ref().unwrap().1;
+            let brace = &m.content.as_ref().unwrap().0;
+            let at = end_of_uses(items).unwrap_or(brace.span.open().byte_range().end);
+            (at, "    ", format!("{}::", m.ident))
+        }
+        None => (range_of(anchor).end, "", String::new()),
+    };
+    for import in imports {
+        rec.edit(
+            &file.path,
+            Edit::insert(at, format!("\n{}{}", indent, import)),
+        );
+    }
+    for function in functions {
+        rec.edit(
+            &file.path,
+            Edit::insert(at, format!("\n\n{}", indent_lines(function, indent))),
+        );
+    }
+    prefix
+}
+
+fn scaffold_contract(
+    file: &SourceFile,
+    protected: &[Protected],
+    options: &AuthOptions,
+    rec: &mut Recorder,
+) -> Result<(), MigrateError> {
+    let path = &file.path;
+    let items = &file.ast.items;
+    let permissions = &options.permissions;
+
+    // imports and the revoked-permits prefix
+    if let Some(std) = find_use(items, "cosmwasm_std") {
+        let names = [
+            "to_binary",
+            "Binary",
+            "Deps",
+            "DepsMut",
+            "Env",
+            "MessageInfo",
+            "Response",
+            "StdResult",
+        ];
+        if let Some(edit) = extend_use(std, &names) {
+            rec.edit(path, edit);
+        }
+        rec.edit(
+            path,
+            Edit::insert(range_of(std).end, "\nuse secret_toolkit::crypto::sha_256;"),
+        );
+    }
+    if let Some(msg) = find_use(items, "crate::msg") {
+        if let Some(edit) = extend_use(msg, &["ExecuteAnswer"]) {
+            rec.edit(path, edit);
+        }
+    }
+    rec.edit(
+        path,
+        Edit::insert(
+            end_of_uses(items).unwrap_or(0),
+            "\n\nuse secret_toolkit::permit::Permit;\nuse secret_toolkit::viewing_key::{ViewingKey, ViewingKeyStore};\n\nconst PREFIX_REVOKED_PERMITS: &str = \"prefix_revoked_permits\";",
+        ),
+    );
+
+    // instantiate: seed the viewing key generator
+    let instantiate = find_fn(items, "instantiate")
+        .ok_or_else(|| scaffold_error("no `instantiate` entry point found"))?;
+    let deps = param(file, &instantiate.sig, "DepsMut", rec).unwrap_or_else(|| "deps".into());
+    let msg = param(file, &instantiate.sig, "InstantiateMsg", rec).unwrap_or_else(|| "msg".into());
+    if let Some(tail) = instantiate.block.stmts.last() {
+        let start = range_of(tail).start;
+        let indent = indentation(&file.text, start);
+        rec.edit(
+            path,
+            Edit::insert(
+                start,
+                format!(
+                    "let prng_seed_hashed = sha_256(&{msg}.prng_seed.0);\n{indent}ViewingKey::set_seed({deps}.storage, &prng_seed_hashed);\n\n{indent}"
+                ),
+            ),
+        );
+        rec.applied(
+            path,
+            line_of(&instantiate.sig.ident),
+            "`instantiate` sets the viewing key seed",
+        );
+    }
+
+    // execute: dispatch and handlers for the viewing key messages
+    let execute = find_fn(items, "execute")
+        .ok_or_else(|| scaffold_error("no `execute` entry point found"))?;
+    let error = match &execute.sig.output {
+        syn::ReturnType::Type(_, ty) => match &**ty {
+            syn::Type::Path(p) => p
+                .path
+                .segments
+                .last()
+                .and_then(|s| match &s.arguments {
+                    syn::PathArguments::AngleBracketed(args) => args.args.iter().nth(1),
+                    _ => None,
+                })
+                .map(|e| file.text_of(e).to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+    .unwrap_or_else(|| "ContractError".to_string());
+    param(file, &execute.sig, "Env", rec);
+    param(file, &execute.sig, "MessageInfo", rec);
+    let execute_msg = param(file, &execute.sig, "ExecuteMsg", rec).unwrap_or_else(|| "msg".into());
+    let prefix = insert_handlers(
+        file,
+        find_mod(items, "execute"),
+        execute,
+        &[],
+        &[
+            format!(
+                "pub fn try_create_key(\n    deps: DepsMut,\n    env: Env,\n    info: MessageInfo,\n    entropy: String,\n) -> Result<Response, {error}> {{\n    let key = ViewingKey::create(\n        deps.storage,\n        &info,\n        &env,\n        info.sender.as_str(),\n        entropy.as_ref(),\n    );\n\n    Ok(Response::new().set_data(to_binary(&ExecuteAnswer::CreateViewingKey {{ key }})?))\n}}"
+            ),
+            format!(
+                "pub fn try_set_key(\n    deps: DepsMut,\n    info: MessageInfo,\n    key: String,\n) -> Result<Response, {error}> {{\n    ViewingKey::set(deps.storage, info.sender.as_str(), key.as_str());\n    Ok(Response::new())\n}}"
+            ),
+        ],
+        rec,
+    );
+    let dispatch_match = dispatch(execute, &execute_msg)
+        .ok_or_else(|| scaffold_error("`execute` doesn't `match` on its message"))?;
+    rec.edit(
+        path,
+        match_list(dispatch_match).append(
+            &file.text,
+            &[
+                format!("ExecuteMsg::SetViewingKey {{ key }} => {prefix}try_set_key(deps, info, key),"),
+                format!("ExecuteMsg::CreateViewingKey {{ entropy, .. }} => {{\n    {prefix}try_create_key(deps, env, info, entropy)\n}}"),
+            ],
+        ),
+    );
+    rec.applied(
+        path,
+        line_of(&execute.sig.ident),
+        "`execute` handles `SetViewingKey` and `CreateViewingKey`",
+    );
+
+    // query: route protected queries through viewing keys and permits
+    let query = find_fn(items, "query").unwrap();
+    param(file, &query.sig, "Env", rec);
+    let query_msg = param(file, &query.sig, "QueryMsg", rec).unwrap_or_else(|| "msg".into());
+    let query_match = dispatch(query, &query_msg)
+        .ok_or_else(|| scaffold_error("`query` doesn't `match` on its message"))?;
+    let query_mod = find_mod(items, "query");
+
+    let mut vk_arms = String::new();
+    let mut permit_arms = String::new();
+    for query in protected {
+        let Some(arm) = query_match
+            .arms
+            .iter()
+            .find(|arm| arm_variant(&arm.pat).as_ref() == Some(&query.name))
+        else {
+            rec.skipped(
+                path,
+                line_of(&query_match.match_token),
+                format!("`QueryMsg::{}` dispatch", query.name),
+                "no match arm found; route it through `viewing_keys_queries` by hand",
+            );
+            continue;
+        };
+        let end = arm
+            .comma
+            .as_ref()
+            .map_or(range_of(arm).end, |c| range_of(c).end);
+        rec.edit(
+            path,
+            Edit::delete(with_leading_whitespace(
+                &file.text,
+                range_of(arm).start..end,
+            )),
+        );
+
+        let body = dedent(file, &arm.body);
+        vk_arms.push_str(&format!(
+            "            {} => {{\n                Ok({}?)\n            }}\n",
+            pattern_with_rest(file, &arm.pat),
+            indent_lines(&body, "                ").trim_start()
+        ));
+
+        let bindings = if query.other_names.is_empty() {
+            "{}".to_string()
+        } else {
+            format!("{{ {} }}", query.other_names.join(", "))
+        };
+        let body = permit_body(file, &arm.body, &options.addr_field, query_mod);
+        let permission = permission_variant(&query.name);
+        permit_arms.push_str(&format!(
+            "        QueryWithPermit::{} {} => {{\n            if !permit.check_permission(&{permissions}::{permission}) {{\n                return Err(StdError::generic_err(format!(\n                    \"No permission to query {}, got permissions {{:?}}\",\n                    permit.params.permissions\n                )));\n            }}\n            let address = Addr::unchecked(account);\n\n            {}\n        }}\n",
+            query.name,
+            bindings,
+            words(&permission),
+            indent_lines(&body, "            ").trim_start()
+        ));
+    }
+
+    let mut query_imports = vec![];
+    let mut missing_std = vec![];
+    let module_names = query_mod
+        .map(|m| imported_names(&m.content.as_ref().unwrap().1))
+        .unwrap_or_default();
+    for name in ["Addr", "StdError"] {
+        if !module_names.contains(name) && !local_names(&file.ast).contains(name) {
+            missing_std.push(name);
+        }
+    }
+    match query_mod.and_then(|m| find_use(&m.content.as_ref().unwrap().1, "cosmwasm_std")) {
+        Some(std) if !missing_std.is_empty() => {
+            if let Some(edit) = extend_use(std, &missing_std) {
+                rec.edit(path, edit);
+            }
+        }
+        _ if !missing_std.is_empty() => {
+            query_imports.push(format!("use cosmwasm_std::{{{}}};", missing_std.join(", ")));
+        }
+        _ => {}
+    }
+    query_imports.push(format!(
+        "use crate::msg::{{{}, QueryWithPermit}};",
+        permissions
+    ));
+
+    let handler_prefix = insert_handlers(
+        file,
+        query_mod,
+        query,
+        &query_imports,
+        &[
+            format!(
+                "pub fn viewing_keys_queries(deps: Deps, msg: QueryMsg) -> StdResult<Binary> {{\n    let (addresses, key) = msg.get_validation_params(deps)?;\n\n    for addr in addresses {{\n        let result = ViewingKey::check(deps.storage, addr.as_str(), key.as_str());\n        if result.is_ok() {{\n            return match msg {{\n                // Base\n{}                _ => panic!(\"This query type does not require authentication\"),\n            }};\n        }}\n    }}\n\n    return Err(StdError::GenericErr {{\n        msg: \"{}\".to_string(),\n    }});\n}}",
+                vk_arms.lines().map(|l| format!("    {}\n", l)).collect::<String>().replace("    \n", "\n"),
+                WRONG_KEY
+            ),
+            format!(
+                "pub fn permit_queries(\n    deps: Deps,\n    env: Env,\n    permit: Permit<{permissions}>,\n    query: QueryWithPermit,\n) -> Result<Binary, StdError> {{\n    // Validate permit content\n    let contract_address = env.contract.address;\n\n    let account = secret_toolkit::permit::validate(\n        deps,\n        PREFIX_REVOKED_PERMITS,\n        &permit,\n        contract_address.into_string(),\n        None,\n    )?;\n\n    // Permit validated! We can now execute the query.\n    match query {{\n{}    }}\n}}",
+                permit_arms
+            ),
+        ],
+        rec,
+    );
+    rec.edit(
+        path,
+        match_list(query_match).append(
+            &file.text,
+            &[
+                format!("QueryMsg::WithPermit {{ permit, query }} => {{\n    {handler_prefix}permit_queries(\n        deps, // env is sometimes not needed\n        env, permit, query,\n    )\n}}"),
+                format!("_ => {handler_prefix}viewing_keys_queries(deps, {query_msg}),"),
+            ],
+        ),
+    );
+    rec.applied(
+        path,
+        line_of(&query.sig.ident),
+        "`query` routes protected queries through viewing keys and permits",
+    );
+
+    scaffold_tests(file, protected, options, rec);
+    Ok(())
+}
+
+/// `QueryMsg::GetUserCount { addr }` → `GetUserCount`.
+fn arm_variant(pat: &Pat) -> Option<String> {
+    let path = match pat {
+        Pat::Struct(s) => &s.path,
+        Pat::TupleStruct(t) => &t.path,
+        Pat::Path(p) => &p.path,
+        _ => return None,
+    };
+    let segments = &path.segments;
+    if segments.len() >= 2 && segments[segments.len() - 2].ident == "QueryMsg" {
+        segments.last().map(|s| s.ident.to_string())
+    } else {
+        None
+    }
+}
+
+/// `QueryMsg::GetUserCount { addr }` → `QueryMsg::GetUserCount { addr, .. }`
+fn pattern_with_rest(file: &SourceFile, pat: &Pat) -> String {
+    match pat {
+        Pat::Struct(s) if s.rest.is_none() => {
+            let mut fields: Vec<String> = s
+                .fields
+                .iter()
+                .map(|f| file.text_of(f).to_string())
+                .collect();
+            fields.push("..".to_string());
+            format!("{} {{ {} }}", file.text_of(&s.path), fields.join(", "))
+        }
+        pat => file.text_of(pat).to_string(),
+    }
+}
+
+/// Source of `expr` with its continuation lines moved to column zero.
+fn dedent(file: &SourceFile, expr: &Expr) -> String {
+    let range = range_of(expr);
+    let indent = indentation(&file.text, range.start).len();
+    file.slice(range)
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                line
+            } else {
+                line.get(indent..).unwrap_or(line.trim_start())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A query arm body rewritten for `permit_queries`: the address comes from the
+/// permit and handlers are called from inside the query module.
+fn permit_body(file: &SourceFile, body: &Expr, addr: &str, module: Option<&ItemMod>) -> String {
+    let range = range_of(body);
+    let mut edits = vec![];
+    collect_permit_edits(
+        quote::ToTokens::to_token_stream(body),
+        addr,
+        module.map(|m| m.ident.to_string()),
+        range.start,
+        &mut edits,
+    );
+    let rewritten = apply_edits(file.slice(range.clone()), edits);
+    let reparsed = SourceFile::parse(&file.path, format!("fn f() {{ {} }}", rewritten));
+    match reparsed {
+        Ok(_) => rewritten,
+        Err(_) => dedent(file, body),
+    }
+}
+
+fn collect_permit_edits(
+    tokens: proc_macro2::TokenStream,
+    addr: &str,
+    module: Option<String>,
+    base: usize,
+    edits: &mut Vec<Edit>,
+) {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            proc_macro2::TokenTree::Group(g) => {
+                collect_permit_edits(g.stream(), addr, module.clone(), base, edits)
+            }
+            proc_macro2::TokenTree::Ident(ident) => {
+                let range = ident.span().byte_range();
+                let relative = range.start - base..range.end - base;
+                let is_path = matches!(
+                    (tokens.get(i + 1), tokens.get(i + 2)),
+                    (Some(proc_macro2::TokenTree::Punct(a)), Some(proc_macro2::TokenTree::Punct(b)))
+                        if a.as_char() == ':' && b.as_char() == ':'
+                );
+                if module.as_deref().is_some_and(|m| ident == m) && is_path {
+                    let end = tokens[i + 2].span().byte_range().end - base;
+                    edits.push(Edit::delete(relative.start..end));
+                } else if ident == addr && !is_path {
+                    edits.push(Edit::replace(relative, "address"));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Finds `InstantiateMsg { .. }` literals and protected `QueryMsg` literals.
+struct Literals<'a, 'ast> {
+    protected: &'a HashSet<&'a str>,
+    instantiate: Vec<&'ast syn::ExprStruct>,
+    queries: Vec<(usize, String)>,
+}
+
+impl<'a, 'ast> Visit<'ast> for Literals<'a, 'ast> {
+    fn visit_expr_struct(&mut self, node: &'ast syn::ExprStruct) {
+        let segments = &node.path.segments;
+        if node.path.is_ident("InstantiateMsg") {
+            self.instantiate.push(node);
+        } else if segments.len() >= 2 && segments[segments.len() - 2].ident == "QueryMsg" {
+            let name = segments.last().unwrap().ident.to_string();
+            if self.protected.contains(name.as_str()) {
+                self.queries.push((line_of(node), name));
+            }
+        }
+        visit::visit_expr_struct(self, node);
+    }
+}
+
+fn prng_seed_edit(file: &SourceFile, literal: &syn::ExprStruct, binary: &str) -> Option<Edit> {
+    let has_seed = literal.fields.iter().any(|f| match &f.member {
+        syn::Member::Named(i) => i == "prng_seed",
+        syn::Member::Unnamed(_) => false,
+    });
+    if has_seed || literal.rest.is_some() {
+        return None;
+    }
+    Some(
+        List::of(&literal.brace_token, &literal.fields)
+            .append(&file.text, &[format!("prng_seed: {}::default(),", binary)]),
+    )
+}
+
+/// Appends a viewing key test per protected query to the contract's `mod tests`.
+fn scaffold_tests(
+    file: &SourceFile,
+    protected: &[Protected],
+    options: &AuthOptions,
+    rec: &mut Recorder,
+) {
+    let Some(tests) = find_mod(&file.ast.items, "tests") else {
+        rec.skipped(
+            &file.path,
+            1,
+            "viewing key unit tests",
+            "no `mod tests` in the contract",
+        );
+        return;
+    };
+    let (brace, items) = tests.content.as_ref().unwrap();
+
+    let mut literals = Literals {
+        protected: &HashSet::new(),
+        instantiate: vec![],
+        queries: vec![],
+    };
+    literals.visit_item_mod(tests);
+    let Some(literal) = literals.instantiate.first() else {
+        rec.skipped(
+            &file.path,
+            line_of(tests),
+            "viewing key unit tests",
+            "no `InstantiateMsg` in the existing tests to copy",
+        );
+        return;
+    };
+    let range = range_of(*literal);
+    let instantiate = match prng_seed_edit(file, literal, "Binary") {
+        Some(edit) => {
+            let shifted = Edit::replace(
+                edit.range.start - range.start..edit.range.end - range.start,
+                edit.replacement,
+            );
+            apply_edits(file.slice(range), vec![shifted])
+        }
+        None => file.slice(range).to_string(),
+    };
+    let instantiate = indent_lines(&instantiate, "    ");
+
+    if let Some(std) = find_use(items, "cosmwasm_std") {
+        if let Some(edit) = extend_use(std, &["coins", "Addr", "StdError"]) {
+            rec.edit(&file.path, edit);
+        }
+    }
+    if let Some(testing) = find_use(items, "cosmwasm_std::testing") {
+        if let Some(edit) = extend_use(testing, &["mock_dependencies", "mock_env", "mock_info"]) {
+            rec.edit(&file.path, edit);
+        }
+    }
+
+    let addr = &options.addr_field;
+    let mut out = String::new();
+    for query in protected.iter().filter(|q| q.other_names.is_empty()) {
+        out.push_str(&format!(
+            r#"
+
+    #[test]
+    fn {name}_with_viewing_key() {{
+        let mut deps = mock_dependencies();
+
+        let msg = {instantiate};
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("secret_user", &coins(2, "token"));
+        let msg = ExecuteMsg::SetViewingKey {{
+            key: "vk_1".to_string(),
+        }};
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let wrong_key = StdError::GenericErr {{
+            msg: "{wrong_key}".to_string(),
+        }};
+
+        // a viewing key only unlocks the address it was set for
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::{variant} {{
+                {addr}: Addr::unchecked("not_secret_user"),
+                key: "vk_1".to_string(),
+            }},
+        );
+        assert_eq!(res.unwrap_err(), wrong_key);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::{variant} {{
+                {addr}: Addr::unchecked("secret_user"),
+                key: "vk_1".to_string(),
+            }},
+        );
+        assert_ne!(res.err(), Some(wrong_key));
+    }}"#,
+            name = snake(&query.name),
+            instantiate = instantiate.trim_start(),
+            wrong_key = WRONG_KEY,
+            variant = query.name,
+        ));
+        rec.applied(
+            &file.path,
+            line_of(tests),
+            format!("unit test `{}_with_viewing_key`", snake(&query.name)),
+        );
+    }
+    for query in protected.iter().filter(|q| !q.other_names.is_empty()) {
+        rec.skipped(
+            &file.path,
+            line_of(tests),
+            format!("unit test for `QueryMsg::{}`", query.name),
+            "the query takes more than an address; write its test by hand",
+        );
+    }
+    let close = brace.span.close().byte_range().start;
+    rec.edit(
+        &file.path,
+        Edit::insert(with_leading_whitespace(&file.text, close..close).start, out),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::migrate_storage;
+    use std::path::{Path, PathBuf};
+
+    /// The `mock_dependencies` tests cw-counter had before they moved to
+    /// scenario files: the kind of tests the scaffolder patches and extends.
+    const MOCK_TESTS: &str = r#"#[cfg(test)]
+mod tests {
+    use crate::msg::GetUserCountResponse;
+
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coins, from_binary};
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 17 };
+        let info = mock_info("creator", &coins(1000, "earth"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // it worked, let's query the state
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        let value: GetCountResponse = from_binary(&res).unwrap();
+        assert_eq!(17, value.count);
+    }
+
+    #[test]
+    fn increment() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg { count: 17 };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // beneficiary can release it
+        let info = mock_info("secret_user", &coins(2, "token"));
+        let msg = ExecuteMsg::Increment {};
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // should increase counter by 1
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        let value: GetCountResponse = from_binary(&res).unwrap();
+        assert_eq!(18, value.count);
+
+        // should increase counter by 1
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetUserCount { addr: info.sender },
+        )
+        .unwrap();
+        let value: GetUserCountResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.count);
+    }
+}
+"#;
+
+    fn contract(name: &str) -> CrateSources {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../contracts")
+            .join(name);
+        CrateSources::load(root).unwrap()
+    }
+
+    fn tokens(node: &impl quote::ToTokens) -> String {
+        node.to_token_stream().to_string()
+    }
+
+    fn function(file: &syn::File, module: Option<&str>, name: &str) -> String {
+        let items = match module {
+            Some(module) => {
+                &find_mod(&file.items, module)
+                    .unwrap()
+                    .content
+                    .as_ref()
+                    .unwrap()
+                    .1
+            }
+            None => &file.items,
+        };
+        tokens(find_fn(items, name).unwrap())
+    }
+
+    /// cw-counter with [`MOCK_TESTS`] in place of its scenario tests.
+    fn cw_counter_with_mock_tests() -> CrateSources {
+        let cw = contract("cw-counter");
+        let text = &cw.file("src/contract.rs").unwrap().text;
+        let head = &text[..text.find("#[cfg(test)]").unwrap()];
+        let contract = (
+            PathBuf::from("src/contract.rs"),
+            format!("{}{}", head, MOCK_TESTS),
+        );
+        cw.with_changes([(&contract.0, &contract.1)]).unwrap()
+    }
+
+    fn scaffolded(cw: CrateSources) -> (CrateSources, Migration) {
+        let storage = migrate_storage(&cw);
+        let migrated = cw.with_changes(&storage.files).unwrap();
+        let options = AuthOptions::new(vec!["GetUserCount".to_string()], "cw-counter");
+        let auth = scaffold_auth(&migrated, &options).unwrap();
+        let scaffolded = migrated.with_changes(&auth.files).unwrap();
+        (scaffolded, auth)
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!("CounterContractPermissions", permissions_name("cw-counter"));
+        assert_eq!(
+            "VaultContractPermissions",
+            permissions_name("vault_contract")
+        );
+        assert_eq!("UserCount", permission_variant("GetUserCount"));
+        assert_eq!("Balance", permission_variant("Balance"));
+        assert_eq!("get_user_count", snake("GetUserCount"));
+    }
+
+    #[test]
+    fn msg_matches_secret_counter() {
+        let (scaffolded, _) = scaffolded(contract("cw-counter"));
+        let secret = contract("secret-counter");
+
+        let msg = &scaffolded.file("src/msg.rs").unwrap().ast;
+        let expected = &secret.file("src/msg.rs").unwrap().ast;
+        assert_eq!(tokens(expected), tokens(msg));
+    }
+
+    #[test]
+    fn contract_handlers_match_secret_counter() {
+        let (scaffolded, _) = scaffolded(contract("cw-counter"));
+        let secret = contract("secret-counter");
+
+        let contract = &scaffolded.file("src/contract.rs").unwrap().ast;
+        let expected = &secret.file("src/contract.rs").unwrap().ast;
+        for (module, name) in [
+            (None, "execute"),
+            (None, "query"),
+            (Some("execute"), "try_create_key"),
+            (Some("execute"), "try_set_key"),
+            (Some("query"), "viewing_keys_queries"),
+        ] {
+            assert_eq!(
+                function(expected, module, name),
+                function(contract, module, name),
+                "{}",
+                name
+            );
+        }
+
+        // secret-counter's error message was copied over from SNIP-20's balance query
+        let permit_queries = function(contract, Some("query"), "permit_queries");
+        assert_eq!(
+            function(expected, Some("query"), "permit_queries")
+                .replace("query balance", "query user count"),
+            permit_queries
+        );
+
+        let text = &scaffolded.file("src/contract.rs").unwrap().text;
+        assert!(text.contains("    let prng_seed_hashed = sha_256(&msg.prng_seed.0);\n    ViewingKey::set_seed(deps.storage, &prng_seed_hashed);\n"));
+        assert!(text.contains("const PREFIX_REVOKED_PERMITS: &str = \"prefix_revoked_permits\";"));
+    }
+
+    #[test]
+    fn tests_are_patched_and_generated() {
+        let (scaffolded, auth) = scaffolded(cw_counter_with_mock_tests());
+        let text = &scaffolded.file("src/contract.rs").unwrap().text;
+
+        assert!(
+            text.contains("let msg = InstantiateMsg { count: 17, prng_seed: Binary::default() };")
+        );
+        assert!(text.contains("use cosmwasm_std::{coins, from_binary, Addr, StdError};"));
+        assert!(text.contains("fn get_user_count_with_viewing_key() {"));
+
+        // the increment test still queries GetUserCount without a key (line 139
+        // before the storage rewrite)
+        let skipped: Vec<_> = auth
+            .skipped()
+            .map(|t| (t.file.display().to_string(), t.line))
+            .collect();
+        assert!(skipped.contains(&("src/contract.rs".to_string(), 144)));
+    }
+
+    #[test]
+    fn unknown_query_is_an_error() {
+        let cw = contract("cw-counter");
+        let options = AuthOptions::new(vec!["GetBalance".to_string()], "cw-counter");
+        let err = scaffold_auth(&cw, &options).err().unwrap();
+        assert_eq!(
+            "cannot scaffold authentication: `QueryMsg::GetBalance` not found",
+            err.to_string()
+        );
+    }
+}
@@ -0,0 +1,817 @@
+//! Secret needs the callee's code hash on every `WasmMsg::Execute`,
+//! `WasmMsg::Instantiate` and `WasmQuery::Smart`. Messages addressed through
+//! a struct holding the contract's `Addr`, like `CwTemplateContract` in
+//! `helpers.rs`, get the hash from a `ContractInfo` or a new `code_hash` field;
+//! messages addressed by a function parameter get a `code_hash` parameter
+//! next to it. Everything else, and the callers that now have to supply a
+//! hash, is reported.
+
+use std::collections::BTreeSet;
+
+use syn::punctuated::Punctuated;
+use syn::visit::{self, Visit};
+
+use crate::imports::{end_of_uses, extend_use, find_use, imported_names, indentation};
+use crate::multi_test::instances;
+use crate::source::{line_of, range_of, whole_lines, CrateSources, Edit, SourceFile};
+use crate::transform::{Migration, Recorder};
+
+pub const RULE: &str = "code-hash";
+
+/// Messages needing a code hash, and the field naming their contract.
+const MESSAGES: &[(&str, &str)] = &[
+    ("WasmMsg::Execute", "contract_addr"),
+    ("WasmMsg::Instantiate", "code_id"),
+    ("WasmQuery::Smart", "contract_addr"),
+];
+
+/// Helpers whose Secret versions take a code hash, and their signatures there.
+const HELPERS: &[(&str, &str)] = &[
+    (
+        "wasm_execute",
+        "wasm_execute(contract_addr, code_hash, msg, funds)",
+    ),
+    (
+        "wasm_instantiate",
+        "wasm_instantiate(code_id, code_hash, msg, funds, label)",
+    ),
+    (
+        "query_wasm_smart",
+        "query_wasm_smart(code_hash, contract_addr, msg)",
+    ),
+];
+
+/// Parameter names that just mean "the contract", whose hash is `code_hash`.
+pub(crate) const CONTRACT_PARAMS: &[&str] =
+    &["contract_addr", "contract", "addr", "address", "code_id"];
+
+pub fn thread_code_hashes(krate: &CrateSources) -> Migration {
+    let mut rec = Recorder::new(RULE);
+    let mut wrappers = vec![];
+    let mut threaded = BTreeSet::new();
+
+    for file in &krate.files {
+        let mut sites = Sites::default();
+        sites.visit_file(&file.ast);
+        for site in &sites.messages {
+            thread_message(krate, file, site, &mut wrappers, &mut threaded, &mut rec);
+        }
+        // ported along with the rest of a multi-test by the `multi-test` rule
+        let multi_test = file.text.contains("cw_multi_test");
+        for (name, node) in &sites.helpers {
+            if *name == "query_wasm_smart" && multi_test {
+                continue;
+            }
+            let (_, signature) = HELPERS.iter().find(|(h, _)| h == name).unwrap();
+            rec.skipped(
+                &file.path,
+                line_of(*node),
+                format!("`{}` call", name),
+                format!("Secret's `{}` takes the callee's code hash", signature),
+            );
+        }
+    }
+
+    for wrapper in &wrappers {
+        convert_wrapper(krate, wrapper, &mut rec);
+    }
+    for (function, method) in &threaded {
+        report_callers(krate, function, *method, &mut rec);
+    }
+    rec.finish(krate)
+}
+
+/// A struct whose `Addr` field addresses the messages built in its impls.
+#[derive(Clone, PartialEq)]
+struct Wrapper {
+    name: String,
+    field: syn::Member,
+}
+
+struct Message<'ast> {
+    expr: &'ast syn::ExprStruct,
+    kind: &'static str,
+    contract_field: &'static str,
+    impl_type: Option<String>,
+    sig: Option<&'ast syn::Signature>,
+}
+
+fn thread_message(
+    krate: &CrateSources,
+    file: &SourceFile,
+    site: &Message,
+    wrappers: &mut Vec<Wrapper>,
+    threaded: &mut BTreeSet<(String, bool)>,
+    rec: &mut Recorder,
+) {
+    let line = line_of(site.expr);
+    let description = format!("`{}` without a `code_hash`", site.kind);
+    let Some(contract) = site
+        .expr
+        .fields
+        .iter()
+        .find(|f| matches!(&f.member, syn::Member::Named(i) if i == site.contract_field))
+    else {
+        rec.skipped(
+            &file.path,
+            line,
+            description,
+            format!(
+                "no `{}` field to pair the code hash with",
+                site.contract_field
+            ),
+        );
+        return;
+    };
+
+    let hash = if mentions_self(&contract.expr) {
+        let wrapper = site
+            .impl_type
+            .as_deref()
+            .and_then(|name| addr_field(krate, name));
+        match wrapper {
+            Some(wrapper) => {
+                let hash = match &wrapper.field {
+                    syn::Member::Unnamed(i) => format!("self.{}.code_hash.clone()", i.index),
+                    syn::Member::Named(_) => "self.code_hash.clone()".to_string(),
+                };
+                if !wrappers.contains(&wrapper) {
+                    wrappers.push(wrapper);
+                }
+                hash
+            }
+            None => {
+                rec.skipped(
+                    &file.path,
+                    line,
+                    description,
+                    "the struct has no `Addr` field to pair a code hash with; add one by hand",
+                );
+                return;
+            }
+        }
+    } else if let Some((sig, param)) = site
+        .sig
+        .and_then(|sig| Some((sig, param_of(sig, &contract.expr)?)))
+    {
+        let name = if CONTRACT_PARAMS.contains(&param.to_string().as_str()) {
+            "code_hash".to_string()
+        } else {
+            format!("{}_code_hash", param)
+        };
+        if !has_param(sig, &name) {
+            rec.edit(&file.path, add_param(&file.text, sig, param, &name));
+            rec.applied(
+                &file.path,
+                line_of(&sig.ident),
+                format!("`{}` takes a `{}` for `{}`", sig.ident, name, param),
+            );
+            threaded.insert((sig.ident.to_string(), sig.receiver().is_some()));
+        }
+        name
+    } else {
+        rec.skipped(
+            &file.path,
+            line,
+            description,
+            format!(
+                "can't tell which contract `{}` is; add its `code_hash` by hand",
+                file.text_of(&contract.expr)
+            ),
+        );
+        return;
+    };
+
+    let field = if hash == "code_hash" {
+        hash
+    } else {
+        format!("code_hash: {}", hash)
+    };
+    rec.edit(
+        &file.path,
+        insert_field(&file.text, site.expr, &site.expr.fields, contract, &field),
+    );
+    rec.applied(
+        &file.path,
+        line,
+        format!("`code_hash` added to `{}`", site.kind),
+    );
+
+    if site.kind == "WasmMsg::Instantiate" {
+        drop_admin(file, site.expr, rec);
+    }
+}
+
+/// Secret contracts have no admin, so `Instantiate` has no `admin` field.
+fn drop_admin(file: &SourceFile, expr: &syn::ExprStruct, rec: &mut Recorder) {
+    let Some(pair) = expr
+        .fields
+        .pairs()
+        .find(|p| matches!(&p.value().member, syn::Member::Named(i) if i == "admin"))
+    else {
+        return;
+    };
+    let field = *pair.value();
+    if !matches!(&field.expr, syn::Expr::Path(p) if p.path.is_ident("None")) {
+        rec.skipped(
+            &file.path,
+            line_of(field),
+            "`WasmMsg::Instantiate` admin",
+            "Secret contracts have no admin; remove the field and any migrations",
+        );
+        return;
+    }
+    let mut range = range_of(field);
+    if let Some(comma) = pair.punct() {
+        range.end = range_of(*comma).end;
+    }
+    let lines = whole_lines(&file.text, range.clone());
+    let own_line = file.text[lines.clone()].trim() == file.text[range.clone()].trim();
+    let range = if own_line {
+        lines
+    } else {
+        let rest = &file.text[range.end..];
+        range.start..range.end + (rest.len() - rest.trim_start().len())
+    };
+    rec.edit(&file.path, Edit::delete(range));
+    rec.applied(
+        &file.path,
+        line_of(field),
+        "`admin` removed from `WasmMsg::Instantiate`",
+    );
+}
+
+/// Switches a wrapper's `Addr` to `ContractInfo` for tuple structs, or adds a
+/// `code_hash` field next to it, and reports where it's constructed.
+fn convert_wrapper(krate: &CrateSources, wrapper: &Wrapper, rec: &mut Recorder) {
+    for file in &krate.files {
+        let Some(item) = file.ast.items.iter().find_map(|item| match item {
+            syn::Item::Struct(s) if s.ident == wrapper.name => Some(s),
+            _ => None,
+        }) else {
+            continue;
+        };
+        match (&wrapper.field, &item.fields) {
+            (syn::Member::Unnamed(index), syn::Fields::Unnamed(fields)) => {
+                let field = &fields.unnamed[index.index as usize];
+                rec.edit(
+                    &file.path,
+                    Edit::replace(range_of(&field.ty), "ContractInfo"),
+                );
+                rec.applied(
+                    &file.path,
+                    line_of(field),
+                    format!(
+                        "`{}` holds a `ContractInfo` with the code hash",
+                        wrapper.name
+                    ),
+                );
+                import_contract_info(file, rec);
+            }
+            (syn::Member::Named(name), syn::Fields::Named(fields)) => {
+                let field = fields
+                    .named
+                    .iter()
+                    .find(|f| f.ident.as_ref() == Some(name))
+                    .unwrap();
+                let vis = match &field.vis {
+                    syn::Visibility::Inherited => String::new(),
+                    vis => format!("{} ", file.text_of(vis)),
+                };
+                rec.edit(
+                    &file.path,
+                    insert_field(
+                        &file.text,
+                        item,
+                        &fields.named,
+                        field,
+                        &format!("{}code_hash: String", vis),
+                    ),
+                );
+                rec.applied(
+                    &file.path,
+                    line_of(field),
+                    format!("`{}` gets a `code_hash` field", wrapper.name),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    for file in &krate.files {
+        let mut uses = WrapperUses {
+            wrapper,
+            fields: vec![],
+            constructions: vec![],
+        };
+        uses.visit_file(&file.ast);
+        for field in uses.fields {
+            rec.edit(&file.path, Edit::insert(range_of(field).end, ".address"));
+        }
+        let instances = instances(&file.ast);
+        for (node, arg) in uses.constructions {
+            let from_instance = matches!(arg, Some(syn::Expr::Path(p))
+                if p.path.get_ident().is_some_and(|i| instances.contains(&i.to_string())));
+            if from_instance {
+                continue;
+            }
+            let reason = match wrapper.field {
+                syn::Member::Unnamed(_) => {
+                    "pass a `ContractInfo` with the contract's address and code hash"
+                }
+                syn::Member::Named(_) => "set `code_hash` to the contract's code hash",
+            };
+            rec.skipped(
+                &file.path,
+                line_of(node),
+                format!("`{}` constructed", wrapper.name),
+                reason,
+            );
+        }
+    }
+}
+
+fn import_contract_info(file: &SourceFile, rec: &mut Recorder) {
+    let items = &file.ast.items;
+    if imported_names(items).contains("ContractInfo") {
+        return;
+    }
+    match find_use(items, "cosmwasm_std").and_then(|std| extend_use(std, &["ContractInfo"])) {
+        Some(edit) => rec.edit(&file.path, edit),
+        None => rec.edit(
+            &file.path,
+            Edit::insert(
+                end_of_uses(items).unwrap_or(0),
+                "\nuse cosmwasm_std::ContractInfo;",
+            ),
+        ),
+    }
+}
+
+/// Calls of a function that now takes a code hash.
+pub(crate) fn report_callers(
+    krate: &CrateSources,
+    function: &str,
+    method: bool,
+    rec: &mut Recorder,
+) {
+    for file in &krate.files {
+        for call in calls_of(&file.ast, function, method) {
+            rec.skipped(
+                &file.path,
+                line_of(call),
+                format!("`{}` call", function),
+                "pass the callee's code hash to the new `code_hash` parameter",
+            );
+        }
+    }
+}
+
+/// Inserts `text` as a field after `after` in a brace-delimited struct
+/// expression or definition, on its own line if the fields are.
+pub(crate) fn insert_field<T: syn::spanned::Spanned, P: syn::spanned::Spanned>(
+    source: &str,
+    node: &impl syn::spanned::Spanned,
+    fields: &Punctuated<T, P>,
+    after: &T,
+    text: &str,
+) -> Edit {
+    let after_range = range_of(after);
+    let pair = fields
+        .pairs()
+        .find(|p| range_of(*p.value()) == after_range)
+        .unwrap();
+    let multiline = source[range_of(node).start..after_range.start].contains('\n');
+    match pair.punct() {
+        Some(comma) if multiline => Edit::insert(
+            range_of(*comma).end,
+            format!("\n{}{},", indentation(source, after_range.start), text),
+        ),
+        Some(comma) => Edit::insert(range_of(*comma).end, format!(" {},", text)),
+        None if multiline => Edit::insert(
+            after_range.end,
+            format!(",\n{}{}", indentation(source, after_range.start), text),
+        ),
+        None => Edit::insert(after_range.end, format!(", {}", text)),
+    }
+}
+
+/// The `Addr` field of the struct `name`, if the crate defines one.
+fn addr_field(krate: &CrateSources, name: &str) -> Option<Wrapper> {
+    let is_addr = |ty: &syn::Type| {
+        matches!(ty, syn::Type::Path(t)
+            if t.path.segments.last().is_some_and(|s| s.ident == "Addr"))
+    };
+    krate.files.iter().find_map(|f| {
+        f.ast.items.iter().find_map(|item| {
+            let syn::Item::Struct(s) = item else {
+                return None;
+            };
+            if s.ident != name {
+                return None;
+            }
+            let field = match &s.fields {
+                syn::Fields::Unnamed(fields) => fields
+                    .unnamed
+                    .iter()
+                    .position(|f| is_addr(&f.ty))
+                    .map(|i| syn::Member::Unnamed(syn::Index::from(i)))?,
+                syn::Fields::Named(fields) => fields
+                    .named
+                    .iter()
+                    .find(|f| is_addr(&f.ty))
+                    .and_then(|f| f.ident.clone())
+                    .map(syn::Member::Named)?,
+                syn::Fields::Unit => return None,
+            };
+            Some(Wrapper {
+                name: name.to_string(),
+                field,
+            })
+        })
+    })
+}
+
+fn mentions_self(expr: &syn::Expr) -> bool {
+    struct SelfFinder(bool);
+    impl<'ast> Visit<'ast> for SelfFinder {
+        fn visit_path(&mut self, node: &'ast syn::Path) {
+            self.0 |= node.is_ident("self");
+        }
+    }
+    let mut finder = SelfFinder(false);
+    finder.visit_expr(expr);
+    finder.0
+}
+
+/// The parameter of `sig` that `expr` is, as `p`, `p.into()`, `&p` and the like.
+pub(crate) fn param_of<'a>(sig: &'a syn::Signature, expr: &syn::Expr) -> Option<&'a syn::Ident> {
+    let ident = match expr {
+        syn::Expr::Path(p) => p.path.get_ident()?,
+        syn::Expr::MethodCall(m)
+            if m.args.is_empty()
+                && ["clone", "into", "to_string", "as_str"]
+                    .contains(&m.method.to_string().as_str()) =>
+        {
+            return param_of(sig, &m.receiver);
+        }
+        syn::Expr::Reference(r) => return param_of(sig, &r.expr),
+        syn::Expr::Paren(p) => return param_of(sig, &p.expr),
+        _ => return None,
+    };
+    typed_param(sig, ident).and_then(|p| match &*p.pat {
+        syn::Pat::Ident(i) => Some(&i.ident),
+        _ => None,
+    })
+}
+
+/// Adds a `name: String` parameter after `param`, on its own line if the
+/// parameters are.
+pub(crate) fn add_param(
+    source: &str,
+    sig: &syn::Signature,
+    param: &syn::Ident,
+    name: &str,
+) -> Edit {
+    let after = range_of(typed_param(sig, param).unwrap());
+    let multiline = source[range_of(&sig.ident).end..after.start].contains('\n');
+    let text = if multiline {
+        format!(",\n{}{}: String", indentation(source, after.start), name)
+    } else {
+        format!(", {}: String", name)
+    };
+    Edit::insert(after.end, text)
+}
+
+pub(crate) fn typed_param<'a>(
+    sig: &'a syn::Signature,
+    name: &syn::Ident,
+) -> Option<&'a syn::PatType> {
+    sig.inputs.iter().find_map(|arg| match arg {
+        syn::FnArg::Typed(p) if matches!(&*p.pat, syn::Pat::Ident(i) if i.ident == *name) => {
+            Some(p)
+        }
+        _ => None,
+    })
+}
+
+pub(crate) fn has_param(sig: &syn::Signature, name: &str) -> bool {
+    sig.inputs.iter().any(|arg| {
+        matches!(arg, syn::FnArg::Typed(p)
+            if matches!(&*p.pat, syn::Pat::Ident(i) if i.ident == name))
+    })
+}
+
+/// Messages lacking a code hash, with the impl and function they're built in,
+/// and calls of the helpers wrapping them.
+#[derive(Default)]
+struct Sites<'ast> {
+    messages: Vec<Message<'ast>>,
+    helpers: Vec<(&'static str, &'ast syn::Expr)>,
+    impl_type: Option<String>,
+    sig: Option<&'ast syn::Signature>,
+}
+
+impl<'ast> Visit<'ast> for Sites<'ast> {
+    fn visit_item_impl(&mut self, node: &'ast syn::ItemImpl) {
+        let outer = self.impl_type.take();
+        if let syn::Type::Path(t) = &*node.self_ty {
+            self.impl_type = t.path.segments.last().map(|s| s.ident.to_string());
+        }
+        visit::visit_item_impl(self, node);
+        self.impl_type = outer;
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        let outer = self.sig.replace(&node.sig);
+        visit::visit_item_fn(self, node);
+        self.sig = outer;
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast syn::ImplItemFn) {
+        let outer = self.sig.replace(&node.sig);
+        visit::visit_impl_item_fn(self, node);
+        self.sig = outer;
+    }
+
+    fn visit_expr_struct(&mut self, node: &'ast syn::ExprStruct) {
+        let path = quote::ToTokens::to_token_stream(&node.path)
+            .to_string()
+            .replace(' ', "");
+        let message = MESSAGES.iter().find(|(kind, _)| path.ends_with(kind));
+        let has_hash = node
+            .fields
+            .iter()
+            .any(|f| matches!(&f.member, syn::Member::Named(i) if i == "code_hash"));
+        if let (Some((kind, contract_field)), false) = (message, has_hash) {
+            self.messages.push(Message {
+                expr: node,
+                kind,
+                contract_field,
+                impl_type: self.impl_type.clone(),
+                sig: self.sig,
+            });
+        }
+        visit::visit_expr_struct(self, node);
+    }
+
+    fn visit_expr(&mut self, node: &'ast syn::Expr) {
+        let name = match node {
+            syn::Expr::Call(c) => match &*c.func {
+                syn::Expr::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+                _ => None,
+            },
+            syn::Expr::MethodCall(m) => Some(m.method.to_string()),
+            _ => None,
+        };
+        let arity = match node {
+            syn::Expr::Call(c) => c.args.len(),
+            syn::Expr::MethodCall(m) => m.args.len(),
+            _ => 0,
+        };
+        // upstream arities: the Secret versions take one argument more
+        let helper = match (name.as_deref(), arity) {
+            (Some("wasm_execute"), 3) => Some("wasm_execute"),
+            (Some("wasm_instantiate"), 4) => Some("wasm_instantiate"),
+            (Some("query_wasm_smart"), 2) => Some("query_wasm_smart"),
+            _ => None,
+        };
+        if let Some(helper) = helper {
+            self.helpers.push((helper, node));
+        }
+        visit::visit_expr(self, node);
+    }
+}
+
+/// `self.0` accesses to a wrapper's `Addr`, which become `self.0.address`,
+/// and expressions constructing the wrapper.
+struct WrapperUses<'a, 'ast> {
+    wrapper: &'a Wrapper,
+    fields: Vec<&'ast syn::ExprField>,
+    constructions: Vec<(&'ast syn::Expr, Option<&'ast syn::Expr>)>,
+}
+
+impl<'a, 'ast> Visit<'ast> for WrapperUses<'a, 'ast> {
+    fn visit_item_impl(&mut self, node: &'ast syn::ItemImpl) {
+        let is_wrapper = matches!(&*node.self_ty, syn::Type::Path(t)
+            if t.path.segments.last().is_some_and(|s| s.ident == self.wrapper.name));
+        if is_wrapper && matches!(self.wrapper.field, syn::Member::Unnamed(_)) {
+            let mut fields = SelfFields {
+                member: &self.wrapper.field,
+                fields: vec![],
+            };
+            fields.visit_item_impl(node);
+            self.fields.extend(fields.fields);
+        }
+        visit::visit_item_impl(self, node);
+    }
+
+    fn visit_expr(&mut self, node: &'ast syn::Expr) {
+        let named = |path: &syn::Path| {
+            path.segments
+                .last()
+                .is_some_and(|s| s.ident == self.wrapper.name)
+        };
+        match node {
+            syn::Expr::Call(c) if matches!(&*c.func, syn::Expr::Path(p) if named(&p.path)) => {
+                self.constructions.push((node, c.args.first()));
+            }
+            syn::Expr::Struct(s) if named(&s.path) => {
+                let has_hash = s
+                    .fields
+                    .iter()
+                    .any(|f| matches!(&f.member, syn::Member::Named(i) if i == "code_hash"));
+                if !has_hash && s.rest.is_none() {
+                    self.constructions.push((node, None));
+                }
+            }
+            _ => {}
+        }
+        visit::visit_expr(self, node);
+    }
+}
+
+struct SelfFields<'a, 'ast> {
+    member: &'a syn::Member,
+    fields: Vec<&'ast syn::ExprField>,
+}
+
+impl<'a, 'ast> Visit<'ast> for SelfFields<'a, 'ast> {
+    fn visit_expr_field(&mut self, node: &'ast syn::ExprField) {
+        let on_self = matches!(&*node.base, syn::Expr::Path(p) if p.path.is_ident("self"));
+        if on_self && node.member == *self.member {
+            self.fields.push(node);
+        }
+        visit::visit_expr_field(self, node);
+    }
+}
+
+/// Calls of `function` in `file`, as a method when `method` is set.
+pub(crate) fn calls_of<'ast>(
+    file: &'ast syn::File,
+    function: &str,
+    method: bool,
+) -> Vec<&'ast syn::Expr> {
+    let mut calls = Calls {
+        function,
+        method,
+        calls: vec![],
+    };
+    calls.visit_file(file);
+    calls.calls
+}
+
+struct Calls<'a, 'ast> {
+    function: &'a str,
+    method: bool,
+    calls: Vec<&'ast syn::Expr>,
+}
+
+impl<'a, 'ast> Visit<'ast> for Calls<'a, 'ast> {
+    fn visit_expr(&mut self, node: &'ast syn::Expr) {
+        let called = match node {
+            syn::Expr::Call(c) if !self.method => matches!(&*c.func, syn::Expr::Path(p)
+                if p.path.segments.last().is_some_and(|s| s.ident == self.function)),
+            syn::Expr::MethodCall(m) if self.method => m.method == self.function,
+            _ => false,
+        };
+        if called {
+            self.calls.push(node);
+        }
+        visit::visit_expr(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn threads_code_hashes_through_wrappers_and_parameters() {
+        let krate = CrateSources::from_files(
+            "",
+            [
+                (
+                    "src/helpers.rs",
+                    r#"use cosmwasm_std::{to_binary, Addr, CosmosMsg, StdResult, WasmMsg};
+
+pub struct Contract(pub Addr);
+
+impl Contract {
+    pub fn addr(&self) -> Addr {
+        self.0.clone()
+    }
+
+    pub fn call(&self, msg: Binary) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: self.addr().into(),
+            msg,
+            funds: vec![],
+        }
+        .into())
+    }
+}
+
+pub struct Token {
+    pub address: Addr,
+}
+
+impl Token {
+    fn query(&self) -> WasmQuery {
+        WasmQuery::Smart { contract_addr: self.address.to_string(), msg: to_binary(&Q {})? }
+    }
+}
+
+pub fn spawn(code_id: u64, msg: Binary, label: String) -> WasmMsg {
+    WasmMsg::Instantiate {
+        admin: None,
+        code_id,
+        msg,
+        funds: vec![],
+        label,
+    }
+}
+"#,
+                ),
+                (
+                    "src/contract.rs",
+                    r#"fn run(config: Config) {
+    let contract = Contract(config.addr);
+    let token = Token { address: config.token };
+    let msg = spawn(config.code_id, to_binary(&Init {})?, "child".into());
+    let forward = WasmMsg::Execute { contract_addr: config.other.into(), msg, funds: vec![] };
+    let send = wasm_execute(config.other, &Send {}, vec![])?;
+}
+"#,
+                ),
+            ],
+        )
+        .unwrap();
+        let migration = thread_code_hashes(&krate);
+
+        assert_eq!(
+            r#"use cosmwasm_std::{to_binary, Addr, ContractInfo, CosmosMsg, StdResult, WasmMsg};
+
+pub struct Contract(pub ContractInfo);
+
+impl Contract {
+    pub fn addr(&self) -> Addr {
+        self.0.address.clone()
+    }
+
+    pub fn call(&self, msg: Binary) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: self.addr().into(),
+            code_hash: self.0.code_hash.clone(),
+            msg,
+            funds: vec![],
+        }
+        .into())
+    }
+}
+
+pub struct Token {
+    pub address: Addr,
+    pub code_hash: String,
+}
+
+impl Token {
+    fn query(&self) -> WasmQuery {
+        WasmQuery::Smart { contract_addr: self.address.to_string(), code_hash: self.code_hash.clone(), msg: to_binary(&Q {})? }
+    }
+}
+
+pub fn spawn(code_id: u64, code_hash: String, msg: Binary, label: String) -> WasmMsg {
+    WasmMsg::Instantiate {
+        code_id,
+        code_hash,
+        msg,
+        funds: vec![],
+        label,
+    }
+}
+"#,
+            migration.files[Path::new("src/helpers.rs")]
+        );
+
+        let skipped: Vec<_> = migration
+            .skipped()
+            .map(|t| (t.file.to_str().unwrap(), t.line, t.description.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "src/contract.rs",
+                    5,
+                    "`WasmMsg::Execute` without a `code_hash`"
+                ),
+                ("src/contract.rs", 6, "`wasm_execute` call"),
+                ("src/contract.rs", 2, "`Contract` constructed"),
+                ("src/contract.rs", 3, "`Token` constructed"),
+                ("src/contract.rs", 4, "`spawn` call"),
+            ],
+            skipped
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Per-contract settings read from a `cw2secret.toml` next to `Cargo.toml`.
+//!
+//! ```toml
+//! skip = ["src/legacy.rs", "src/bin/"]
+//!
+//! [storage.keys]
+//! state = "counter_state"
+//!
+//! [auth]
+//! queries = ["GetUserCount"]
+//! addr-field = "addr"
+//! permissions = "CounterPermissions"
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use toml_edit::{DocumentMut, Item};
+
+use crate::auth::AuthOptions;
+use crate::error::MigrateError;
+use crate::transform::Migration;
+
+/// Name of the configuration file in a contract directory.
+pub const FILE: &str = "cw2secret.toml";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Files or whole directories, relative to the crate root, the rules must
+    /// leave untouched.
+    pub skip: Vec<PathBuf>,
+    /// Storage namespaces to change while migrating, old → new.
+    pub storage_keys: BTreeMap<String, String>,
+    /// `QueryMsg` variants `auth` protects when none are given on the command line.
+    pub queries: Vec<String>,
+    /// Field of the protected queries holding the queried address.
+    pub addr_field: Option<String>,
+    /// Name of the generated permissions enum.
+    pub permissions: Option<String>,
+}
+
+impl Config {
+    /// Reads `cw2secret.toml` from a contract directory; an absent file is an
+    /// empty configuration.
+    pub fn load(root: impl AsRef<Path>) -> Result<Self, MigrateError> {
+        let path = root.as_ref().join(FILE);
+        match fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(MigrateError::io(path, e)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, MigrateError> {
+        let doc = text
+            .parse::<DocumentMut>()
+            .map_err(|e| MigrateError::Config(e.to_string()))?;
+        let invalid = |what: &str| MigrateError::Config(format!("invalid {}", what));
+
+        for (key, _) in doc.iter() {
+            if !["skip", "storage", "auth"].contains(&key) {
+                return Err(MigrateError::Config(format!("unknown key `{}`", key)));
+            }
+        }
+        for (table, known) in [
+            ("storage", &["keys"][..]),
+            ("auth", &["queries", "addr-field", "permissions"][..]),
+        ] {
+            let Some(item) = doc.get(table) else {
+                continue;
+            };
+            let keys = item
+                .as_table_like()
+                .ok_or_else(|| invalid(&format!("[{}]", table)))?;
+            for (key, _) in keys.iter() {
+                if !known.contains(&key) {
+                    return Err(MigrateError::Config(format!(
+                        "unknown key `{}` in [{}]",
+                        key, table
+                    )));
+                }
+            }
+        }
+
+        let skip = match doc.get("skip") {
+            Some(item) => strings(item).ok_or_else(|| invalid("`skip`"))?,
+            None => vec![],
+        };
+        let mut storage_keys = BTreeMap::new();
+        if let Some(keys) = doc.get("storage").and_then(|s| s.get("keys")) {
+            let keys = keys
+                .as_table_like()
+                .ok_or_else(|| invalid("[storage.keys]"))?;
+            for (old, new) in keys.iter() {
+                let new = new
+                    .as_str()
+                    .ok_or_else(|| invalid(&format!("storage key `{}`", old)))?;
+                storage_keys.insert(old.to_string(), new.to_string());
+            }
+        }
+
+        let auth = doc.get("auth");
+        let string = |key: &str| match auth.and_then(|a| a.get(key)) {
+            Some(item) => item
+                .as_str()
+                .map(|s| Some(s.to_string()))
+                .ok_or_else(|| invalid(&format!("`auth.{}`", key))),
+            None => Ok(None),
+        };
+        Ok(Config {
+            skip: skip.into_iter().map(PathBuf::from).collect(),
+            storage_keys,
+            queries: match auth.and_then(|a| a.get("queries")) {
+                Some(item) => strings(item).ok_or_else(|| invalid("`auth.queries`"))?,
+                None => vec![],
+            },
+            addr_field: string("addr-field")?,
+            permissions: string("permissions")?,
+        })
+    }
+
+    /// Whether `path`, relative to the crate root, is listed under `skip`.
+    pub fn skips(&self, path: &Path) -> bool {
+        self.skip.iter().any(|skip| path.starts_with(skip))
+    }
+
+    /// Drops a rule's changes and report entries for skipped files.
+    pub fn drop_skipped(&self, migration: &mut Migration) {
+        migration.files.retain(|path, _| !self.skips(path));
+        migration.removed.retain(|path| !self.skips(path));
+        migration.transformations.retain(|t| !self.skips(&t.file));
+    }
+
+    /// `auth` options for the configured queries, with the permissions enum
+    /// named after the crate unless the file names it.
+    pub fn auth_options(&self, crate_name: &str) -> AuthOptions {
+        let mut options = AuthOptions::new(self.queries.clone(), crate_name);
+        if let Some(addr_field) = &self.addr_field {
+            options.addr_field = addr_field.clone();
+        }
+        if let Some(permissions) = &self.permissions {
+            options.permissions = permissions.clone();
+        }
+        options
+    }
+}
+
+fn strings(item: &Item) -> Option<Vec<String>> {
+    item.as_array()?
+        .iter()
+        .map(|v| v.as_str().map(str::to_string))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_section() {
+        let config = Config::parse(
+            "skip = [\"src/legacy.rs\", \"src/bin/\"]\n\n\
+             [storage.keys]\nstate = \"counter_state\"\n\n\
+             [auth]\nqueries = [\"GetUserCount\"]\npermissions = \"CounterPermissions\"\n",
+        )
+        .unwrap();
+
+        assert!(config.skips(Path::new("src/legacy.rs")));
+        assert!(config.skips(Path::new("src/bin/schema.rs")));
+        assert!(!config.skips(Path::new("src/legacy_state.rs")));
+        assert_eq!("counter_state", config.storage_keys["state"]);
+
+        let auth = config.auth_options("cw-counter");
+        assert_eq!(vec!["GetUserCount".to_string()], auth.queries);
+        assert_eq!("addr", auth.addr_field);
+        assert_eq!("CounterPermissions", auth.permissions);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_wrong_types() {
+        assert_eq!(Config::default(), Config::parse("").unwrap());
+        let err = Config::parse("skipped = []").unwrap_err();
+        assert_eq!(
+            "invalid cw2secret.toml: unknown key `skipped`",
+            err.to_string()
+        );
+        let err = Config::parse("[auth]\nquery = [\"GetUserCount\"]").unwrap_err();
+        assert_eq!(
+            "invalid cw2secret.toml: unknown key `query` in [auth]",
+            err.to_string()
+        );
+        let err = Config::parse("[storage.key]\nstate = \"counter_state\"").unwrap_err();
+        assert_eq!(
+            "invalid cw2secret.toml: unknown key `key` in [storage]",
+            err.to_string()
+        );
+        let err = Config::parse("[auth]\nqueries = \"GetUserCount\"").unwrap_err();
+        assert_eq!(
+            "invalid cw2secret.toml: invalid `auth.queries`",
+            err.to_string()
+        );
+    }
+}
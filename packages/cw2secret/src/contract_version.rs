@@ -0,0 +1,381 @@
+//! Replaces `cw2`'s contract-version storage with an equivalent on
+//! `secret_toolkit::storage::Item`.
+//!
+//! The tutorial deletes the `set_contract_version` call, losing the version
+//! metadata. Instead, `ContractVersion`, `CONTRACT`, `set_contract_version` and
+//! `get_contract_version` are defined in `state.rs` with `cw2`'s signatures and
+//! storage key, and `cw2::` imports and paths point there. The
+//! `CONTRACT_NAME`/`CONTRACT_VERSION` constants and the calls stay as they are.
+
+use syn::visit::{self, Visit};
+
+use crate::imports::{end_of_uses, extend_use, find_use, imported_names, use_leaves};
+use crate::manifest::Manifest;
+use crate::source::{line_of, range_of, whole_lines, CrateSources, Edit, SourceFile};
+use crate::transform::{Migration, Recorder};
+
+pub const RULE: &str = "cw2";
+
+/// The `cw2` items with a Secret equivalent.
+const REPLACED: &[&str] = &[
+    "ContractVersion",
+    "CONTRACT",
+    "set_contract_version",
+    "get_contract_version",
+];
+
+/// Where the replacements go when the crate has no `src/state.rs`.
+const STATE: &str = "src/state.rs";
+
+pub fn replace_cw2(krate: &CrateSources, manifest: &Manifest) -> Migration {
+    let mut rec = Recorder::new(RULE);
+    let mut users = vec![];
+    for file in &krate.files {
+        let mut finder = Cw2Uses::default();
+        finder.visit_file(&file.ast);
+        if !finder.uses.is_empty() || !finder.paths.is_empty() {
+            users.push((file, finder));
+        }
+    }
+    if users.is_empty() {
+        return rec.finish(krate);
+    }
+    let target = krate
+        .file(STATE)
+        .unwrap_or_else(|| users.first().unwrap().0);
+    let module = target.module_path();
+
+    let mut replaced = false;
+    for (file, found) in &users {
+        let local = file.path == target.path;
+        for item in &found.uses {
+            replaced |= rewrite_use(file, item, local, &module, &mut rec);
+        }
+        for path in &found.paths {
+            let name = path.segments.last().unwrap().ident.to_string();
+            if !REPLACED.contains(&name.as_str()) {
+                rec.skipped(
+                    &file.path,
+                    line_of(*path),
+                    format!("`cw2::{}`", name),
+                    unsupported(&name),
+                );
+                continue;
+            }
+            let prefix = range_of(&path.segments[0]).start..range_of(&path.segments[1]).start;
+            let replacement = if local {
+                String::new()
+            } else {
+                format!("{}::", module)
+            };
+            rec.edit(&file.path, Edit::replace(prefix, replacement));
+            rec.applied(
+                &file.path,
+                line_of(*path),
+                format!("`cw2::{}` → `{}::{}`", name, module, name),
+            );
+            replaced = true;
+        }
+    }
+
+    if replaced {
+        define_replacements(target, &mut rec);
+        let has_toolkit = ["secret-toolkit", "cw-storage-plus"]
+            .iter()
+            .any(|name| manifest.dependency(name).is_some());
+        if !has_toolkit {
+            rec.skipped(
+                &manifest.path,
+                1,
+                "`secret-toolkit` dependency",
+                "the contract version is stored in a `secret_toolkit::storage::Item`; add `secret-toolkit` with its `storage` feature",
+            );
+        }
+    }
+    rec.finish(krate)
+}
+
+fn unsupported(name: &str) -> String {
+    format!(
+        "Secret has no `cw2`; only {} have a `secret_toolkit` equivalent, so replace `{}` by hand",
+        REPLACED
+            .iter()
+            .map(|n| format!("`{}`", n))
+            .collect::<Vec<_>>()
+            .join(", "),
+        name
+    )
+}
+
+/// Points a `use cw2::...` at `module`, merging it into an existing
+/// `use <module>::...`, or deletes it in `module` itself.
+fn rewrite_use(
+    file: &SourceFile,
+    item: &syn::ItemUse,
+    local: bool,
+    module: &str,
+    rec: &mut Recorder,
+) -> bool {
+    let line = line_of(item);
+    let leaves = use_leaves(&item.tree);
+    if let Some(name) = leaves
+        .iter()
+        .map(|l| l.name.as_deref().unwrap_or("*"))
+        .find(|name| !REPLACED.contains(name))
+    {
+        rec.skipped(&file.path, line, "`cw2` import", unsupported(name));
+        return false;
+    }
+    let renamed = leaves.iter().any(|l| l.rename.is_some());
+    let names: Vec<&str> = leaves.iter().filter_map(|l| l.name.as_deref()).collect();
+    let lines = whole_lines(&file.text, range_of(item));
+
+    if local && !renamed {
+        rec.edit(&file.path, Edit::delete(lines));
+        rec.applied(&file.path, line, "`cw2` import removed");
+        return true;
+    }
+    let merged = match find_use(&file.ast.items, module) {
+        Some(existing) if !renamed && !local => extend_use(existing, &names),
+        _ => None,
+    };
+    match merged {
+        Some(edit) => {
+            rec.edit(&file.path, edit);
+            rec.edit(&file.path, Edit::delete(lines));
+        }
+        None => {
+            let syn::UseTree::Path(root) = &item.tree else {
+                return false;
+            };
+            let prefix = if local { "self" } else { module };
+            rec.edit(&file.path, Edit::replace(range_of(&root.ident), prefix));
+        }
+    }
+    rec.applied(&file.path, line, format!("`cw2` import → `{}`", module));
+    true
+}
+
+/// Appends `ContractVersion`, `CONTRACT` and the two functions to `target`.
+fn define_replacements(target: &SourceFile, rec: &mut Recorder) {
+    let items = &target.ast.items;
+    let imported = imported_names(items);
+
+    // `Item` may still be `cw_storage_plus`'s if the storage rule skipped it
+    let toolkit_item = find_use(items, "secret_toolkit::storage").is_some_and(|u| {
+        use_leaves(&u.tree)
+            .iter()
+            .any(|l| l.local() == Some("Item"))
+    });
+    let item = if toolkit_item || !imported.contains("Item") {
+        "Item"
+    } else {
+        "secret_toolkit::storage::Item"
+    };
+    let mut uses = vec![];
+    if !imported.contains("Item") {
+        match find_use(items, "secret_toolkit::storage").and_then(|u| extend_use(u, &["Item"])) {
+            Some(edit) => rec.edit(&target.path, edit),
+            None => uses.push("use secret_toolkit::storage::Item;".to_string()),
+        }
+    }
+    let std_names: Vec<&str> = ["StdResult", "Storage"]
+        .into_iter()
+        .filter(|n| !imported.contains(*n))
+        .collect();
+    if !std_names.is_empty() {
+        match find_use(items, "cosmwasm_std").and_then(|u| extend_use(u, &std_names)) {
+            Some(edit) => rec.edit(&target.path, edit),
+            None => uses.push(format!("use cosmwasm_std::{{{}}};", std_names.join(", "))),
+        }
+    }
+    if !uses.is_empty() {
+        let at = end_of_uses(items).unwrap_or(0);
+        let text = if at == 0 {
+            format!("{}\n\n", uses.join("\n"))
+        } else {
+            format!("\n{}", uses.join("\n"))
+        };
+        rec.edit(&target.path, Edit::insert(at, text));
+    }
+
+    let mut derives = vec![
+        "Serialize",
+        "Deserialize",
+        "Clone",
+        "Debug",
+        "PartialEq",
+        "Eq",
+    ];
+    if imported.contains("JsonSchema") {
+        derives.push("JsonSchema");
+    }
+    let derives: Vec<String> = derives
+        .into_iter()
+        .map(|d| match d {
+            "Serialize" | "Deserialize" if !imported.contains(d) => format!("serde::{}", d),
+            _ => d.to_string(),
+        })
+        .collect();
+
+    let separator = if target.text.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    let definitions = format!(
+        r#"{separator}/// Contract name and version, stored where `cw2` kept them.
+#[derive({derives})]
+pub struct ContractVersion {{
+    /// The crate name, e.g. `crates.io:cw-counter`.
+    pub contract: String,
+    /// The crate version.
+    pub version: String,
+}}
+
+pub const CONTRACT: {item}<ContractVersion> = {item}::new(b"contract_info");
+
+/// Stores the contract's name and version, like `cw2::set_contract_version`.
+pub fn set_contract_version(
+    store: &mut dyn Storage,
+    name: impl Into<String>,
+    version: impl Into<String>,
+) -> StdResult<()> {{
+    CONTRACT.save(
+        store,
+        &ContractVersion {{
+            contract: name.into(),
+            version: version.into(),
+        }},
+    )
+}}
+
+/// Loads the contract's name and version, like `cw2::get_contract_version`.
+pub fn get_contract_version(store: &dyn Storage) -> StdResult<ContractVersion> {{
+    CONTRACT.load(store)
+}}
+"#,
+        derives = derives.join(", "),
+    );
+    rec.edit(&target.path, Edit::insert(target.text.len(), definitions));
+    rec.applied(
+        &target.path,
+        line_of_end(&target.text),
+        "contract version stored in a `secret_toolkit` `Item`",
+    );
+}
+
+/// The line a definition appended to `text` starts on.
+fn line_of_end(text: &str) -> usize {
+    text.lines().count() + 2
+}
+
+/// `use cw2::...` declarations and `cw2::...` paths outside them.
+#[derive(Default)]
+struct Cw2Uses<'ast> {
+    uses: Vec<&'ast syn::ItemUse>,
+    paths: Vec<&'ast syn::Path>,
+}
+
+impl<'ast> Visit<'ast> for Cw2Uses<'ast> {
+    fn visit_item_use(&mut self, node: &'ast syn::ItemUse) {
+        if matches!(&node.tree, syn::UseTree::Path(p) if p.ident == "cw2") {
+            self.uses.push(node);
+        }
+    }
+
+    fn visit_path(&mut self, node: &'ast syn::Path) {
+        if node.segments.len() >= 2 && node.segments[0].ident == "cw2" {
+            self.paths.push(node);
+            return;
+        }
+        visit::visit_path(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn keeps_the_contract_version_in_a_toolkit_item() {
+        let krate = CrateSources::from_files(
+            "",
+            [
+                (
+                    "src/contract.rs",
+                    r#"use cosmwasm_std::{DepsMut, Response, StdResult};
+use cw2::set_contract_version;
+
+use crate::state::{State, STATE};
+
+const CONTRACT_NAME: &str = "crates.io:cw-counter";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub fn instantiate(deps: DepsMut) -> StdResult<Response> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new())
+}
+
+pub fn migrate(deps: DepsMut) -> StdResult<Response> {
+    let version = cw2::get_contract_version(deps.storage)?;
+    cw2::assert_contract_version(deps.storage, CONTRACT_NAME, &version.version)?;
+    Ok(Response::new())
+}
+"#,
+                ),
+                (
+                    "src/state.rs",
+                    r#"use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::Addr;
+use secret_toolkit::storage::{Item, Keymap};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct State {
+    pub count: i32,
+    pub owner: Addr,
+}
+
+pub const STATE: Item<State> = Item::new(b"state");
+"#,
+                ),
+            ],
+        )
+        .unwrap();
+        let manifest = Manifest::parse(
+            "[package]\nname = \"cw-counter\"\n\n[dependencies]\nsecret-toolkit = \"0.10.0\"\n",
+        )
+        .unwrap();
+        let migration = replace_cw2(&krate, &manifest);
+
+        let contract = &migration.files[Path::new("src/contract.rs")];
+        assert!(contract.starts_with(
+            "use cosmwasm_std::{DepsMut, Response, StdResult};\n\n\
+             use crate::state::{set_contract_version, State, STATE};\n"
+        ));
+        assert!(contract.contains("crate::state::get_contract_version(deps.storage)?"));
+        assert!(contract.contains("const CONTRACT_NAME: &str = \"crates.io:cw-counter\";"));
+
+        let state = &migration.files[Path::new("src/state.rs")];
+        assert!(state.contains("use cosmwasm_std::{Addr, StdResult, Storage};"));
+        assert!(state.contains(
+            "#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]\n\
+             pub struct ContractVersion {"
+        ));
+        assert!(state.contains(
+            "pub const CONTRACT: Item<ContractVersion> = Item::new(b\"contract_info\");"
+        ));
+
+        let skipped: Vec<_> = migration
+            .skipped()
+            .map(|t| (t.file.to_str().unwrap(), t.line, t.description.as_str()))
+            .collect();
+        assert_eq!(
+            vec![("src/contract.rs", 16, "`cw2::assert_contract_version`")],
+            skipped
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Step 3b of the migration pathway: swaps the upstream CosmWasm crates for
+//! the Secret forks [`VersionMap`] lists for their version, and
+//! `cw-storage-plus` for `secret-toolkit`.
+
+use std::collections::BTreeSet;
+
+use syn::visit::{self, Visit};
+
+use crate::imports::path_names;
+use crate::manifest::{Dependency, Manifest, PATCH_SECTION};
+use crate::source::{whole_lines, CrateSources, Edit};
+use crate::transform::{Migration, Recorder};
+use crate::versions::{Release, VersionMap, FORKED};
+
+pub const RULE: &str = "dependency";
+
+/// Crates that are dropped once the sources no longer use them, with the
+/// crate name they're imported under and whether `secret-toolkit` replaces
+/// them.
+const DROPPED: &[(&str, &str, bool)] = &[
+    ("cw-storage-plus", "cw_storage_plus", true),
+    ("cw2", "cw2", false),
+    ("cw20", "cw20", true),
+];
+
+pub fn replace_dependencies(
+    krate: &CrateSources,
+    manifest: &Manifest,
+    versions: &VersionMap,
+) -> Migration {
+    let mut rec = Recorder::new(RULE);
+    let dependencies = manifest.dependencies();
+    let Some(release) = pick_release(manifest, &dependencies, versions, &mut rec) else {
+        return rec.finish_with_manifest(krate, manifest);
+    };
+
+    for dep in dependencies
+        .iter()
+        .filter(|d| FORKED.contains(&d.name.as_str()))
+    {
+        replace_spec(manifest, dep, release, &mut rec);
+    }
+    patch_dependents(manifest, &dependencies, release, versions, &mut rec);
+
+    let mut toolkit_added = dependencies.iter().any(|d| d.name == "secret-toolkit");
+    for dep in &dependencies {
+        let Some((_, krate_name, replaced_by_toolkit)) =
+            DROPPED.iter().find(|(name, ..)| *name == dep.name)
+        else {
+            continue;
+        };
+        if let Some(user) = krate
+            .files
+            .iter()
+            .find(|f| path_names(&f.ast).contains(*krate_name))
+        {
+            rec.skipped(
+                &manifest.path,
+                dep.line,
+                format!("`{}` dependency", dep.name),
+                format!(
+                    "still used by {}; see `cw2secret lint`",
+                    user.path.display()
+                ),
+            );
+            continue;
+        }
+        let Some(entry) = manifest.entry_range(&dep.section, &dep.name) else {
+            rec.skipped(
+                &manifest.path,
+                dep.line,
+                format!("`{}` dependency", dep.name),
+                "declared as a table; remove it by hand",
+            );
+            continue;
+        };
+        let lines = whole_lines(&manifest.text, entry);
+        if *replaced_by_toolkit && !toolkit_added && dep.section == "dependencies" {
+            let features =
+                versions.toolkit_features(toolkit_modules(krate).iter().map(String::as_str));
+            let spec = release
+                .spec("secret-toolkit", true, &features)
+                .expect("releases always list `secret-toolkit`");
+            rec.edit(
+                &manifest.path,
+                Edit::replace(lines, format!("secret-toolkit = {}\n", spec)),
+            );
+            rec.applied(
+                &manifest.path,
+                dep.line,
+                format!("`{}` replaced with `secret-toolkit`", dep.name),
+            );
+            toolkit_added = true;
+        } else {
+            rec.edit(&manifest.path, Edit::delete(lines));
+            rec.applied(&manifest.path, dep.line, format!("`{}` removed", dep.name));
+        }
+    }
+
+    rec.finish_with_manifest(krate, manifest)
+}
+
+/// The release matching the crate's `cosmwasm-std`, or the first one when the
+/// crate doesn't name an upstream version.
+fn pick_release<'a>(
+    manifest: &Manifest,
+    dependencies: &[Dependency],
+    versions: &'a VersionMap,
+    rec: &mut Recorder,
+) -> Option<&'a Release> {
+    let upstream = FORKED.iter().find_map(|name| {
+        dependencies
+            .iter()
+            .find(|d| d.name == *name && d.git.is_none() && d.version.is_some())
+    });
+    let Some(dep) = upstream else {
+        return versions.releases.first();
+    };
+    let version = dep.version.as_deref().unwrap_or_default();
+    let description = format!("`{}` {}", dep.name, version);
+    match versions.release_for(version) {
+        Some(release) => {
+            if let Some(note) = &release.note {
+                rec.skipped(&manifest.path, dep.line, description, note.clone());
+            }
+            Some(release)
+        }
+        None => {
+            rec.skipped(
+                &manifest.path,
+                dep.line,
+                description,
+                "no Secret release listed for it in versions.toml",
+            );
+            None
+        }
+    }
+}
+
+fn replace_spec(manifest: &Manifest, dep: &Dependency, release: &Release, rec: &mut Recorder) {
+    let description = format!("`{}` in [{}]", dep.name, dep.section);
+    let Some(range) = manifest.value_range(&dep.section, &dep.name) else {
+        rec.skipped(
+            &manifest.path,
+            dep.line,
+            description,
+            "declared as a table; port it to the spec in versions.toml by hand",
+        );
+        return;
+    };
+    let current = &manifest.text[range.clone()];
+    if current.contains("scrtlabs") || current.contains("secret-cosmwasm") {
+        return;
+    }
+    if dep.name == "cosmwasm-std" {
+        let unsupported: Vec<&str> = dep
+            .features
+            .iter()
+            .filter(|f| !release.std_features.contains(f))
+            .map(String::as_str)
+            .collect();
+        if !unsupported.is_empty() {
+            rec.skipped(
+                &manifest.path,
+                dep.line,
+                description,
+                format!(
+                    "the Secret fork has no `{}` feature; drop it and port the code using it",
+                    unsupported.join("`, `")
+                ),
+            );
+            return;
+        }
+    }
+    let Some(spec) = release.spec(&dep.name, dep.default_features, &dep.features) else {
+        return;
+    };
+    rec.edit(&manifest.path, Edit::replace(range, spec.clone()));
+    rec.applied(
+        &manifest.path,
+        dep.line,
+        format!("{} switched to `{}`", description, spec),
+    );
+}
+
+/// Points crates.io's `cosmwasm-std` at the fork for dependencies like
+/// `cw-utils` that would otherwise pull the upstream one.
+fn patch_dependents(
+    manifest: &Manifest,
+    dependencies: &[Dependency],
+    release: &Release,
+    versions: &VersionMap,
+    rec: &mut Recorder,
+) {
+    let patches = manifest.patches();
+    for patch in patches.iter().filter(|p| FORKED.contains(&p.name.as_str())) {
+        let description = format!("`{}` in [{}]", patch.name, PATCH_SECTION);
+        let Some(range) = manifest.value_range(PATCH_SECTION, &patch.name) else {
+            continue;
+        };
+        if manifest.text[range.clone()].contains("scrtlabs") {
+            continue;
+        }
+        match release.patch(&patch.name) {
+            Some(spec) => {
+                rec.edit(&manifest.path, Edit::replace(range, spec.clone()));
+                rec.applied(
+                    &manifest.path,
+                    patch.line,
+                    format!("{} switched to `{}`", description, spec),
+                );
+            }
+            None => rec.skipped(
+                &manifest.path,
+                patch.line,
+                description,
+                "versions.toml has no fork that can patch crates.io for this release",
+            ),
+        }
+    }
+
+    let Some(dependent) = dependencies
+        .iter()
+        .find(|d| versions.patched_dependents.contains(&d.name))
+    else {
+        return;
+    };
+    if release.patch.is_empty() {
+        rec.skipped(
+            &manifest.path,
+            dependent.line,
+            format!("`{}` dependency", dependent.name),
+            "it pulls upstream `cosmwasm-std`, and versions.toml has no fork that can patch crates.io",
+        );
+        return;
+    }
+    let missing: Vec<(&String, String)> = release
+        .patch
+        .keys()
+        .filter(|name| !patches.iter().any(|p| p.name == **name))
+        .filter_map(|name| Some((name, release.patch(name)?)))
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+
+    let lines: String = missing
+        .iter()
+        .map(|(name, spec)| format!("{} = {}\n", name, spec))
+        .collect();
+    let edit = match manifest.header_range(PATCH_SECTION) {
+        Some(header) => {
+            let after = whole_lines(&manifest.text, header).end;
+            Edit::insert(after, lines)
+        }
+        None => {
+            let separator = if manifest.text.ends_with('\n') {
+                "\n"
+            } else {
+                "\n\n"
+            };
+            Edit::insert(
+                manifest.text.len(),
+                format!("{}[{}]\n{}", separator, PATCH_SECTION, lines),
+            )
+        }
+    };
+    rec.edit(&manifest.path, edit);
+    rec.applied(
+        &manifest.path,
+        dependent.line,
+        format!(
+            "[{}] points {} at the Secret fork for `{}`",
+            PATCH_SECTION,
+            missing
+                .iter()
+                .map(|(name, _)| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(" and "),
+            dependent.name
+        ),
+    );
+}
+
+/// `secret_toolkit` modules the crate uses, e.g. `storage`.
+fn toolkit_modules(krate: &CrateSources) -> BTreeSet<String> {
+    let mut modules = ToolkitModules::default();
+    for file in &krate.files {
+        modules.visit_file(&file.ast);
+    }
+    modules.modules
+}
+
+#[derive(Default)]
+struct ToolkitModules {
+    modules: BTreeSet<String>,
+}
+
+impl ToolkitModules {
+    fn add_tree(&mut self, tree: &syn::UseTree) {
+        match tree {
+            syn::UseTree::Path(p) => {
+                self.modules.insert(p.ident.to_string());
+            }
+            syn::UseTree::Name(n) => {
+                self.modules.insert(n.ident.to_string());
+            }
+            syn::UseTree::Rename(r) => {
+                self.modules.insert(r.ident.to_string());
+            }
+            syn::UseTree::Group(g) => g.items.iter().for_each(|t| self.add_tree(t)),
+            syn::UseTree::Glob(_) => {}
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for ToolkitModules {
+    fn visit_item_use(&mut self, node: &'ast syn::ItemUse) {
+        if let syn::UseTree::Path(root) = &node.tree {
+            if root.ident == "secret_toolkit" {
+                self.add_tree(&root.tree);
+            }
+        }
+    }
+
+    fn visit_path(&mut self, node: &'ast syn::Path) {
+        let mut segments = node.segments.iter();
+        if segments.next().is_some_and(|s| s.ident == "secret_toolkit") {
+            if let Some(module) = segments.next() {
+                self.modules.insert(module.ident.to_string());
+            }
+        }
+        visit::visit_path(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn features_and_existing_toolkit_are_respected() {
+        let krate = CrateSources::from_files("", [("src/lib.rs", "")]).unwrap();
+        let manifest = Manifest::parse(
+            r#"[dependencies]
+cosmwasm-std = { version = "1.1", features = ["stargate"] }
+cosmwasm-storage = { git = "https://github.com/scrtlabs/cosmwasm/", tag = "v1.1.9-secret" }
+cw-storage-plus = "0.15.1"
+secret-toolkit = "0.9"
+"#,
+        )
+        .unwrap();
+        let migration = replace_dependencies(&krate, &manifest, &VersionMap::default());
+
+        assert_eq!(
+            r#"[dependencies]
+cosmwasm-std = { version = "1.1", features = ["stargate"] }
+cosmwasm-storage = { git = "https://github.com/scrtlabs/cosmwasm/", tag = "v1.1.9-secret" }
+secret-toolkit = "0.9"
+"#,
+            migration.files[Path::new("Cargo.toml")]
+        );
+        let skipped: Vec<_> = migration.skipped().map(|t| t.line).collect();
+        assert_eq!(vec![2], skipped);
+    }
+
+    #[test]
+    fn iterator_feature_carries_over() {
+        let krate = CrateSources::from_files("", [("src/lib.rs", "")]).unwrap();
+        let manifest = Manifest::parse(
+            "[dependencies]\ncosmwasm-std = { version = \"1.1.3\", features = [\"iterator\"] }\n",
+        )
+        .unwrap();
+        let migration = replace_dependencies(&krate, &manifest, &VersionMap::default());
+
+        assert_eq!(
+            "[dependencies]\ncosmwasm-std = { git = \"https://github.com/scrtlabs/cosmwasm/\", default-features = false, tag = \"v1.1.9-secret\", features = [\"iterator\"] }\n",
+            migration.files[Path::new("Cargo.toml")]
+        );
+        assert_eq!(0, migration.skipped().count());
+    }
+
+    #[test]
+    fn versions_pick_specs_features_and_patches() {
+        let krate = CrateSources::from_files(
+            "",
+            [(
+                "src/state.rs",
+                "use secret_toolkit::storage::Item;\nuse secret_toolkit::{permit::Permit, viewing_key::ViewingKey};\n",
+            )],
+        )
+        .unwrap();
+        let manifest = Manifest::parse(
+            r#"[dependencies]
+cosmwasm-std = { version = "1.1.3", default-features = false, features = ["staking"] }
+cosmwasm-storage = { version = "1.1.3", default-features = false }
+cw-storage-plus = "0.15.1"
+cw-utils = "0.15.1"
+"#,
+        )
+        .unwrap();
+        let migration = replace_dependencies(&krate, &manifest, &VersionMap::default());
+
+        assert_eq!(
+            r#"[dependencies]
+cosmwasm-std = { git = "https://github.com/scrtlabs/cosmwasm/", default-features = false, tag = "v1.1.9-secret", features = ["staking"] }
+cosmwasm-storage = { git = "https://github.com/scrtlabs/cosmwasm/", tag = "v1.1.9-secret", default-features = false }
+secret-toolkit = { git = "https://github.com/scrtlabs/secret-toolkit", rev = "9b74bdac71c2fedcc12246f18cdfdd94b8991282", features = ["permit", "viewing-key"] }
+cw-utils = "0.15.1"
+
+[patch.crates-io]
+cosmwasm-std = { git = "https://github.com/scrtlabs/cosmwasm/", tag = "v1.1.9-secret" }
+cosmwasm-storage = { git = "https://github.com/scrtlabs/cosmwasm/", tag = "v1.1.9-secret" }
+"#,
+            migration.files[Path::new("Cargo.toml")]
+        );
+        assert_eq!(0, migration.skipped().count());
+
+        let newer = Manifest::parse("[dependencies]\ncosmwasm-std = \"1.4\"\ncw-utils = \"1.0\"\n")
+            .unwrap();
+        let migration = replace_dependencies(&krate, &newer, &VersionMap::default());
+        assert_eq!(
+            "[dependencies]\ncosmwasm-std = { package = \"secret-cosmwasm-std\", version = \"1.1.11\", default-features = false }\ncw-utils = \"1.0\"\n",
+            migration.files[Path::new("Cargo.toml")]
+        );
+        let skipped: Vec<_> = migration.skipped().map(|t| t.line).collect();
+        assert_eq!(vec![2, 3], skipped);
+    }
+}
@@ -0,0 +1,97 @@
+//! Unified diffs of a migration, for review before anything is written.
+
+use std::path::Path;
+
+use similar::TextDiff;
+
+use crate::manifest::Manifest;
+use crate::source::CrateSources;
+use crate::transform::Migration;
+
+/// A unified diff of every file `migration` changes or removes, with paths
+/// relative to the crate root so it applies with `git apply` or `patch -p1`
+/// from there.
+pub fn unified_diff(krate: &CrateSources, manifest: &Manifest, migration: &Migration) -> String {
+    let original = |path: &Path| {
+        if path == manifest.path {
+            Some(manifest.text.as_str())
+        } else {
+            krate.file(path).map(|f| f.text.as_str())
+        }
+    };
+
+    let mut paths: Vec<&Path> = migration
+        .files
+        .keys()
+        .chain(&migration.removed)
+        .map(|p| p.as_path())
+        .collect();
+    paths.sort();
+
+    let mut out = String::new();
+    for path in paths {
+        let old = original(path);
+        let new = migration.files.get(path).map(String::as_str);
+        let from = match old {
+            Some(_) => format!("a/{}", path.display()),
+            None => "/dev/null".to_string(),
+        };
+        let to = match new {
+            Some(_) => format!("b/{}", path.display()),
+            None => "/dev/null".to_string(),
+        };
+        let diff = TextDiff::from_lines(old.unwrap_or(""), new.unwrap_or(""));
+        out.push_str(&diff.unified_diff().header(&from, &to).to_string());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn diffs_changed_and_removed_files() {
+        let krate = CrateSources::from_files(
+            "",
+            [
+                ("src/lib.rs", "pub mod contract;\npub mod helpers;\n"),
+                ("src/helpers.rs", "pub struct Helper;\n"),
+            ],
+        )
+        .unwrap();
+        let manifest = Manifest::parse("[package]\nname = \"cw-counter\"\n").unwrap();
+        let mut migration = Migration::default();
+        migration.files.insert(
+            PathBuf::from("src/lib.rs"),
+            "pub mod contract;\n".to_string(),
+        );
+        migration.files.insert(
+            PathBuf::from("Cargo.toml"),
+            "[package]\nname = \"secret-counter\"\n".to_string(),
+        );
+        migration.removed.insert(PathBuf::from("src/helpers.rs"));
+
+        assert_eq!(
+            "\
+--- a/Cargo.toml
++++ b/Cargo.toml
+@@ -1,2 +1,2 @@
+ [package]
+-name = \"cw-counter\"
++name = \"secret-counter\"
+--- a/src/helpers.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-pub struct Helper;
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1 @@
+ pub mod contract;
+-pub mod helpers;
+",
+            unified_diff(&krate, &manifest, &migration)
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MigrateError {
+    #[error("failed to access {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("failed to parse {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: syn::Error,
+    },
+
+    #[error("failed to parse {path}: {source}")]
+    Manifest {
+        path: PathBuf,
+        #[source]
+        source: toml_edit::TomlError,
+    },
+
+    #[error("{path} is not a crate: no src/ directory found")]
+    NotACrate { path: PathBuf },
+
+    #[error("cannot scaffold authentication: {0}")]
+    Scaffold(String),
+
+    #[error("invalid version map: {0}")]
+    Versions(String),
+
+    #[error("invalid cw2secret.toml: {0}")]
+    Config(String),
+
+    #[error("cannot migrate the workspace: {0}")]
+    Workspace(String),
+}
+
+impl MigrateError {
+    pub fn io(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+        MigrateError::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Helpers for reading and extending `use` declarations.
+
+use std::collections::HashSet;
+
+use syn::visit::{self, Visit};
+
+use crate::source::{range_of, whole_lines, Edit};
+
+/// A leaf of a `use` tree: `Map`, `Map as CwMap` or `*`.
+pub(crate) struct UseLeaf {
+    pub name: Option<String>,
+    pub rename: Option<String>,
+}
+
+impl UseLeaf {
+    pub fn local(&self) -> Option<&str> {
+        self.rename.as_deref().or(self.name.as_deref())
+    }
+
+    pub fn render(&self, name: &str) -> String {
+        match &self.rename {
+            Some(rename) => format!("{} as {}", name, rename),
+            None => name.to_string(),
+        }
+    }
+}
+
+pub(crate) fn use_leaves(tree: &syn::UseTree) -> Vec<UseLeaf> {
+    match tree {
+        syn::UseTree::Path(p) => use_leaves(&p.tree),
+        syn::UseTree::Name(n) => vec![UseLeaf {
+            name: Some(n.ident.to_string()),
+            rename: None,
+        }],
+        syn::UseTree::Rename(r) => vec![UseLeaf {
+            name: Some(r.ident.to_string()),
+            rename: Some(r.rename.to_string()),
+        }],
+        syn::UseTree::Glob(_) => vec![UseLeaf {
+            name: None,
+            rename: None,
+        }],
+        syn::UseTree::Group(g) => g.items.iter().flat_map(use_leaves).collect(),
+    }
+}
+
+/// Every `use` declaration in a file, including those inside modules and functions.
+#[derive(Default)]
+pub(crate) struct UseFinder<'ast> {
+    pub uses: Vec<&'ast syn::ItemUse>,
+}
+
+impl<'ast> Visit<'ast> for UseFinder<'ast> {
+    fn visit_item_use(&mut self, node: &'ast syn::ItemUse) {
+        self.uses.push(node);
+    }
+}
+
+/// Every identifier used as a path segment in a file, e.g. `crate`, `helpers`
+/// and `CwTemplateContract` for `use crate::helpers::CwTemplateContract;`.
+#[derive(Default)]
+pub(crate) struct PathNames {
+    pub names: HashSet<String>,
+}
+
+impl<'ast> Visit<'ast> for PathNames {
+    fn visit_path_segment(&mut self, node: &'ast syn::PathSegment) {
+        self.names.insert(node.ident.to_string());
+        visit::visit_path_segment(self, node);
+    }
+
+    fn visit_use_path(&mut self, node: &'ast syn::UsePath) {
+        self.names.insert(node.ident.to_string());
+        visit::visit_use_path(self, node);
+    }
+
+    fn visit_use_name(&mut self, node: &'ast syn::UseName) {
+        self.names.insert(node.ident.to_string());
+    }
+}
+
+pub(crate) fn path_names(file: &syn::File) -> HashSet<String> {
+    let mut visitor = PathNames::default();
+    visitor.visit_file(file);
+    visitor.names
+}
+
+/// Names usable unqualified in a file: imports plus top-level type definitions.
+pub(crate) fn local_names(file: &syn::File) -> HashSet<String> {
+    let mut uses = UseFinder::default();
+    uses.visit_file(file);
+    let mut names: HashSet<String> = uses
+        .uses
+        .iter()
+        .flat_map(|u| use_leaves(&u.tree))
+        .filter_map(|leaf| leaf.local().map(str::to_string))
+        .collect();
+    for item in &file.items {
+        match item {
+            syn::Item::Struct(s) => names.insert(s.ident.to_string()),
+            syn::Item::Enum(e) => names.insert(e.ident.to_string()),
+            syn::Item::Type(t) => names.insert(t.ident.to_string()),
+            _ => continue,
+        };
+    }
+    names
+}
+
+/// Names imported by the `use` declarations directly in `items`.
+pub(crate) fn imported_names(items: &[syn::Item]) -> HashSet<String> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Use(u) => Some(use_leaves(&u.tree)),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|leaf| leaf.local().map(str::to_string))
+        .collect()
+}
+
+/// Path leading to the leaves of a simple `use` tree: `crate::msg` for
+/// `use crate::msg::{ExecuteMsg, QueryMsg};`. `None` for nested groups.
+fn use_prefix(tree: &syn::UseTree) -> Option<Vec<String>> {
+    let mut prefix = vec![];
+    let mut tree = tree;
+    loop {
+        match tree {
+            syn::UseTree::Path(p) => {
+                prefix.push(p.ident.to_string());
+                tree = &p.tree;
+            }
+            syn::UseTree::Name(_) | syn::UseTree::Rename(_) => return Some(prefix),
+            syn::UseTree::Group(g) => {
+                let flat = g
+                    .items
+                    .iter()
+                    .all(|i| matches!(i, syn::UseTree::Name(_) | syn::UseTree::Rename(_)));
+                return flat.then_some(prefix);
+            }
+            syn::UseTree::Glob(_) => return None,
+        }
+    }
+}
+
+/// The simple `use <prefix>::...;` declaration among `items`, if any. Uses
+/// behind attributes, like a `#[cfg(...)]`'d `entry_point` import, are left alone.
+pub(crate) fn find_use<'a>(items: &'a [syn::Item], prefix: &str) -> Option<&'a syn::ItemUse> {
+    items.iter().find_map(|item| match item {
+        syn::Item::Use(u)
+            if u.attrs.is_empty()
+                && use_prefix(&u.tree).is_some_and(|p| p.join("::") == prefix) =>
+        {
+            Some(u)
+        }
+        _ => None,
+    })
+}
+
+/// Orders names the way rustfmt does: `self`, then modules and functions,
+/// then types, then constants.
+pub(crate) fn sort_names(names: &mut [String]) {
+    names.sort_by_key(|name| {
+        let class = if name == "self" {
+            0
+        } else if name.starts_with(|c: char| c.is_lowercase()) {
+            1
+        } else if name.chars().all(|c| !c.is_lowercase()) {
+            3
+        } else {
+            2
+        };
+        (class, name.clone())
+    });
+}
+
+/// Adds `names` to a simple `use` declaration; `None` if they're all there already.
+pub(crate) fn extend_use(item: &syn::ItemUse, names: &[&str]) -> Option<Edit> {
+    let prefix = use_prefix(&item.tree)?;
+    let leaves = use_leaves(&item.tree);
+    let present: HashSet<_> = leaves.iter().filter_map(|l| l.local()).collect();
+    let missing: Vec<_> = names.iter().filter(|n| !present.contains(*n)).collect();
+    if missing.is_empty() {
+        return None;
+    }
+
+    let mut rendered: Vec<String> = leaves
+        .iter()
+        .filter_map(|l| Some(l.render(l.name.as_deref()?)))
+        .collect();
+    rendered.extend(missing.into_iter().map(|n| n.to_string()));
+    sort_names(&mut rendered);
+    Some(Edit::replace(
+        range_of(&item.tree),
+        render_tree(&prefix, &rendered),
+    ))
+}
+
+/// Replaces the leaf `name` of a simple `use` declaration with `names`; `None`
+/// for nested groups.
+pub(crate) fn replace_use_leaf(item: &syn::ItemUse, name: &str, names: &[String]) -> Option<Edit> {
+    let prefix = use_prefix(&item.tree)?;
+    let mut rendered: Vec<String> = use_leaves(&item.tree)
+        .iter()
+        .filter(|l| l.local() != Some(name))
+        .filter_map(|l| Some(l.render(l.name.as_deref()?)))
+        .collect();
+    rendered.extend(names.iter().cloned());
+    sort_names(&mut rendered);
+    let tree = match &rendered[..] {
+        [leaf] => format!("{}::{}", prefix.join("::"), leaf),
+        leaves => render_tree(&prefix, leaves),
+    };
+    Some(Edit::replace(range_of(&item.tree), tree))
+}
+
+/// Drops `remove` from a simple `use` declaration and adds `add`, deleting the
+/// declaration if nothing is left; `None` for nested groups or no change.
+pub(crate) fn rewrite_use(
+    text: &str,
+    item: &syn::ItemUse,
+    remove: &[&str],
+    add: &[&str],
+) -> Option<Edit> {
+    let prefix = use_prefix(&item.tree)?;
+    let leaves = use_leaves(&item.tree);
+    let kept: Vec<_> = leaves
+        .iter()
+        .filter(|l| !l.local().is_some_and(|n| remove.contains(&n)))
+        .collect();
+    let present: HashSet<_> = kept.iter().filter_map(|l| l.local()).collect();
+    let missing: Vec<_> = add.iter().filter(|n| !present.contains(*n)).collect();
+    if kept.len() == leaves.len() && missing.is_empty() {
+        return None;
+    }
+
+    let mut rendered: Vec<String> = kept
+        .iter()
+        .filter_map(|l| Some(l.render(l.name.as_deref()?)))
+        .collect();
+    rendered.extend(missing.into_iter().map(|n| n.to_string()));
+    sort_names(&mut rendered);
+    let tree = match &rendered[..] {
+        [] => return Some(Edit::delete(whole_lines(text, range_of(item)))),
+        [leaf] => format!("{}::{}", prefix.join("::"), leaf),
+        leaves => render_tree(&prefix, leaves),
+    };
+    Some(Edit::replace(range_of(&item.tree), tree))
+}
+
+/// `prefix::{names}`, wrapped the way rustfmt does when the `use` would run
+/// past 100 columns.
+fn render_tree(prefix: &[String], names: &[String]) -> String {
+    let prefix = prefix.join("::");
+    let line = format!("use {}::{{{}}};", prefix, names.join(", "));
+    if line.len() <= 100 {
+        return format!("{}::{{{}}}", prefix, names.join(", "));
+    }
+    let mut lines = vec![];
+    let mut line = String::new();
+    for name in names {
+        if !line.is_empty() && 4 + line.len() + name.len() + 2 > 98 {
+            lines.push(format!("    {}\n", line));
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(name);
+        line.push(',');
+    }
+    lines.push(format!("    {}\n", line));
+    format!("{}::{{\n{}}}", prefix, lines.concat())
+}
+
+/// Byte offset just past the last `use` declaration among `items`.
+pub(crate) fn end_of_uses(items: &[syn::Item]) -> Option<usize> {
+    items
+        .iter()
+        .rev()
+        .find(|item| matches!(item, syn::Item::Use(_)))
+        .map(|item| range_of(item).end)
+}
+
+/// Imports `names` from `module`, extending its `use` declaration or adding one
+/// after the last `use`; `None` if they're all imported already.
+pub(crate) fn import(items: &[syn::Item], module: &str, names: &[&str]) -> Option<Edit> {
+    let imported = imported_names(items);
+    let mut missing: Vec<String> = names
+        .iter()
+        .filter(|n| !imported.contains(**n))
+        .map(|n| n.to_string())
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    sort_names(&mut missing);
+    if let Some(existing) = find_use(items, module) {
+        let missing: Vec<&str> = missing.iter().map(String::as_str).collect();
+        return extend_use(existing, &missing);
+    }
+    let tree = match &missing[..] {
+        [name] => format!("{}::{}", module, name),
+        names => format!("{}::{{{}}}", module, names.join(", ")),
+    };
+    Some(match end_of_uses(items) {
+        Some(at) => Edit::insert(at, format!("\nuse {};", tree)),
+        None => Edit::insert(0, format!("use {};\n\n", tree)),
+    })
+}
+
+/// Leading whitespace of the line containing `offset`.
+pub(crate) fn indentation(text: &str, offset: usize) -> &str {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[line_start..offset];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::SourceFile;
+
+    #[test]
+    fn extends_simple_uses_in_rustfmt_order() {
+        let file = SourceFile::parse(
+            "src/msg.rs",
+            "use cosmwasm_std::Addr;\nuse crate::msg::{ExecuteMsg, QueryMsg};\n",
+        )
+        .unwrap();
+
+        let std = find_use(&file.ast.items, "cosmwasm_std").unwrap();
+        let edit = extend_use(std, &["Binary", "to_binary", "Addr"]).unwrap();
+        assert_eq!("cosmwasm_std::{to_binary, Addr, Binary}", edit.replacement);
+
+        let msg = find_use(&file.ast.items, "crate::msg").unwrap();
+        assert!(extend_use(msg, &["QueryMsg"]).is_none());
+        assert_eq!(Some(file.text.len() - 1), end_of_uses(&file.ast.items));
+    }
+}
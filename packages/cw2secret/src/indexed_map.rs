@@ -0,0 +1,947 @@
+//! Rewrites `cw_storage_plus::IndexedMap` into a `Keymap` plus one `Keymap` per index.
+//!
+//! `secret_toolkit::storage` has no secondary indexes. A `MultiIndex` becomes a
+//! keymap of primary keys nested by the index key with `add_suffix`, a
+//! `UniqueIndex` a keymap from the index key to the primary key, and generated
+//! `save_*`/`remove_*` functions keep them in step with the map.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+use syn::visit::{self, Visit};
+use syn::{Expr, ExprMethodCall, Pat, Type};
+
+use crate::imports::{import, replace_use_leaf, use_leaves, UseFinder};
+use crate::source::{line_of, range_of, whole_lines, Edit, SourceFile};
+use crate::storage::{
+    generic_types, is_none, operand, owned_key, suffix_bytes, without_borrow, StorageDecl,
+    StorageKind, INSERTION_ORDER,
+};
+use crate::transform::Recorder;
+
+/// A function returning an `IndexedMap`, e.g. `fn tokens<'a>() -> IndexedMap<..>`.
+pub(crate) struct IndexedMap {
+    /// The function's name, e.g. `tokens`.
+    pub function: String,
+    /// The `Keymap` replacing it, e.g. `TOKENS`.
+    pub decl: StorageDecl,
+    indexes: Vec<SecondaryIndex>,
+}
+
+/// A `MultiIndex` or `UniqueIndex` field of the map's indexes struct.
+struct SecondaryIndex {
+    /// Field name, e.g. `owner`.
+    field: String,
+    /// Owned index key type, e.g. `Addr`.
+    key: String,
+    unique: bool,
+    namespace: String,
+    /// The index function's value parameter and body, e.g. `d` and `d.owner.clone()`.
+    param: String,
+    body: String,
+}
+
+impl IndexedMap {
+    fn constant(&self, index: &SecondaryIndex) -> String {
+        format!("{}_BY_{}", self.decl.name, index.field.to_uppercase())
+    }
+
+    fn key_function(&self, index: &SecondaryIndex) -> String {
+        format!("{}_{}", self.function, index.field)
+    }
+
+    fn lookup(&self, index: &SecondaryIndex) -> String {
+        format!("{}_by_{}", self.function, index.field)
+    }
+}
+
+/// Replaces each `IndexedMap` function in `file`, with its indexes struct and
+/// `IndexList` impl, by the keymaps and the functions maintaining them.
+pub(crate) fn migrate_indexed_maps(
+    file: &SourceFile,
+    imports: &HashMap<String, String>,
+    rec: &mut Recorder,
+) -> Vec<IndexedMap> {
+    let mut maps = vec![];
+    for item in &file.ast.items {
+        let syn::Item::Fn(func) = item else {
+            continue;
+        };
+        let syn::ReturnType::Type(_, ty) = &func.sig.output else {
+            continue;
+        };
+        let Type::Path(ty) = &**ty else {
+            continue;
+        };
+        if !is_indexed_map(&ty.path, imports) {
+            continue;
+        }
+        let line = line_of(&func.sig.ident);
+        match describe(file, func, ty) {
+            Ok((map, removed)) => {
+                for item in removed {
+                    rec.edit(&file.path, Edit::delete(item_lines(&file.text, item)));
+                }
+                let vis = match file.text_of(&func.vis) {
+                    "" => String::new(),
+                    vis => format!("{} ", vis),
+                };
+                rec.edit(
+                    &file.path,
+                    Edit::replace(range_of(func), definitions(&map, &vis)),
+                );
+                let mut replacements = vec![format!("`{}`", map.decl.name)];
+                replacements.extend(map.indexes.iter().map(|i| format!("`{}`", map.constant(i))));
+                rec.applied(
+                    &file.path,
+                    line,
+                    format!(
+                        "`{}` `IndexedMap` → {} keymaps, kept in step by `save_{}`/`remove_{}`",
+                        map.function,
+                        replacements.join(", "),
+                        map.function,
+                        map.function
+                    ),
+                );
+                maps.push(map);
+            }
+            Err(reason) => rec.skipped(
+                &file.path,
+                line,
+                format!("`{}` `IndexedMap`", func.sig.ident),
+                reason,
+            ),
+        }
+    }
+
+    if !maps.is_empty() {
+        let mut names = vec!["StdResult", "Storage"];
+        if maps.iter().any(|m| !m.indexes.is_empty()) {
+            names.push("StdError");
+        }
+        if let Some(edit) = import(&file.ast.items, "cosmwasm_std", &names) {
+            rec.edit(&file.path, edit);
+        }
+    }
+    maps
+}
+
+fn is_indexed_map(path: &syn::Path, imports: &HashMap<String, String>) -> bool {
+    let Some(last) = path.segments.last() else {
+        return false;
+    };
+    if path.segments.len() == 1 {
+        imports.get(&last.ident.to_string()).map(String::as_str) == Some("IndexedMap")
+    } else {
+        path.segments[0].ident == "cw_storage_plus" && last.ident == "IndexedMap"
+    }
+}
+
+/// The map `func` builds, and the items only it used.
+fn describe<'ast>(
+    file: &'ast SourceFile,
+    func: &'ast syn::ItemFn,
+    ty: &'ast syn::TypePath,
+) -> Result<(IndexedMap, Vec<&'ast syn::Item>), String> {
+    let [key, value, indexes] = generic_types(&ty.path)[..] else {
+        return Err("unrecognised generic arguments".to_string());
+    };
+    if matches!(key, Type::Tuple(_)) {
+        return Err(
+            "`Keymap` has no composite keys; nest keymaps with `add_suffix` and index them by hand"
+                .to_string(),
+        );
+    }
+    let (key, key_borrowed, key_is_str) = owned_key(file, key);
+    let Some(indexes) = (match indexes {
+        Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }) else {
+        return Err("unrecognised indexes type".to_string());
+    };
+
+    let mut removed = vec![];
+    let mut fields = None;
+    for item in &file.ast.items {
+        match item {
+            syn::Item::Struct(s) if s.ident == indexes => {
+                fields = Some(&s.fields);
+                removed.push(item);
+            }
+            syn::Item::Impl(i)
+                if i.trait_.as_ref().is_some_and(|(_, path, _)| {
+                    path.segments.last().is_some_and(|s| s.ident == "IndexList")
+                }) && matches!(&*i.self_ty, Type::Path(p)
+                    if p.path.segments.last().is_some_and(|s| s.ident == indexes)) =>
+            {
+                removed.push(item);
+            }
+            _ => {}
+        }
+    }
+    let Some(fields) = fields else {
+        return Err(format!("`{}` isn't declared next to the map", indexes));
+    };
+
+    let mut builder = Builder {
+        indexes: &indexes,
+        inits: vec![],
+        namespaces: vec![],
+    };
+    builder.visit_block(&func.block);
+    let ([init], [namespace]) = (&builder.inits[..], &builder.namespaces[..]) else {
+        return Err(format!(
+            "the function has to build one `{}` and pass it to `IndexedMap::new` with a literal namespace",
+            indexes
+        ));
+    };
+
+    let mut secondary = vec![];
+    for field in fields {
+        let Some(name) = &field.ident else {
+            return Err(format!("`{}` has unnamed fields", indexes));
+        };
+        let index = secondary_index(file, name, &field.ty, init)
+            .map_err(|reason| format!("index `{}`: {}", name, reason))?;
+        secondary.push(index);
+    }
+
+    let decl = StorageDecl {
+        name: func.sig.ident.to_string().to_uppercase(),
+        kind: StorageKind::Map,
+        namespace: Some(namespace.clone()),
+        key: Some(key),
+        value: file.text_of(value).to_string(),
+        module: file.module_path(),
+        file: file.path.clone(),
+        line: line_of(&func.sig.ident),
+        key_borrowed,
+        key_is_str,
+        suffixes: vec![],
+        indexed: Some(func.sig.ident.to_string()),
+    };
+    let map = IndexedMap {
+        function: func.sig.ident.to_string(),
+        decl,
+        indexes: secondary,
+    };
+    Ok((map, removed))
+}
+
+/// Finds the indexes struct literal and the `IndexedMap::new` namespace in a function.
+struct Builder<'a, 'ast> {
+    indexes: &'a str,
+    inits: Vec<&'ast syn::ExprStruct>,
+    namespaces: Vec<String>,
+}
+
+impl<'a, 'ast> Visit<'ast> for Builder<'a, 'ast> {
+    fn visit_expr_struct(&mut self, node: &'ast syn::ExprStruct) {
+        if node
+            .path
+            .segments
+            .last()
+            .is_some_and(|s| s.ident == self.indexes)
+        {
+            self.inits.push(node);
+        }
+        visit::visit_expr_struct(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let Expr::Path(func) = &*node.func {
+            let segments: Vec<String> = func
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            if segments.ends_with(&["IndexedMap".to_string(), "new".to_string()]) {
+                if let Some(namespace) = node.args.first().and_then(literal) {
+                    self.namespaces.push(namespace);
+                }
+            }
+        }
+        visit::visit_expr_call(self, node);
+    }
+}
+
+fn literal(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(s),
+            ..
+        }) => Some(s.value()),
+        _ => None,
+    }
+}
+
+fn secondary_index(
+    file: &SourceFile,
+    field: &syn::Ident,
+    ty: &Type,
+    init: &syn::ExprStruct,
+) -> Result<SecondaryIndex, &'static str> {
+    let Type::Path(ty) = ty else {
+        return Err("unrecognised index type");
+    };
+    let unique = match ty
+        .path
+        .segments
+        .last()
+        .map(|s| s.ident.to_string())
+        .as_deref()
+    {
+        Some("MultiIndex") => false,
+        Some("UniqueIndex") => true,
+        _ => return Err("only `MultiIndex` and `UniqueIndex` are supported"),
+    };
+    let Some(key) = generic_types(&ty.path)
+        .first()
+        .map(|k| owned_key(file, k).0)
+    else {
+        return Err("unrecognised generic arguments");
+    };
+    if !unique && suffix_bytes(&key, "key").is_none() {
+        return Err("its key can't be nested with `add_suffix`");
+    }
+
+    let value = init.fields.iter().find_map(|f| match &f.member {
+        syn::Member::Named(name) if name == field => Some(&f.expr),
+        _ => None,
+    });
+    let Some(Expr::Call(new)) = value else {
+        return Err("it has to be built with `new`");
+    };
+    let args: Vec<&Expr> = new.args.iter().collect();
+    let (function, namespace) = match (unique, &args[..]) {
+        (false, [function, _, namespace]) | (true, [function, namespace]) => (function, namespace),
+        _ => return Err("unexpected number of arguments"),
+    };
+    let Some(namespace) = literal(namespace) else {
+        return Err("its namespace has to be a literal");
+    };
+    let Expr::Closure(closure) = function else {
+        return Err("its index function has to be a closure");
+    };
+    let inputs: Vec<&Pat> = closure.inputs.iter().collect();
+    let param = match inputs[..] {
+        [value] => value,
+        [pk, value] if ignored(pk) => value,
+        [_, _] => return Err("its index function reads the primary key"),
+        _ => return Err("unexpected index function"),
+    };
+    let param = match param {
+        Pat::Type(t) => &*t.pat,
+        p => p,
+    };
+    let Pat::Ident(param) = param else {
+        return Err("unexpected index function");
+    };
+    Ok(SecondaryIndex {
+        field: field.to_string(),
+        key,
+        unique,
+        namespace,
+        param: param.ident.to_string(),
+        body: file.text_of(&*closure.body).to_string(),
+    })
+}
+
+fn ignored(pat: &Pat) -> bool {
+    match pat {
+        Pat::Wild(_) => true,
+        Pat::Ident(i) => i.ident.to_string().starts_with('_'),
+        Pat::Type(t) => ignored(&t.pat),
+        _ => false,
+    }
+}
+
+/// The item's lines, and the blank line after it.
+fn item_lines(text: &str, item: &syn::Item) -> std::ops::Range<usize> {
+    let lines = whole_lines(text, range_of(item));
+    match text[lines.end..].find(|c: char| c != ' ' && c != '\t') {
+        Some(i) if text[lines.end + i..].starts_with('\n') => lines.start..lines.end + i + 1,
+        _ => lines,
+    }
+}
+
+/// How a function takes a key of type `ty`, and how to pass it on to a `Keymap`
+/// method: `&str` for a `String`, which clippy wants rather than `&String`.
+fn key_param(ty: &str, name: &str) -> (String, String) {
+    match ty {
+        "String" => ("&str".to_string(), format!("&{}.to_string()", name)),
+        ty => (format!("&{}", ty), name.to_string()),
+    }
+}
+
+/// How a function takes an index key of type `ty` that it only nests by.
+fn suffix_param(ty: &str) -> String {
+    match ty {
+        "String" => "&str".to_string(),
+        "Vec<u8>" => "&[u8]".to_string(),
+        ty if suffix_bytes(ty, "").is_some_and(|b| b.ends_with("to_be_bytes()")) => ty.to_string(),
+        ty => format!("&{}", ty),
+    }
+}
+
+/// A local for the index key, unless the field name is taken by a parameter.
+fn binding(field: &str) -> String {
+    match field {
+        "store" | "key" | "value" | "old" => format!("{}_key", field),
+        field => field.to_string(),
+    }
+}
+
+fn definitions(map: &IndexedMap, vis: &str) -> String {
+    let IndexedMap { function, decl, .. } = map;
+    let (name, value) = (&decl.name, &decl.value);
+    let key = decl.key.as_deref().unwrap_or_default();
+    let (key_type, key_arg) = key_param(key, "key");
+    let rebind = if key_arg == "key" {
+        String::new()
+    } else {
+        format!("    let key = {};\n", key_arg)
+    };
+
+    let mut out = format!(
+        "{}const {}: Keymap<{}, {}> = Keymap::new(b\"{}\");\n",
+        vis,
+        name,
+        key,
+        value,
+        decl.namespace.as_deref().unwrap_or_default()
+    );
+    for index in &map.indexes {
+        if index.unique {
+            let _ = writeln!(
+                out,
+                "/// `{}` keys by `{}`, which is unique.\n{}const {}: Keymap<{}, {}> = Keymap::new(b\"{}\");",
+                name,
+                index.field,
+                vis,
+                map.constant(index),
+                index.key,
+                key,
+                index.namespace
+            );
+        } else {
+            let _ = writeln!(
+                out,
+                "/// `{}` keys nested by `{}` with `add_suffix`.\n{}const {}: Keymap<{}, ()> = Keymap::new(b\"{}\");",
+                name,
+                index.field,
+                vis,
+                map.constant(index),
+                key,
+                index.namespace
+            );
+        }
+    }
+    for index in &map.indexes {
+        let body = if index.body.starts_with('{') {
+            index.body.clone()
+        } else {
+            format!("{{\n    {}\n}}", index.body)
+        };
+        let _ = write!(
+            out,
+            "\nfn {}({}: &{}) -> {} {}\n",
+            map.key_function(index),
+            index.param,
+            value,
+            index.key,
+            body
+        );
+    }
+
+    // save checks unique indexes, then replaces the old value's entries
+    let mut checks = String::new();
+    let mut inserts = String::new();
+    for index in &map.indexes {
+        let constant = map.constant(index);
+        let key_function = map.key_function(index);
+        if index.unique {
+            let local = binding(&index.field);
+            let _ = write!(
+                checks,
+                "    let {local} = {key_function}(value);\n    \
+                 if matches!({constant}.get(store, &{local}), Some(existing) if existing != *key) {{\n        \
+                 return Err(StdError::generic_err(\"Violates unique constraint on index\"));\n    \
+                 }}\n"
+            );
+            let _ = writeln!(inserts, "    {constant}.insert(store, &{local}, key)?;");
+        } else {
+            let _ = writeln!(
+                inserts,
+                "    {constant}\n        .add_suffix({})\n        .insert(store, key, &())?;",
+                suffix_bytes(&index.key, &format!("{}(value)", key_function)).unwrap()
+            );
+        }
+    }
+    let reindex = if map.indexes.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "    if let Some(old) = {name}.get(store, key) {{\n{}    }}\n{inserts}",
+            removals(map, "        ")
+        )
+    };
+    let _ = write!(
+        out,
+        "\n/// Saves `value` under `key` in `{name}` and updates its indexes.\n\
+         {vis}fn save_{function}(store: &mut dyn Storage, key: {key_type}, value: &{value}) -> StdResult<()> {{\n\
+         {rebind}{checks}{reindex}    {name}.insert(store, key, value)\n}}\n"
+    );
+    let found = if map.indexes.is_empty() {
+        format!("{name}.contains(store, key)")
+    } else {
+        format!("let Some(old) = {name}.get(store, key)")
+    };
+    let _ = write!(
+        out,
+        "\n/// Removes `key` from `{name}` and its indexes.\n\
+         {vis}fn remove_{function}(store: &mut dyn Storage, key: {key_type}) -> StdResult<()> {{\n\
+         {rebind}    if {found} {{\n{}        {name}.remove(store, key)?;\n    }}\n    Ok(())\n}}\n",
+        removals(map, "        ")
+    );
+
+    for index in &map.indexes {
+        let constant = map.constant(index);
+        let local = binding(&index.field);
+        let field = &index.field;
+        let lookup = map.lookup(index);
+        if index.unique {
+            let (param, arg) = key_param(&index.key, &local);
+            let _ = write!(
+                out,
+                "\n/// The `{name}` entry whose `{field}` is `{local}`.\n\
+                 {vis}fn {lookup}(store: &dyn Storage, {local}: {param}) -> Option<({key}, {value})> {{\n    \
+                 let key = {constant}.get(store, {arg})?;\n    \
+                 let value = {name}.get(store, &key)?;\n    \
+                 Some((key, value))\n}}\n"
+            );
+        } else {
+            let _ = write!(
+                out,
+                "\n/// The `{name}` entries whose `{field}` is `{local}`, in the order they were indexed.\n\
+                 {vis}fn {lookup}(store: &dyn Storage, {local}: {}) -> StdResult<Vec<({key}, {value})>> {{\n    \
+                 {constant}\n        \
+                 .add_suffix({})\n        \
+                 .iter_keys(store)?\n        \
+                 .map(|key| {{\n            \
+                 let key = key?;\n            \
+                 let value = {name}\n                \
+                 .get(store, &key)\n                \
+                 .ok_or_else(|| StdError::not_found(\"{value}\"))?;\n            \
+                 Ok((key, value))\n        \
+                 }})\n        \
+                 .collect()\n}}\n",
+                suffix_param(&index.key),
+                suffix_bytes(&index.key, &local).unwrap()
+            );
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// Statements removing the index entries of `old`, stored under `key`.
+fn removals(map: &IndexedMap, indent: &str) -> String {
+    let mut out = String::new();
+    for index in &map.indexes {
+        let constant = map.constant(index);
+        let old = format!("{}(&old)", map.key_function(index));
+        if index.unique {
+            let _ = writeln!(out, "{indent}{constant}.remove(store, &{old})?;");
+        } else {
+            let _ = writeln!(
+                out,
+                "{indent}{constant}\n{indent}    .add_suffix({})\n{indent}    .remove(store, key)?;",
+                suffix_bytes(&index.key, &old).unwrap()
+            );
+        }
+    }
+    out
+}
+
+/// `tokens().idx.owner.prefix(owner).range(storage, None, None, order)` →
+/// `tokens_by_owner(storage, &owner)?.into_iter().map(Ok)`. Returns the names
+/// the rewritten calls use.
+pub(crate) fn rewrite_index_calls(
+    file: &SourceFile,
+    maps: &[IndexedMap],
+    rec: &mut Recorder,
+) -> BTreeSet<String> {
+    let mut calls = IndexCalls {
+        maps,
+        handled: HashSet::new(),
+        calls: vec![],
+    };
+    calls.visit_file(&file.ast);
+
+    let mut used = BTreeSet::new();
+    for call in calls.calls {
+        let IndexCall {
+            map,
+            index,
+            module,
+            call,
+            parent,
+        } = call;
+        let line = line_of(call);
+        let what = format!("`{}().idx.{}.{}`", map.function, index.field, call.method);
+        let iterated = |method: &syn::Ident| {
+            format!(
+                "`{}().idx.{}.prefix(..).{}` → `{}`",
+                map.function,
+                index.field,
+                method,
+                map.lookup(index)
+            )
+        };
+        let lookup = map.lookup(index);
+
+        let iteration = parent.filter(|_| call.method == "prefix" && !index.unique);
+        let iteration = iteration.and_then(|parent| {
+            let keys = match parent.method.to_string().as_str() {
+                "range" => false,
+                "keys" => true,
+                _ => return None,
+            };
+            let prefix: Vec<&Expr> = call.args.iter().collect();
+            let args: Vec<&Expr> = parent.args.iter().collect();
+            match (&prefix[..], &args[..]) {
+                ([prefix], [storage, min, max, order]) if is_none(min) && is_none(max) => {
+                    Some((parent, keys, *prefix, *storage, *order))
+                }
+                _ => None,
+            }
+        });
+        let Some((parent, keys, prefix, storage, order)) = iteration else {
+            let reason = if index.unique {
+                format!(
+                    "`{}` returns the key rather than its bytes; use it to look the entry up",
+                    lookup
+                )
+            } else {
+                format!(
+                    "only `prefix(..).range` and `prefix(..).keys` over a whole prefix can be rewritten; use `{}`",
+                    lookup
+                )
+            };
+            rec.skipped(&file.path, line, what, reason);
+            continue;
+        };
+
+        let prefix = operand(file, without_borrow(prefix));
+        let prefix = if suffix_param(&index.key).starts_with('&') {
+            format!("&{}", prefix)
+        } else {
+            prefix
+        };
+        let separator = if file.text_of(parent).contains('\n') {
+            format!(
+                "\n{}",
+                crate::imports::indentation(&file.text, range_of(&parent.method).start)
+            )
+        } else {
+            String::new()
+        };
+        let rev = if file.text_of(order).contains("Descending") {
+            format!("{}.rev()", separator)
+        } else {
+            String::new()
+        };
+        let map_entry = if keys {
+            "map(|(key, _)| Ok(key))"
+        } else {
+            "map(Ok)"
+        };
+        rec.edit(
+            &file.path,
+            Edit::replace(
+                range_of(parent),
+                format!(
+                    "{}{}({}, {})?{}.into_iter(){}{}.{}",
+                    module,
+                    lookup,
+                    file.text_of(storage),
+                    prefix,
+                    separator,
+                    rev,
+                    separator,
+                    map_entry
+                ),
+            ),
+        );
+        rec.warned(&file.path, line, iterated(&parent.method), INSERTION_ORDER);
+        used.insert(lookup);
+    }
+    used
+}
+
+struct IndexCall<'m, 'ast> {
+    map: &'m IndexedMap,
+    index: &'m SecondaryIndex,
+    /// Path to the map's module, e.g. `state::` in `state::tokens().idx.owner`.
+    module: String,
+    /// The method called on the index, e.g. `prefix`.
+    call: &'ast ExprMethodCall,
+    /// The method called on its result, e.g. `range`.
+    parent: Option<&'ast ExprMethodCall>,
+}
+
+struct IndexCalls<'m, 'ast> {
+    maps: &'m [IndexedMap],
+    handled: HashSet<(usize, usize)>,
+    calls: Vec<IndexCall<'m, 'ast>>,
+}
+
+impl<'m, 'ast> IndexCalls<'m, 'ast> {
+    /// `(map, index, module)` when `expr` is `module::map().idx.index`.
+    fn index_of(&self, expr: &Expr) -> Option<(&'m IndexedMap, &'m SecondaryIndex, String)> {
+        let Expr::Field(index) = expr else {
+            return None;
+        };
+        let syn::Member::Named(field) = &index.member else {
+            return None;
+        };
+        let Expr::Field(idx) = &*index.base else {
+            return None;
+        };
+        let Expr::Call(call) = &*idx.base else {
+            return None;
+        };
+        let Expr::Path(func) = &*call.func else {
+            return None;
+        };
+        if !matches!(&idx.member, syn::Member::Named(n) if n == "idx") || !call.args.is_empty() {
+            return None;
+        }
+        let segments: Vec<String> = func
+            .path
+            .segments
+            .iter()
+            .map(|s| s.ident.to_string())
+            .collect();
+        let (function, module) = segments.split_last()?;
+        let map = self.maps.iter().find(|m| &m.function == function)?;
+        let index = map.indexes.iter().find(|i| field == &i.field)?;
+        let module: String = module.iter().map(|m| format!("{}::", m)).collect();
+        Some((map, index, module))
+    }
+}
+
+impl<'m, 'ast> Visit<'ast> for IndexCalls<'m, 'ast> {
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        let range = range_of(node);
+        if let Expr::MethodCall(inner) = &*node.receiver {
+            if let Some((map, index, module)) = self.index_of(&inner.receiver) {
+                let inner_range = range_of(inner);
+                self.handled.insert((inner_range.start, inner_range.end));
+                self.calls.push(IndexCall {
+                    map,
+                    index,
+                    module,
+                    call: inner,
+                    parent: Some(node),
+                });
+            }
+        }
+        if !self.handled.contains(&(range.start, range.end)) {
+            if let Some((map, index, module)) = self.index_of(&node.receiver) {
+                self.calls.push(IndexCall {
+                    map,
+                    index,
+                    module,
+                    call: node,
+                    parent: None,
+                });
+            }
+        }
+        visit::visit_expr_method_call(self, node);
+    }
+}
+
+/// `use crate::state::{tokens, Token};` → `use crate::state::{save_tokens, Token, TOKENS};`
+/// in files that use `used` rather than the map's function now.
+pub(crate) fn rewrite_function_imports(
+    file: &SourceFile,
+    maps: &[IndexedMap],
+    used: &BTreeSet<String>,
+    rec: &mut Recorder,
+) {
+    let mut uses = UseFinder::default();
+    uses.visit_file(&file.ast);
+
+    for item in uses.uses {
+        for leaf in use_leaves(&item.tree) {
+            let Some(map) = maps.iter().find(|m| {
+                m.decl.file != file.path
+                    && leaf.name.as_deref() == Some(m.function.as_str())
+                    && leaf.rename.is_none()
+            }) else {
+                continue;
+            };
+            let names: Vec<String> = used
+                .iter()
+                .filter(|name| {
+                    **name == map.decl.name
+                        || **name == format!("save_{}", map.function)
+                        || **name == format!("remove_{}", map.function)
+                        || map.indexes.iter().any(|i| **name == map.lookup(i))
+                })
+                .cloned()
+                .collect();
+            if names.is_empty() {
+                continue;
+            }
+            let line = line_of(item);
+            let what = format!("`{}` import", map.function);
+            match replace_use_leaf(item, &map.function, &names) {
+                Some(edit) => {
+                    rec.edit(&file.path, edit);
+                    rec.applied(
+                        &file.path,
+                        line,
+                        format!("{} → `{}`", what, names.join("`, `")),
+                    );
+                }
+                None => rec.skipped(
+                    &file.path,
+                    line,
+                    what,
+                    format!("import `{}` instead", names.join("`, `")),
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::CrateSources;
+    use crate::storage::migrate_storage;
+    use crate::transform::Status;
+    use std::path::Path;
+
+    const STATE: &str = r#"use cosmwasm_std::Addr;
+use cw_storage_plus::{Index, IndexList, IndexedMap, MultiIndex, UniqueIndex};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Token {
+    pub owner: Addr,
+    pub serial: u64,
+}
+
+pub struct TokenIndexes<'a> {
+    pub owner: MultiIndex<'a, Addr, Token, String>,
+    pub serial: UniqueIndex<'a, u64, Token, String>,
+}
+
+impl<'a> IndexList<Token> for TokenIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Token>> + '_> {
+        let v: Vec<&dyn Index<Token>> = vec![&self.owner, &self.serial];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn tokens<'a>() -> IndexedMap<'a, &'a str, Token, TokenIndexes<'a>> {
+    let indexes = TokenIndexes {
+        owner: MultiIndex::new(|_pk, d| d.owner.clone(), "tokens", "tokens__owner"),
+        serial: UniqueIndex::new(|d| d.serial, "tokens__serial"),
+    };
+    IndexedMap::new("tokens", indexes)
+}
+"#;
+
+    const CONTRACT: &str = r#"use cosmwasm_std::{Addr, Deps, DepsMut, Order, StdResult};
+
+use crate::state::{tokens, Token};
+
+pub fn mint(deps: DepsMut, id: String, owner: Addr, serial: u64) -> StdResult<()> {
+    tokens().save(deps.storage, &id, &Token { owner, serial })
+}
+
+pub fn burn(deps: DepsMut, id: String) -> StdResult<()> {
+    tokens().remove(deps.storage, &id)
+}
+
+pub fn tokens_of(deps: Deps, owner: Addr) -> StdResult<Vec<(String, Token)>> {
+    tokens()
+        .idx
+        .owner
+        .prefix(owner)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect()
+}
+
+pub fn by_serial(deps: Deps, serial: u64) -> StdResult<Option<Token>> {
+    Ok(tokens().idx.serial.item(deps.storage, serial)?.map(|(_, t)| t))
+}
+"#;
+
+    #[test]
+    fn indexes_become_keymaps_kept_in_step() {
+        let krate =
+            CrateSources::from_files("", [("src/state.rs", STATE), ("src/contract.rs", CONTRACT)])
+                .unwrap();
+        let migration = migrate_storage(&krate);
+        let state = &migration.files[Path::new("src/state.rs")];
+        let contract = &migration.files[Path::new("src/contract.rs")];
+
+        assert!(state.starts_with(
+            "use cosmwasm_std::{Addr, StdError, StdResult, Storage};\n\
+             use secret_toolkit::storage::Keymap;\n"
+        ));
+        assert!(!state.contains("TokenIndexes"));
+        assert!(
+            state.contains("pub const TOKENS: Keymap<String, Token> = Keymap::new(b\"tokens\");\n")
+        );
+        assert!(state.contains(
+            "pub const TOKENS_BY_OWNER: Keymap<String, ()> = Keymap::new(b\"tokens__owner\");\n"
+        ));
+        assert!(state.contains(
+            "pub const TOKENS_BY_SERIAL: Keymap<u64, String> = Keymap::new(b\"tokens__serial\");\n"
+        ));
+        assert!(state.contains("fn tokens_owner(d: &Token) -> Addr {\n    d.owner.clone()\n}"));
+        assert!(state.contains(
+            "pub fn save_tokens(store: &mut dyn Storage, key: &str, value: &Token) -> StdResult<()> {\n    \
+             let key = &key.to_string();\n    \
+             let serial = tokens_serial(value);\n"
+        ));
+        assert!(state.contains(
+            "    if let Some(old) = TOKENS.get(store, key) {\n        \
+             TOKENS_BY_OWNER\n            \
+             .add_suffix(tokens_owner(&old).as_bytes())\n            \
+             .remove(store, key)?;\n        \
+             TOKENS_BY_SERIAL.remove(store, &tokens_serial(&old))?;\n        \
+             TOKENS.remove(store, key)?;\n    }\n    Ok(())\n"
+        ));
+        assert!(state.contains(
+            "pub fn tokens_by_owner(store: &dyn Storage, owner: &Addr) -> StdResult<Vec<(String, Token)>> {"
+        ));
+        assert!(state.contains(
+            "pub fn tokens_by_serial(store: &dyn Storage, serial: &u64) -> Option<(String, Token)> {"
+        ));
+
+        assert!(contract
+            .contains("use crate::state::{remove_tokens, save_tokens, tokens_by_owner, Token};"));
+        assert!(contract.contains("    save_tokens(deps.storage, &id, &Token { owner, serial })\n"));
+        assert!(contract.contains("    remove_tokens(deps.storage, &id)\n"));
+        assert!(contract.contains(
+            "    tokens_by_owner(deps.storage, &owner)?\n        .into_iter()\n        .map(Ok)\n        .collect()"
+        ));
+
+        let warned: Vec<_> = migration.warnings().map(|t| t.line).collect();
+        assert_eq!(vec![14], warned);
+        let skipped: Vec<_> = migration.skipped().collect();
+        assert_eq!(1, skipped.len());
+        assert_eq!(23, skipped[0].line);
+        assert!(matches!(
+            &skipped[0].status,
+            Status::Skipped { reason } if reason.contains("`tokens_by_serial`")
+        ));
+    }
+}
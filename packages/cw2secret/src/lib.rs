@@ -0,0 +1,25 @@
+pub mod auth;
+pub mod code_hash;
+pub mod config;
+pub mod contract_version;
+pub mod dependencies;
+pub mod diff;
+mod imports;
+mod indexed_map;
+pub mod lint;
+pub mod manifest;
+pub mod migrate;
+pub mod multi_test;
+pub mod package;
+pub mod privacy;
+pub mod rule;
+pub mod snip20;
+pub mod source;
+pub mod storage;
+pub mod transform;
+pub mod versions;
+pub mod workspace;
+
+mod error;
+
+pub use crate::error::MigrateError;
@@ -0,0 +1,483 @@
+//! Flags CosmWasm APIs and dependencies that don't carry over to Secret Network.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use syn::visit::{self, Visit};
+
+use crate::code_hash::thread_code_hashes;
+use crate::contract_version::replace_cw2;
+use crate::manifest::{Dependency, Manifest};
+use crate::snip20::translate_cw20;
+use crate::source::{line_of, CrateSources, SourceFile};
+use crate::storage::migrate_storage;
+use crate::transform::Status;
+use crate::versions::VersionMap;
+
+/// Upstream `cosmwasm-std` features the Secret fork doesn't provide.
+const UNSUPPORTED_STD_FEATURES: &[&str] = &["stargate", "ibc3", "cosmwasm_1_2"];
+
+const CW2_SUGGESTION: &str = "Secret has no `cw2`; keep the contract version in a \
+                              `secret_toolkit` `Item` with the same functions (pathway step 4b)";
+
+/// Upstream crates that have to be swapped for their Secret counterparts.
+const REPLACED_DEPENDENCIES: &[(&str, &str, bool)] = &[
+    (
+        "cosmwasm-std",
+        "use the scrtlabs fork, `tag = \"v1.1.9-secret\"` with `default-features = false` (pathway step 3b)",
+        true,
+    ),
+    (
+        "cosmwasm-storage",
+        "use the scrtlabs fork, `tag = \"v1.1.9-secret\"` (pathway step 3b)",
+        true,
+    ),
+    (
+        "cw-storage-plus",
+        "replace with `secret-toolkit`'s `storage` feature (pathway step 4a)",
+        true,
+    ),
+    (
+        "cw2",
+        "remove it once the contract version is stored with `secret-toolkit` (pathway step 4b)",
+        false,
+    ),
+    (
+        "cw-multi-test",
+        "replace with `secret-multi-test` (pathway step 2a)",
+        true,
+    ),
+    (
+        "cw20",
+        "use `secret-toolkit`'s `snip20` feature instead",
+        false,
+    ),
+];
+
+/// Entry points the Secret fork pinned by the tutorial can't export.
+const UNSUPPORTED_ENTRY_POINTS: &[(&str, &str)] = &[
+    (
+        "migrate",
+        "Secret contracts can't be migrated in place; remove the entry point (pathway step 4b)",
+    ),
+    ("sudo", "Secret has no `sudo` entry point; remove it"),
+];
+
+const IBC_ENTRY_POINTS: &[&str] = &[
+    "ibc_channel_open",
+    "ibc_channel_connect",
+    "ibc_channel_close",
+    "ibc_packet_receive",
+    "ibc_packet_ack",
+    "ibc_packet_timeout",
+];
+
+/// One incompatibility found in the crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub lint: &'static str,
+    pub file: PathBuf,
+    pub line: usize,
+    pub message: String,
+    /// What the tutorial replaces it with.
+    pub suggestion: String,
+    /// Whether `cw2secret` can make the change itself.
+    pub fixable: bool,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}:{}: [{}] {}",
+            self.file.display(),
+            self.line,
+            self.lint,
+            self.message
+        )?;
+        write!(
+            f,
+            "    = help: {}{}",
+            self.suggestion,
+            if self.fixable { " (auto-fixable)" } else { "" }
+        )
+    }
+}
+
+/// Lints a contract crate's manifest and sources.
+pub fn lint(krate: &CrateSources, manifest: &Manifest) -> Vec<Finding> {
+    let mut findings = lint_manifest(manifest);
+
+    for file in &krate.files {
+        let mut visitor = SourceLints {
+            file,
+            findings: vec![],
+        };
+        visitor.visit_file(&file.ast);
+        findings.extend(visitor.findings);
+    }
+
+    // messages whose code hash `migrate` can thread through
+    let threaded: Vec<_> = thread_code_hashes(krate)
+        .applied()
+        .map(|t| (t.file.clone(), t.line))
+        .collect();
+    // `cw2` goes as a whole or not at all
+    let cw2_replaced = replace_cw2(krate, manifest).skipped().next().is_none();
+    let cw20_replaced = translate_cw20(krate).skipped().next().is_none();
+    for finding in &mut findings {
+        match finding.lint {
+            "code-hash" => {
+                finding.fixable = threaded.contains(&(finding.file.clone(), finding.line))
+            }
+            "cw2" => finding.fixable = cw2_replaced,
+            "dependency" if finding.message.starts_with("`cw2`") => finding.fixable = cw2_replaced,
+            "cw20" => finding.fixable = cw20_replaced,
+            "dependency" if finding.message.starts_with("`cw20`") => {
+                finding.fixable = cw20_replaced
+            }
+            _ => {}
+        }
+    }
+
+    findings.extend(
+        migrate_storage(krate)
+            .transformations
+            .into_iter()
+            .map(|t| match t.status {
+                Status::Applied => Finding {
+                    lint: "storage",
+                    file: t.file,
+                    line: t.line,
+                    message: "`cw_storage_plus` storage".to_string(),
+                    suggestion: t.description,
+                    fixable: true,
+                },
+                Status::Warned { warning } => Finding {
+                    lint: "storage",
+                    file: t.file,
+                    line: t.line,
+                    message: "`cw_storage_plus` storage".to_string(),
+                    suggestion: format!("{}; {}", t.description, warning),
+                    fixable: true,
+                },
+                Status::Skipped { reason } => Finding {
+                    lint: "storage",
+                    file: t.file,
+                    line: t.line,
+                    message: t.description,
+                    suggestion: reason,
+                    fixable: false,
+                },
+            }),
+    );
+
+    findings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    findings
+}
+
+/// Whether `migrate` can move `cosmwasm-std` and its features to a fork.
+fn std_features_supported(dep: &Dependency) -> bool {
+    let versions = VersionMap::default();
+    let release = match dep.version.as_deref() {
+        Some(version) => versions.release_for(version),
+        None => versions.releases.first(),
+    };
+    release.is_some_and(|r| dep.features.iter().all(|f| r.std_features.contains(f)))
+}
+
+fn lint_manifest(manifest: &Manifest) -> Vec<Finding> {
+    let mut findings = vec![];
+    for dep in manifest.dependencies() {
+        let Some((_, suggestion, fixable)) = REPLACED_DEPENDENCIES
+            .iter()
+            .find(|(name, ..)| *name == dep.name)
+        else {
+            continue;
+        };
+        // already pointing at the scrtlabs fork, from git or crates.io
+        if dep
+            .git
+            .as_deref()
+            .is_some_and(|git| git.contains("scrtlabs"))
+            || dep.package.as_deref() == Some(&format!("secret-{}", dep.name))
+        {
+            continue;
+        }
+        findings.push(Finding {
+            lint: "dependency",
+            file: manifest.path.clone(),
+            line: dep.line,
+            message: format!("`{}` in [{}]", dep.name, dep.section),
+            suggestion: suggestion.to_string(),
+            // `migrate` leaves features the fork lacks for a human to port
+            fixable: *fixable && (dep.name != "cosmwasm-std" || std_features_supported(&dep)),
+        });
+
+        if dep.name == "cosmwasm-std" {
+            for feature in dep
+                .features
+                .iter()
+                .filter(|f| UNSUPPORTED_STD_FEATURES.contains(&f.as_str()))
+            {
+                findings.push(Finding {
+                    lint: "std-feature",
+                    file: manifest.path.clone(),
+                    line: dep.line,
+                    message: format!("`cosmwasm-std` feature `{}`", feature),
+                    suggestion: "drop the feature; the Secret fork doesn't provide it".to_string(),
+                    fixable: false,
+                });
+            }
+        }
+    }
+    findings
+}
+
+struct SourceLints<'a> {
+    file: &'a SourceFile,
+    findings: Vec<Finding>,
+}
+
+impl<'a> SourceLints<'a> {
+    fn push(
+        &mut self,
+        lint: &'static str,
+        line: usize,
+        message: String,
+        suggestion: &str,
+        fixable: bool,
+    ) {
+        self.findings.push(Finding {
+            lint,
+            file: self.file.path.clone(),
+            line,
+            message,
+            suggestion: suggestion.to_string(),
+            fixable,
+        });
+    }
+}
+
+fn is_entry_point(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        quote::ToTokens::to_token_stream(attr)
+            .to_string()
+            .contains("entry_point")
+    })
+}
+
+impl<'a, 'ast> Visit<'ast> for SourceLints<'a> {
+    fn visit_item_use(&mut self, node: &'ast syn::ItemUse) {
+        let syn::UseTree::Path(root) = &node.tree else {
+            return;
+        };
+        let line = line_of(node);
+        match root.ident.to_string().as_str() {
+            "cw2" => self.push(
+                "cw2",
+                line,
+                "`cw2` import".to_string(),
+                CW2_SUGGESTION,
+                false,
+            ),
+            "cw_multi_test" => self.push(
+                "multi-test",
+                line,
+                "`cw_multi_test` import".to_string(),
+                "port the tests to `secret-multi-test` (pathway step 2a)",
+                true,
+            ),
+            "cw20" => self.push(
+                "cw20",
+                line,
+                "`cw20` import".to_string(),
+                "use SNIP-20 messages from `secret_toolkit::snip20`",
+                false,
+            ),
+            _ => {}
+        }
+        let tokens = quote::ToTokens::to_token_stream(&node.tree).to_string();
+        if root.ident == "cosmwasm_std" && tokens.contains("Ibc") {
+            self.push(
+                "ibc",
+                line,
+                "IBC types from `cosmwasm_std`".to_string(),
+                "the `v1.1.9-secret` fork has no IBC support; drop the IBC handlers",
+                false,
+            );
+        }
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        if is_entry_point(&node.attrs) {
+            let name = node.sig.ident.to_string();
+            let line = line_of(&node.sig.ident);
+            if IBC_ENTRY_POINTS.contains(&name.as_str()) {
+                self.push(
+                    "ibc",
+                    line,
+                    format!("IBC entry point `{}`", name),
+                    "the `v1.1.9-secret` fork has no IBC entry points; remove it",
+                    false,
+                );
+            } else if let Some((_, suggestion)) =
+                UNSUPPORTED_ENTRY_POINTS.iter().find(|(n, _)| *n == name)
+            {
+                self.push(
+                    "entry-point",
+                    line,
+                    format!("`{}` entry point", name),
+                    suggestion,
+                    false,
+                );
+            }
+        }
+        visit::visit_item_fn(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(func) = &*node.func {
+            let last = func.path.segments.last().map(|s| s.ident.to_string());
+            if let Some(name @ ("set_contract_version" | "get_contract_version")) = last.as_deref()
+            {
+                self.push(
+                    "cw2",
+                    line_of(node),
+                    format!("`cw2::{}` call", name),
+                    CW2_SUGGESTION,
+                    false,
+                );
+            }
+        }
+        visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_struct(&mut self, node: &'ast syn::ExprStruct) {
+        let path = quote::ToTokens::to_token_stream(&node.path)
+            .to_string()
+            .replace(' ', "");
+        let needs_hash = [
+            "WasmMsg::Execute",
+            "WasmMsg::Instantiate",
+            "WasmQuery::Smart",
+        ]
+        .iter()
+        .any(|p| path.ends_with(p));
+        let has_hash = node.fields.iter().any(|f| match &f.member {
+            syn::Member::Named(ident) => ident == "code_hash",
+            syn::Member::Unnamed(_) => false,
+        });
+        if needs_hash && !has_hash {
+            self.push(
+                "code-hash",
+                line_of(node),
+                format!("`{}` without a `code_hash`", path),
+                "Secret requires the callee's `code_hash` on every contract call and query",
+                false,
+            );
+        }
+        visit::visit_expr_struct(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn contract(name: &str) -> (CrateSources, Manifest) {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../contracts")
+            .join(name);
+        (
+            CrateSources::load(&root).unwrap(),
+            Manifest::load(&root).unwrap(),
+        )
+    }
+
+    fn summary(findings: &[Finding]) -> Vec<(&'static str, String, usize, bool)> {
+        findings
+            .iter()
+            .map(|f| (f.lint, f.file.display().to_string(), f.line, f.fixable))
+            .collect()
+    }
+
+    #[test]
+    fn cw_counter() {
+        let (krate, manifest) = contract("cw-counter");
+        let findings = lint(&krate, &manifest);
+        let expected = vec![
+            ("dependency", "Cargo.toml", 44, true),
+            ("dependency", "Cargo.toml", 45, true),
+            ("dependency", "Cargo.toml", 46, true),
+            ("dependency", "Cargo.toml", 47, true),
+            ("dependency", "Cargo.toml", 53, true),
+            ("cw2", "src/contract.rs", 4, true),
+            ("cw2", "src/contract.rs", 25, true),
+            ("storage", "src/contract.rs", 51, true),
+            ("storage", "src/contract.rs", 57, true),
+            ("storage", "src/contract.rs", 61, true),
+            ("storage", "src/contract.rs", 88, true),
+            ("code-hash", "src/helpers.rs", 22, true),
+            ("code-hash", "src/helpers.rs", 38, true),
+            ("multi-test", "src/integration_tests.rs", 6, true),
+            ("storage", "src/state.rs", 5, true),
+            ("storage", "src/state.rs", 13, true),
+            ("storage", "src/state.rs", 20, true),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(lint, file, line, fixable)| (lint, file.to_string(), line, fixable))
+            .collect();
+        assert_eq!(expected, summary(&findings));
+    }
+
+    #[test]
+    fn secret_counter_is_clean() {
+        let (krate, manifest) = contract("secret-counter");
+        assert!(lint(&krate, &manifest).is_empty());
+    }
+
+    #[test]
+    fn ibc_entry_points_and_std_features() {
+        let krate = CrateSources::from_files(
+            "",
+            [(
+                "src/contract.rs",
+                r#"
+use cosmwasm_std::{IbcBasicResponse, IbcChannelOpenMsg};
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_open(deps: DepsMut, env: Env, msg: IbcChannelOpenMsg) -> StdResult<()> {
+    Ok(())
+}
+
+#[entry_point]
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> StdResult<Response> {
+    Ok(Response::new())
+}
+"#,
+            )],
+        )
+        .unwrap();
+        let manifest = Manifest::parse(
+            "[package]\nname = \"x\"\n\n[dependencies]\ncosmwasm-std = { version = \"1.1\", features = [\"stargate\", \"staking\"] }\n",
+        )
+        .unwrap();
+
+        let findings = lint(&krate, &manifest);
+        let lints: Vec<_> = findings.iter().map(|f| (f.lint, f.line)).collect();
+        assert_eq!(
+            vec![
+                ("dependency", 5),
+                ("std-feature", 5),
+                ("ibc", 2),
+                ("ibc", 5),
+                ("entry-point", 10)
+            ],
+            lints
+        );
+        assert!(findings.iter().all(|f| !f.fixable));
+    }
+}
@@ -0,0 +1,316 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+
+use cw2secret::auth::scaffold_auth;
+use cw2secret::config::Config;
+use cw2secret::diff::unified_diff;
+use cw2secret::lint::lint;
+use cw2secret::manifest::Manifest;
+use cw2secret::migrate::{migrate_crate, MigrateOptions, Report};
+use cw2secret::privacy::{analyze_privacy, Severity};
+use cw2secret::source::CrateSources;
+use cw2secret::storage::migrate_storage_with;
+use cw2secret::transform::Migration;
+use cw2secret::versions::VersionMap;
+use cw2secret::workspace::{sync_member, FileStatus, Workspace};
+use cw2secret::MigrateError;
+
+#[derive(Parser)]
+#[command(
+    name = "cw2secret",
+    version,
+    about = "Migrates CosmWasm contracts to Secret Network"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run every rule: code hashes, multi-tests, package name, storage, cw2 and dependencies
+    Migrate {
+        /// Contract crate directory, e.g. contracts/cw-counter
+        crate_dir: PathBuf,
+        /// New package name [default: `cw-` prefix replaced with `secret-`]
+        #[arg(long)]
+        name: Option<String>,
+        /// Write the migrated crate back instead of printing a unified diff
+        #[arg(long)]
+        write: bool,
+        /// Also write a JSON report of applied and skipped transformations
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+        /// Version map to use instead of the built-in versions.toml
+        #[arg(long, value_name = "FILE")]
+        versions: Option<PathBuf>,
+    },
+    /// Migrate every contract of a workspace into a sibling crate, e.g. cw-counter into secret-counter
+    Workspace {
+        /// Workspace root holding the `[workspace]` Cargo.toml
+        #[arg(default_value = ".")]
+        root: PathBuf,
+        /// Member to migrate; repeat for several [default: every member on upstream cosmwasm-std]
+        #[arg(long = "member", value_name = "DIR")]
+        members: Vec<PathBuf>,
+        /// Version map to use instead of the built-in versions.toml
+        #[arg(long, value_name = "FILE")]
+        versions: Option<PathBuf>,
+        /// Write the new crates and add them to the workspace instead of only reporting
+        #[arg(long)]
+        write: bool,
+    },
+    /// Rewrite `cw_storage_plus::{Item, Map}` to `secret_toolkit::storage::{Item, Keymap}`
+    Storage {
+        /// Contract crate directory, e.g. contracts/cw-counter
+        crate_dir: PathBuf,
+        /// Write the rewritten files back instead of only reporting
+        #[arg(long)]
+        write: bool,
+    },
+    /// Report CosmWasm APIs and dependencies that don't work on Secret Network
+    Lint {
+        /// Contract crate directory, e.g. contracts/cw-counter
+        crate_dir: PathBuf,
+    },
+    /// Rank queries and events that disclose per-address data
+    Privacy {
+        /// Contract crate directory, e.g. contracts/cw-counter
+        crate_dir: PathBuf,
+    },
+    /// Generate viewing key and permit support for the given queries
+    Auth {
+        /// Contract crate directory, e.g. contracts/cw-counter
+        crate_dir: PathBuf,
+        /// `QueryMsg` variant to protect; repeat for several [default: from cw2secret.toml]
+        #[arg(long = "query")]
+        queries: Vec<String>,
+        /// Field of the protected queries holding the queried address [default: addr]
+        #[arg(long)]
+        addr_field: Option<String>,
+        /// Name of the permissions enum [default: derived from the crate name]
+        #[arg(long)]
+        permissions: Option<String>,
+        /// Write the rewritten files back instead of only reporting
+        #[arg(long)]
+        write: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode, MigrateError> {
+    match cli.command {
+        Command::Migrate {
+            crate_dir,
+            name,
+            write,
+            report,
+            versions,
+        } => {
+            let krate = CrateSources::load(&crate_dir)?;
+            let manifest = Manifest::load(&crate_dir)?;
+            let config = Config::load(&crate_dir)?;
+            let options = MigrateOptions {
+                name,
+                versions: load_versions(versions)?,
+                config,
+                ..MigrateOptions::default()
+            };
+            let migration = migrate_crate(&krate, &manifest, &options)?;
+            if write {
+                print_report(&migration);
+                write_files(&krate, &migration)?;
+            } else {
+                // the diff goes to stdout so it can be piped into a patch file
+                print!("{}", unified_diff(&krate, &manifest, &migration));
+                for warning in migration.warnings() {
+                    eprintln!("{}", warning);
+                }
+                eprintln!(
+                    "{} transformations applied ({} with warnings), {} skipped",
+                    migration.applied().count(),
+                    migration.warnings().count(),
+                    migration.skipped().count()
+                );
+            }
+            if let Some(path) = report {
+                let crate_name = manifest.package_name().unwrap_or_default();
+                let json = Report::new(crate_name, &migration).to_json();
+                fs::write(&path, json + "\n").map_err(|e| MigrateError::io(&path, e))?;
+            }
+        }
+        Command::Workspace {
+            root,
+            members,
+            versions,
+            write,
+        } => {
+            let workspace = Workspace::load(&root)?;
+            let members = match members {
+                members if members.is_empty() => workspace.contracts()?,
+                members => members,
+            };
+            let options = MigrateOptions {
+                versions: load_versions(versions)?,
+                ..MigrateOptions::default()
+            };
+            let mut synced = vec![];
+            for member in &members {
+                let sync = sync_member(&workspace, member, &options)?;
+                println!("{} → {}", member.display(), sync.target.display());
+                for (path, status) in &sync.files {
+                    let status = match status {
+                        FileStatus::Created => "created",
+                        FileStatus::Updated => "updated",
+                        FileStatus::Removed => "removed",
+                        FileStatus::Kept => "kept, edited by hand",
+                        FileStatus::Conflict { .. } => "conflict, edited by hand",
+                        FileStatus::Unchanged => continue,
+                    };
+                    println!("  {}: {}", path.display(), status);
+                }
+                for transformation in sync.new_transformations() {
+                    println!("  {}", transformation);
+                }
+                for status in sync.files.values() {
+                    if let FileStatus::Conflict { diff } = status {
+                        print!("{}", diff);
+                    }
+                }
+                if write {
+                    sync.write(&workspace.root)?;
+                }
+                synced.push(sync);
+            }
+            if write {
+                if let Some(manifest) = workspace.with_members(&synced) {
+                    let path = workspace.root.join("Cargo.toml");
+                    fs::write(&path, manifest).map_err(|e| MigrateError::io(&path, e))?;
+                }
+            }
+        }
+        Command::Storage { crate_dir, write } => {
+            let krate = CrateSources::load(&crate_dir)?;
+            let config = Config::load(&crate_dir)?;
+            let mut migration = migrate_storage_with(&krate, &config.storage_keys);
+            config.drop_skipped(&mut migration);
+            print_report(&migration);
+            if write {
+                write_files(&krate, &migration)?;
+            }
+        }
+        Command::Lint { crate_dir } => {
+            let krate = CrateSources::load(&crate_dir)?;
+            let manifest = Manifest::load(&crate_dir)?;
+            let findings = lint(&krate, &manifest);
+            for finding in &findings {
+                println!("{}", finding);
+            }
+            let fixable = findings.iter().filter(|f| f.fixable).count();
+            println!(
+                "{} incompatibilities found, {} auto-fixable",
+                findings.len(),
+                fixable
+            );
+            if !findings.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Privacy { crate_dir } => {
+            let krate = CrateSources::load(&crate_dir)?;
+            let risks = analyze_privacy(&krate);
+            for risk in &risks {
+                println!("{}", risk);
+            }
+            let high = risks
+                .iter()
+                .filter(|r| r.severity == Severity::High)
+                .count();
+            println!("{} privacy risks found, {} high", risks.len(), high);
+            if high > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Auth {
+            crate_dir,
+            queries,
+            addr_field,
+            permissions,
+            write,
+        } => {
+            let krate = CrateSources::load(&crate_dir)?;
+            let manifest = Manifest::load(&crate_dir)?;
+            let crate_name = match manifest.package_name() {
+                Some(name) => name.to_string(),
+                None => crate_dir
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            };
+            let config = Config::load(&crate_dir)?;
+            let mut options = config.auth_options(&crate_name);
+            if !queries.is_empty() {
+                options.queries = queries;
+            }
+            if let Some(addr_field) = addr_field {
+                options.addr_field = addr_field;
+            }
+            if let Some(permissions) = permissions {
+                options.permissions = permissions;
+            }
+            if options.queries.is_empty() {
+                return Err(MigrateError::Scaffold(
+                    "no queries to protect; pass --query or list them under [auth] in cw2secret.toml"
+                        .to_string(),
+                ));
+            }
+            let mut migration = scaffold_auth(&krate, &options)?;
+            config.drop_skipped(&mut migration);
+            print_report(&migration);
+            if write {
+                write_files(&krate, &migration)?;
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn load_versions(path: Option<PathBuf>) -> Result<VersionMap, MigrateError> {
+    match path {
+        Some(path) => {
+            let text = fs::read_to_string(&path).map_err(|e| MigrateError::io(&path, e))?;
+            VersionMap::parse(&text)
+        }
+        None => Ok(VersionMap::default()),
+    }
+}
+
+fn print_report(migration: &Migration) {
+    for transformation in &migration.transformations {
+        println!("{}", transformation);
+    }
+}
+
+fn write_files(krate: &CrateSources, migration: &Migration) -> Result<(), MigrateError> {
+    for (path, text) in &migration.files {
+        let path = krate.root.join(path);
+        fs::write(&path, text).map_err(|e| MigrateError::io(&path, e))?;
+    }
+    for path in &migration.removed {
+        let path = krate.root.join(path);
+        fs::remove_file(&path).map_err(|e| MigrateError::io(&path, e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use toml_edit::{DocumentMut, ImDocument, Item, Table};
+
+use crate::error::MigrateError;
+
+/// A crate's `Cargo.toml`, kept as text so edits preserve its formatting.
+pub struct Manifest {
+    /// Path relative to the crate root, i.e. `Cargo.toml`.
+    pub path: PathBuf,
+    pub text: String,
+    pub doc: DocumentMut,
+}
+
+/// A dependency as declared in a manifest section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dependency {
+    pub section: String,
+    pub name: String,
+    pub line: usize,
+    pub version: Option<String>,
+    pub features: Vec<String>,
+    pub default_features: bool,
+    pub git: Option<String>,
+    /// The package's own name, when the dependency renames it.
+    pub package: Option<String>,
+}
+
+pub const DEPENDENCY_SECTIONS: &[&str] =
+    &["dependencies", "dev-dependencies", "build-dependencies"];
+
+pub const PATCH_SECTION: &str = "patch.crates-io";
+
+impl Manifest {
+    pub fn load(root: impl AsRef<Path>) -> Result<Self, MigrateError> {
+        let path = root.as_ref().join("Cargo.toml");
+        let text = fs::read_to_string(&path).map_err(|e| MigrateError::io(&path, e))?;
+        Manifest::parse(text)
+    }
+
+    pub fn parse(text: impl Into<String>) -> Result<Self, MigrateError> {
+        let text = text.into();
+        let doc = text
+            .parse::<DocumentMut>()
+            .map_err(|source| MigrateError::Manifest {
+                path: PathBuf::from("Cargo.toml"),
+                source,
+            })?;
+        Ok(Manifest {
+            path: PathBuf::from("Cargo.toml"),
+            text,
+            doc,
+        })
+    }
+
+    pub fn package_name(&self) -> Option<&str> {
+        self.doc.get("package")?.get("name")?.as_str()
+    }
+
+    pub fn dependencies(&self) -> Vec<Dependency> {
+        DEPENDENCY_SECTIONS
+            .iter()
+            .flat_map(|section| self.dependencies_in(section))
+            .collect()
+    }
+
+    /// Entries of `[patch.crates-io]`.
+    pub fn patches(&self) -> Vec<Dependency> {
+        self.dependencies_in(PATCH_SECTION)
+    }
+
+    fn dependencies_in(&self, section: &str) -> Vec<Dependency> {
+        let mut deps = vec![];
+        let Some(table) = item(self.doc.as_table(), section).and_then(|t| t.as_table_like()) else {
+            return deps;
+        };
+        for (name, item) in table.iter() {
+            let mut dep = Dependency {
+                section: section.to_string(),
+                name: name.to_string(),
+                line: self.line_of_key(section, name).unwrap_or(1),
+                version: item.as_str().map(str::to_string),
+                features: vec![],
+                default_features: true,
+                git: None,
+                package: None,
+            };
+            if let Some(spec) = item.as_table_like() {
+                dep.version = spec
+                    .get("version")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string);
+                dep.git = spec.get("git").and_then(|v| v.as_str()).map(str::to_string);
+                dep.package = spec
+                    .get("package")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string);
+                dep.default_features = spec
+                    .get("default-features")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                dep.features = spec
+                    .get("features")
+                    .and_then(|v| v.as_array())
+                    .map(|a| {
+                        a.iter()
+                            .filter_map(|f| f.as_str())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+            }
+            deps.push(dep);
+        }
+        deps
+    }
+
+    pub fn dependency(&self, name: &str) -> Option<Dependency> {
+        self.dependencies().into_iter().find(|d| d.name == name)
+    }
+
+    /// 1-based line of `key` within `[section]`, or of a `[section.key]` header.
+    pub fn line_of_key(&self, section: &str, key: &str) -> Option<usize> {
+        let mut current = String::new();
+        for (i, line) in self.text.lines().enumerate() {
+            let trimmed = line.trim();
+            if let Some(header) = trimmed.strip_prefix('[').and_then(|h| h.split(']').next()) {
+                current = header.trim().to_string();
+                if current == format!("{}.{}", section, key) {
+                    return Some(i + 1);
+                }
+                continue;
+            }
+            if current == section {
+                let name = trimmed.split('=').next().unwrap_or_default().trim();
+                if name == key || name.trim_matches('"') == key {
+                    return Some(i + 1);
+                }
+            }
+        }
+        None
+    }
+
+    /// Byte range of the value of `key` in `[section]`, e.g. of `"cw-counter"`
+    /// in `name = "cw-counter"`.
+    pub fn value_range(&self, section: &str, key: &str) -> Option<Range<usize>> {
+        let doc = ImDocument::parse(self.text.as_str()).ok()?;
+        let (_, item) = item(doc.as_table(), section)?
+            .as_table_like()?
+            .get_key_value(key)?;
+        item.as_value()?.span()
+    }
+
+    /// Byte range of a whole `key = value` entry in `[section]`, from the start
+    /// of the key to the end of the value. `None` for `[section.key]` tables.
+    pub fn entry_range(&self, section: &str, key: &str) -> Option<Range<usize>> {
+        let doc = ImDocument::parse(self.text.as_str()).ok()?;
+        let (key, item) = item(doc.as_table(), section)?
+            .as_table_like()?
+            .get_key_value(key)?;
+        Some(key.span()?.start..item.as_value()?.span()?.end)
+    }
+
+    /// Byte range of a `[section]` header line's text.
+    pub fn header_range(&self, section: &str) -> Option<Range<usize>> {
+        let doc = ImDocument::parse(self.text.as_str()).ok()?;
+        item(doc.as_table(), section)?.as_table()?.span()
+    }
+}
+
+/// The item at a dotted `section` path like `patch.crates-io`.
+fn item<'a>(root: &'a Table, section: &str) -> Option<&'a Item> {
+    let mut parts = section.split('.');
+    let mut item = root.get(parts.next()?)?;
+    for part in parts {
+        item = item.get(part)?;
+    }
+    Some(item)
+}
@@ -0,0 +1,303 @@
+//! Runs every migration rule over a crate, each on the output of the one before.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::code_hash::thread_code_hashes;
+use crate::config::Config;
+use crate::contract_version::replace_cw2;
+use crate::dependencies::replace_dependencies;
+use crate::error::MigrateError;
+use crate::manifest::Manifest;
+use crate::multi_test::port_multi_tests;
+use crate::package::rename_package;
+use crate::rule::Rule;
+use crate::snip20::translate_cw20;
+use crate::source::CrateSources;
+use crate::storage::migrate_storage_with;
+use crate::transform::{Migration, Transformation};
+use crate::versions::VersionMap;
+
+#[derive(Clone, Debug, Default)]
+pub struct MigrateOptions {
+    /// New package name; `cw-counter` becomes `secret-counter` by default.
+    pub name: Option<String>,
+    /// Fork versions to migrate to; the built-in `versions.toml` by default.
+    pub versions: VersionMap,
+    /// Settings from the contract's `cw2secret.toml`.
+    pub config: Config,
+    /// Downstream rules, run in order after the built-in ones.
+    pub rules: Vec<Arc<dyn Rule>>,
+}
+
+impl MigrateOptions {
+    /// Registers a rule to run after the built-in ones and any registered before it.
+    pub fn rule(mut self, rule: impl Rule + 'static) -> Self {
+        self.rules.push(Arc::new(rule));
+        self
+    }
+}
+
+/// Migrates a crate and its manifest. Nothing is written; the result holds
+/// the new contents of every changed file.
+pub fn migrate_crate(
+    krate: &CrateSources,
+    manifest: &Manifest,
+    options: &MigrateOptions,
+) -> Result<Migration, MigrateError> {
+    let config = &options.config;
+    let mut pipeline = Pipeline::new(krate, manifest, config)?;
+    pipeline.run(|k, _| translate_cw20(k))?;
+    pipeline.run(|k, _| thread_code_hashes(k))?;
+    pipeline.run(port_multi_tests)?;
+    pipeline.run(|k, m| rename_package(k, m, options.name.as_deref()))?;
+    pipeline.run(|k, _| migrate_storage_with(k, &config.storage_keys))?;
+    pipeline.run(replace_cw2)?;
+    pipeline.run(|k, m| replace_dependencies(k, m, &options.versions))?;
+    for rule in &options.rules {
+        pipeline.run(|k, m| rule.apply(k, m, config))?;
+    }
+    Ok(pipeline.finish(krate, manifest))
+}
+
+/// The crate as the rules run so far left it.
+struct Pipeline<'a> {
+    config: &'a Config,
+    krate: CrateSources,
+    manifest: Manifest,
+    removed: BTreeSet<PathBuf>,
+    transformations: Vec<Transformation>,
+}
+
+impl<'a> Pipeline<'a> {
+    fn new(
+        krate: &CrateSources,
+        manifest: &Manifest,
+        config: &'a Config,
+    ) -> Result<Self, MigrateError> {
+        Ok(Pipeline {
+            config,
+            krate: krate.with_changes([])?,
+            manifest: Manifest::parse(manifest.text.clone())?,
+            removed: BTreeSet::new(),
+            transformations: vec![],
+        })
+    }
+
+    fn run(
+        &mut self,
+        rule: impl FnOnce(&CrateSources, &Manifest) -> Migration,
+    ) -> Result<(), MigrateError> {
+        let mut migration = rule(&self.krate, &self.manifest);
+        self.config.drop_skipped(&mut migration);
+        if let Some(text) = migration.files.remove(&self.manifest.path) {
+            self.manifest = Manifest::parse(text)?;
+        }
+        self.krate = self.krate.with_changes(&migration.files)?;
+        self.krate
+            .files
+            .retain(|f| !migration.removed.contains(&f.path));
+        self.removed.extend(migration.removed);
+        self.transformations.extend(migration.transformations);
+        Ok(())
+    }
+
+    fn finish(self, krate: &CrateSources, manifest: &Manifest) -> Migration {
+        let mut files: BTreeMap<_, _> = self
+            .krate
+            .files
+            .into_iter()
+            .filter(|f| krate.file(&f.path).is_none_or(|old| old.text != f.text))
+            .map(|f| (f.path, f.text))
+            .collect();
+        if self.manifest.text != manifest.text {
+            files.insert(self.manifest.path, self.manifest.text);
+        }
+        Migration {
+            files,
+            removed: self.removed,
+            transformations: self.transformations,
+        }
+    }
+}
+
+/// Machine-readable summary of a migration, for review tooling.
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    #[serde(rename = "crate")]
+    pub krate: &'a str,
+    pub changed: Vec<&'a PathBuf>,
+    pub removed: Vec<&'a PathBuf>,
+    pub applied: usize,
+    pub warnings: usize,
+    pub skipped: usize,
+    pub transformations: &'a [Transformation],
+}
+
+impl<'a> Report<'a> {
+    pub fn new(krate: &'a str, migration: &'a Migration) -> Self {
+        Report {
+            krate,
+            changed: migration.files.keys().collect(),
+            removed: migration.removed.iter().collect(),
+            applied: migration.applied().count(),
+            warnings: migration.warnings().count(),
+            skipped: migration.skipped().count(),
+            transformations: &migration.transformations,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is always serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::Edit;
+    use crate::transform::Recorder;
+    use std::path::Path;
+    use toml_edit::DocumentMut;
+
+    fn contract(name: &str) -> (CrateSources, Manifest) {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../contracts")
+            .join(name);
+        (
+            CrateSources::load(&root).unwrap(),
+            Manifest::load(&root).unwrap(),
+        )
+    }
+
+    #[test]
+    fn cw_counter_follows_the_tutorial() {
+        let (cw, cw_manifest) = contract("cw-counter");
+        let (secret, secret_manifest) = contract("secret-counter");
+        let migration = migrate_crate(&cw, &cw_manifest, &MigrateOptions::default()).unwrap();
+
+        assert!(migration.removed.is_empty());
+        assert_eq!(
+            secret.file("src/bin/schema.rs").unwrap().text,
+            migration.files[Path::new("src/bin/schema.rs")]
+        );
+
+        // secret-counter is pinned past the tutorial's fork, for
+        // secret-multi-test's `env.block.random`; `secret-toolkit` gets its
+        // `permit`/`viewing-key` features from the auth scaffolding; and the
+        // port added test helpers of its own by hand
+        let mut expected: DocumentMut = secret_manifest
+            .text
+            .replace(
+                "{ package = \"secret-cosmwasm-std\", version = \"1.1.11\", default-features = false }",
+                "{ git = \"https://github.com/scrtlabs/cosmwasm/\", default-features = false, tag = \"v1.1.9-secret\" }",
+            )
+            .replace(
+                "{ package = \"secret-cosmwasm-storage\", version = \"1.1.11\" }",
+                "{ git = \"https://github.com/scrtlabs/cosmwasm/\", tag = \"v1.1.9-secret\" }",
+            )
+            .replace(
+                "{ version = \"0.10.0\", features = [\n  \"permit\",\n  \"viewing-key\",\n] }",
+                "{ git = \"https://github.com/scrtlabs/secret-toolkit\", rev = \"9b74bdac71c2fedcc12246f18cdfdd94b8991282\" }",
+            )
+            .parse()
+            .unwrap();
+        let migrated = &migration.files[Path::new("Cargo.toml")];
+        let generated = migrated.parse::<DocumentMut>().unwrap()["dev-dependencies"].clone();
+        expected["dev-dependencies"]
+            .as_table_mut()
+            .unwrap()
+            .retain(|name, _| generated.get(name).is_some());
+        assert_eq!(&expected.to_string(), migrated);
+
+        assert_eq!(0, migration.skipped().count());
+        let state = &migration.files[Path::new("src/state.rs")];
+        assert!(state.contains(
+            "pub const CONTRACT: Item<ContractVersion> = Item::new(b\"contract_info\");"
+        ));
+    }
+
+    #[test]
+    fn report_lists_files_and_statuses() {
+        let (cw, manifest) = contract("cw-counter");
+        let migration = migrate_crate(&cw, &manifest, &MigrateOptions::default()).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&Report::new("cw-counter", &migration).to_json()).unwrap();
+
+        assert_eq!("cw-counter", json["crate"]);
+        assert_eq!(
+            serde_json::json!([
+                "Cargo.toml",
+                "src/bin/schema.rs",
+                "src/contract.rs",
+                "src/helpers.rs",
+                "src/integration_tests.rs",
+                "src/state.rs"
+            ]),
+            json["changed"]
+        );
+        assert_eq!(migration.applied().count(), json["applied"]);
+        assert_eq!(0, json["skipped"]);
+        let cw2 = &json["transformations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|t| t["rule"] == "cw2")
+            .unwrap();
+        assert_eq!("src/contract.rs", cw2["file"]);
+        assert_eq!(4, cw2["line"]);
+        assert_eq!("applied", cw2["status"]);
+        assert!(cw2.get("reason").is_none());
+    }
+
+    struct Banner;
+
+    impl Rule for Banner {
+        fn name(&self) -> &'static str {
+            "banner"
+        }
+
+        fn apply(&self, krate: &CrateSources, _: &Manifest, _: &Config) -> Migration {
+            let mut rec = Recorder::new(self.name());
+            for file in &krate.files {
+                rec.edit(&file.path, Edit::insert(0, "// Migrated by cw2secret\n"));
+                rec.applied(&file.path, 1, "banner added");
+            }
+            rec.finish(krate)
+        }
+    }
+
+    #[test]
+    fn config_and_downstream_rules_shape_the_migration() {
+        let (cw, manifest) = contract("cw-counter");
+        let config = Config::parse(
+            "skip = [\"src/integration_tests.rs\"]\n\n[storage.keys]\nstate = \"counter_state\"\n",
+        )
+        .unwrap();
+        let options = MigrateOptions {
+            config,
+            ..MigrateOptions::default()
+        }
+        .rule(Banner);
+        let migration = migrate_crate(&cw, &manifest, &options).unwrap();
+
+        let skipped = Path::new("src/integration_tests.rs");
+        assert!(!migration.files.contains_key(skipped));
+        assert!(migration.transformations.iter().all(|t| t.file != skipped));
+
+        let state = &migration.files[Path::new("src/state.rs")];
+        assert!(state.starts_with("// Migrated by cw2secret\n"));
+        assert!(state.contains("Item::new(b\"counter_state\");"));
+        assert!(state.contains("Keymap::new(b\"user_state\");"));
+        assert!(migration.applied().any(|t| t
+            .description
+            .ends_with("namespace `state` renamed to `counter_state`")));
+        assert_eq!(
+            migration.files.len() - 1,
+            migration.applied().filter(|t| t.rule == "banner").count()
+        );
+    }
+}
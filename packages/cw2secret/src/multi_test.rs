@@ -0,0 +1,448 @@
+//! Step 1 of the migration pathway: `cw-multi-test` doesn't run against the
+//! Secret fork, so integration tests built on it are ported to
+//! `secret-multi-test`, whose API only differs where Secret needs code hashes.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use syn::visit::{self, Visit};
+
+use crate::imports::{use_leaves, UseFinder};
+use crate::manifest::Manifest;
+use crate::source::{line_of, range_of, CrateSources, Edit, SourceFile};
+use crate::transform::{Migration, Recorder};
+
+pub const RULE: &str = "multi-test";
+
+const HARNESS: &str = r#"secret-multi-test = { git = "https://github.com/itsHaseebSaeed/CW-to-Secret-Contract-Migration" }"#;
+/// Where the harness lives in this repository.
+const HARNESS_DIR: &str = "packages/secret-multi-test";
+
+/// `cw-multi-test` names `secret-multi-test` exports unchanged.
+const EXPORTS: &[&str] = &[
+    "AnyResult",
+    "App",
+    "AppBuilder",
+    "AppResponse",
+    "Contract",
+    "ContractWrapper",
+    "Executor",
+    "Router",
+];
+
+pub fn port_multi_tests(krate: &CrateSources, manifest: &Manifest) -> Migration {
+    let mut rec = Recorder::new(RULE);
+    let mut ported = false;
+    for file in &krate.files {
+        ported |= port_file(krate, file, &mut rec);
+    }
+    if ported {
+        replace_dependency(&harness(&krate.root), manifest, &mut rec);
+    }
+    rec.finish_with_manifest(krate, manifest)
+}
+
+/// Ports the `cw_multi_test` imports and calls in `file`; false if it has none.
+fn port_file(krate: &CrateSources, file: &SourceFile, rec: &mut Recorder) -> bool {
+    let mut uses = UseFinder::default();
+    uses.visit_file(&file.ast);
+    let roots: Vec<_> = uses
+        .uses
+        .iter()
+        .filter_map(|u| match &u.tree {
+            syn::UseTree::Path(p) if p.ident == "cw_multi_test" => Some(p),
+            _ => None,
+        })
+        .collect();
+    if roots.is_empty() {
+        return false;
+    }
+
+    for root in roots {
+        rec.edit(
+            &file.path,
+            Edit::replace(range_of(&root.ident), "secret_multi_test"),
+        );
+        rec.applied(
+            &file.path,
+            line_of(&root.ident),
+            "`cw_multi_test` import → `secret_multi_test`",
+        );
+        for name in use_leaves(&root.tree).into_iter().filter_map(|l| l.name) {
+            if !EXPORTS.contains(&name.as_str()) {
+                rec.skipped(
+                    &file.path,
+                    line_of(root),
+                    format!("`cw_multi_test::{}` import", name),
+                    "`secret-multi-test` has no counterpart; port the test by hand",
+                );
+            }
+        }
+    }
+
+    let mut calls = HarnessCalls::default();
+    calls.visit_file(&file.ast);
+    let instances = instances(&file.ast);
+    for call in calls.instantiates {
+        port_instantiate(file, call, rec);
+    }
+    for call in calls.executes {
+        port_execute(file, call, &instances, rec);
+    }
+    for call in calls.queries {
+        port_query(file, call, &instances, rec);
+    }
+    for (callee, arg) in calls.passed {
+        if !instances.contains(&arg.to_string()) || holds_contract_info(krate, callee) {
+            continue;
+        }
+        rec.skipped(
+            &file.path,
+            line_of(arg),
+            format!("`{}` passed to `{}`", arg, file.text_of(callee)),
+            format!(
+                "`{}` is now a `ContractInfo` with the address and code hash; \
+                 pass `{}.address` or keep the code hash alongside it",
+                arg, arg
+            ),
+        );
+    }
+    true
+}
+
+/// `instantiate_contract(code_id, sender, msg, funds, label, admin)` becomes
+/// `instantiate_contract(&code, sender, msg, funds, label)`.
+fn port_instantiate(file: &SourceFile, call: &syn::ExprMethodCall, rec: &mut Recorder) {
+    let args: Vec<&syn::Expr> = call.args.iter().collect();
+    let line = line_of(&call.method);
+    if args.len() != 6 {
+        rec.skipped(
+            &file.path,
+            line,
+            "`instantiate_contract` call",
+            "expected cw-multi-test's six arguments",
+        );
+        return;
+    }
+    if !matches!(args[5], syn::Expr::Path(p) if p.path.is_ident("None")) {
+        rec.skipped(
+            &file.path,
+            line_of(args[5]),
+            "`instantiate_contract` admin",
+            "Secret contracts have no admin; drop the argument and any migrate tests",
+        );
+        return;
+    }
+    if !matches!(args[0], syn::Expr::Reference(_)) {
+        rec.edit(&file.path, Edit::insert(range_of(args[0]).start, "&"));
+    }
+    rec.edit(
+        &file.path,
+        Edit::delete(range_of(args[4]).end..range_of(args[5]).end),
+    );
+    rec.applied(
+        &file.path,
+        line,
+        "`instantiate_contract` takes the `StoredCode` and no admin",
+    );
+}
+
+/// `execute_contract(sender, addr, msg, funds)` takes the `ContractInfo`
+/// `instantiate_contract` returned, so the code hash goes along.
+fn port_execute(
+    file: &SourceFile,
+    call: &syn::ExprMethodCall,
+    instances: &HashSet<String>,
+    rec: &mut Recorder,
+) {
+    let Some(contract) = call.args.iter().nth(1) else {
+        return;
+    };
+    if matches!(contract, syn::Expr::Reference(_)) {
+        return;
+    }
+    match instance(contract, instances) {
+        Some(name) => {
+            rec.edit(
+                &file.path,
+                Edit::replace(range_of(contract), format!("&{}", name)),
+            );
+            rec.applied(
+                &file.path,
+                line_of(&call.method),
+                format!("`execute_contract` takes `&{}`", name),
+            );
+        }
+        None => rec.skipped(
+            &file.path,
+            line_of(contract),
+            "`execute_contract` call",
+            "pass the `ContractInfo` `instantiate_contract` returned",
+        ),
+    }
+}
+
+/// Secret's `query_wasm_smart(code_hash, address, msg)` needs the hash first.
+fn port_query(
+    file: &SourceFile,
+    call: &syn::ExprMethodCall,
+    instances: &HashSet<String>,
+    rec: &mut Recorder,
+) {
+    if call.args.len() != 2 {
+        return;
+    }
+    let contract = &call.args[0];
+    match instance(contract, instances) {
+        Some(name) => {
+            rec.edit(
+                &file.path,
+                Edit::replace(
+                    range_of(contract),
+                    format!("&{0}.code_hash, &{0}.address", name),
+                ),
+            );
+            rec.applied(
+                &file.path,
+                line_of(&call.method),
+                format!("`query_wasm_smart` given `{}`'s code hash", name),
+            );
+        }
+        None => rec.skipped(
+            &file.path,
+            line_of(contract),
+            "`query_wasm_smart` call",
+            "Secret queries take the code hash first: `query_wasm_smart(code_hash, address, msg)`",
+        ),
+    }
+}
+
+/// The instance `expr` names, as `x`, `x.clone()` or `&x`.
+fn instance(expr: &syn::Expr, instances: &HashSet<String>) -> Option<String> {
+    match expr {
+        syn::Expr::Path(p) => p
+            .path
+            .get_ident()
+            .map(|i| i.to_string())
+            .filter(|name| instances.contains(name)),
+        syn::Expr::MethodCall(m) if m.method == "clone" && m.args.is_empty() => {
+            instance(&m.receiver, instances)
+        }
+        syn::Expr::Reference(r) => instance(&r.expr, instances),
+        _ => None,
+    }
+}
+
+/// Whether `callee` names a tuple struct of the crate wrapping `ContractInfo`.
+fn holds_contract_info(krate: &CrateSources, callee: &syn::Path) -> bool {
+    let Some(name) = callee.segments.last().map(|s| &s.ident) else {
+        return false;
+    };
+    krate.files.iter().any(|f| {
+        f.ast.items.iter().any(|item| match item {
+            syn::Item::Struct(s) if s.ident == *name => match &s.fields {
+                syn::Fields::Unnamed(fields) => fields.unnamed.first().is_some_and(|field| {
+                    matches!(&field.ty, syn::Type::Path(t)
+                        if t.path.segments.last().is_some_and(|s| s.ident == "ContractInfo"))
+                }),
+                _ => false,
+            },
+            _ => false,
+        })
+    })
+}
+
+/// Variables bound to what `instantiate_contract` returns, which hold a
+/// `ContractInfo` once ported.
+pub(crate) fn instances(file: &syn::File) -> HashSet<String> {
+    #[derive(Default)]
+    struct Instances(HashSet<String>);
+
+    impl<'ast> Visit<'ast> for Instances {
+        fn visit_local(&mut self, node: &'ast syn::Local) {
+            if let (syn::Pat::Ident(binding), Some(init)) = (&node.pat, &node.init) {
+                if instantiates(&init.expr) {
+                    self.0.insert(binding.ident.to_string());
+                }
+            }
+            visit::visit_local(self, node);
+        }
+    }
+
+    let mut instances = Instances::default();
+    instances.visit_file(file);
+    instances.0
+}
+
+/// Harness calls in a file.
+#[derive(Default)]
+struct HarnessCalls<'ast> {
+    instantiates: Vec<&'ast syn::ExprMethodCall>,
+    executes: Vec<&'ast syn::ExprMethodCall>,
+    queries: Vec<&'ast syn::ExprMethodCall>,
+    /// Calls like `CwTemplateContract(addr)` passing a variable along.
+    passed: Vec<(&'ast syn::Path, &'ast syn::Ident)>,
+}
+
+impl<'ast> Visit<'ast> for HarnessCalls<'ast> {
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        match node.method.to_string().as_str() {
+            "instantiate_contract" => self.instantiates.push(node),
+            "execute_contract" => self.executes.push(node),
+            "query_wasm_smart" => self.queries.push(node),
+            _ => {}
+        }
+        visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(callee) = &*node.func {
+            for arg in &node.args {
+                if let syn::Expr::Path(p) = arg {
+                    if let Some(ident) = p.path.get_ident() {
+                        self.passed.push((&callee.path, ident));
+                    }
+                }
+            }
+        }
+        visit::visit_expr_call(self, node);
+    }
+}
+
+/// Whether `expr` is `instantiate_contract(..)`, possibly unwrapped.
+fn instantiates(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::MethodCall(m) if m.method == "instantiate_contract" => true,
+        syn::Expr::MethodCall(m) if m.method == "unwrap" || m.method == "expect" => {
+            instantiates(&m.receiver)
+        }
+        syn::Expr::Try(t) => instantiates(&t.expr),
+        _ => false,
+    }
+}
+
+/// The harness dependency for the crate at `root`: by path when the crate sits
+/// in a checkout of this repository, as its contracts do, from git otherwise.
+fn harness(root: &Path) -> String {
+    let Ok(root) = root.canonicalize() else {
+        return HARNESS.to_string();
+    };
+    root.ancestors()
+        .position(|dir| dir.join(HARNESS_DIR).join("Cargo.toml").is_file())
+        .map(|depth| {
+            format!(
+                r#"secret-multi-test = {{ path = "{}{}" }}"#,
+                "../".repeat(depth),
+                HARNESS_DIR
+            )
+        })
+        .unwrap_or_else(|| HARNESS.to_string())
+}
+
+fn replace_dependency(harness: &str, manifest: &Manifest, rec: &mut Recorder) {
+    for dep in manifest
+        .dependencies()
+        .into_iter()
+        .filter(|d| d.name == "cw-multi-test")
+    {
+        let description = format!("`cw-multi-test` in [{}]", dep.section);
+        let Some(entry) = manifest.entry_range(&dep.section, &dep.name) else {
+            rec.skipped(
+                &manifest.path,
+                dep.line,
+                description,
+                format!("declared as a table; use `{}`", harness),
+            );
+            continue;
+        };
+        rec.edit(&manifest.path, Edit::replace(entry, harness));
+        rec.applied(
+            &manifest.path,
+            dep.line,
+            format!("{} replaced with `secret-multi-test`", description),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports_calls_to_the_secret_harness() {
+        let krate = CrateSources::from_files(
+            "",
+            [
+                ("src/helpers.rs", "pub struct Wrapper(pub Addr);\n"),
+                (
+                    "src/tests.rs",
+                    r#"use cw_multi_test::{App, ContractWrapper, Executor, next_block};
+
+fn run(mut app: App) {
+    let code = app.store_code(contract());
+    let addr = app
+        .instantiate_contract(code, owner(), &msg, &[], "test", None)
+        .unwrap();
+    app.execute_contract(owner(), addr.clone(), &msg, &[]).unwrap();
+    let res: Count = app.wrap().query_wasm_smart(&addr, &query).unwrap();
+    let wrapper = Wrapper(addr);
+    app.instantiate_contract(code, owner(), &msg, &[], "admin", Some(owner().to_string()));
+}
+"#,
+                ),
+            ],
+        )
+        .unwrap();
+        let manifest = Manifest::parse(
+            "[dependencies]\nserde = \"1\"\n\n[dev-dependencies]\ncw-multi-test = \"0.15.1\" # tests\n",
+        )
+        .unwrap();
+        let migration = port_multi_tests(&krate, &manifest);
+
+        assert_eq!(
+            r#"use secret_multi_test::{App, ContractWrapper, Executor, next_block};
+
+fn run(mut app: App) {
+    let code = app.store_code(contract());
+    let addr = app
+        .instantiate_contract(&code, owner(), &msg, &[], "test")
+        .unwrap();
+    app.execute_contract(owner(), &addr, &msg, &[]).unwrap();
+    let res: Count = app.wrap().query_wasm_smart(&addr.code_hash, &addr.address, &query).unwrap();
+    let wrapper = Wrapper(addr);
+    app.instantiate_contract(code, owner(), &msg, &[], "admin", Some(owner().to_string()));
+}
+"#,
+            migration.files[Path::new("src/tests.rs")]
+        );
+        assert_eq!(
+            format!(
+                "[dependencies]\nserde = \"1\"\n\n[dev-dependencies]\n{} # tests\n",
+                HARNESS
+            ),
+            migration.files[Path::new("Cargo.toml")]
+        );
+        let skipped: Vec<_> = migration
+            .skipped()
+            .map(|t| (t.description.as_str(), t.line))
+            .collect();
+        assert_eq!(
+            vec![
+                ("`cw_multi_test::next_block` import", 1),
+                ("`instantiate_contract` admin", 11),
+                ("`addr` passed to `Wrapper`", 10),
+            ],
+            skipped
+        );
+    }
+
+    #[test]
+    fn harness_is_a_path_inside_this_repository() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(
+            r#"secret-multi-test = { path = "../../packages/secret-multi-test" }"#,
+            harness(&manifest_dir.join("../../contracts/cw-counter"))
+        );
+        assert_eq!(HARNESS, harness(Path::new("/")));
+    }
+}
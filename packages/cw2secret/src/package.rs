@@ -0,0 +1,129 @@
+//! Step 2 of the migration pathway: the crate gets its Secret name, and paths
+//! naming the old crate, like `use cw_counter::msg::...` in `bin/schema.rs`,
+//! follow.
+
+use syn::visit::{self, Visit};
+
+use crate::manifest::Manifest;
+use crate::source::{line_of, range_of, CrateSources, Edit};
+use crate::transform::{Migration, Recorder};
+
+pub const RULE: &str = "package";
+
+/// `cw-counter` → `secret-counter`; names without a `cw-` prefix just gain
+/// `secret-`.
+pub fn secret_name(name: &str) -> String {
+    if name.starts_with("secret-") || name.starts_with("secret_") {
+        return name.to_string();
+    }
+    let base = name
+        .strip_prefix("cw-")
+        .or_else(|| name.strip_prefix("cw_"))
+        .unwrap_or(name);
+    format!("secret-{}", base)
+}
+
+/// Renames the package to `name`, or to [`secret_name`] of its current name.
+pub fn rename_package(krate: &CrateSources, manifest: &Manifest, name: Option<&str>) -> Migration {
+    let mut rec = Recorder::new(RULE);
+    let (Some(old), Some(range)) = (
+        manifest.package_name(),
+        manifest.value_range("package", "name"),
+    ) else {
+        rec.skipped(
+            &manifest.path,
+            1,
+            "package name",
+            "no `[package] name` found",
+        );
+        return rec.finish_with_manifest(krate, manifest);
+    };
+    let new = name.map_or_else(|| secret_name(old), str::to_string);
+    if new == old {
+        return rec.finish_with_manifest(krate, manifest);
+    }
+
+    rec.edit(&manifest.path, Edit::replace(range, format!("\"{}\"", new)));
+    rec.applied(
+        &manifest.path,
+        manifest.line_of_key("package", "name").unwrap_or(1),
+        format!("package renamed `{}` → `{}`", old, new),
+    );
+
+    let old_ident = old.replace('-', "_");
+    let new_ident = new.replace('-', "_");
+    for file in &krate.files {
+        let mut paths = CratePaths {
+            name: &old_ident,
+            idents: vec![],
+        };
+        paths.visit_file(&file.ast);
+        for ident in paths.idents {
+            rec.edit(
+                &file.path,
+                Edit::replace(range_of(ident), new_ident.clone()),
+            );
+            rec.applied(
+                &file.path,
+                line_of(ident),
+                format!("`{}` path renamed to `{}`", old_ident, new_ident),
+            );
+        }
+    }
+    rec.finish_with_manifest(krate, manifest)
+}
+
+/// Leading `name` segments of paths and `use` trees.
+struct CratePaths<'a, 'ast> {
+    name: &'a str,
+    idents: Vec<&'ast syn::Ident>,
+}
+
+impl<'a, 'ast> Visit<'ast> for CratePaths<'a, 'ast> {
+    fn visit_item_use(&mut self, node: &'ast syn::ItemUse) {
+        match &node.tree {
+            syn::UseTree::Path(p) if p.ident == self.name => self.idents.push(&p.ident),
+            syn::UseTree::Name(n) if n.ident == self.name => self.idents.push(&n.ident),
+            _ => {}
+        }
+    }
+
+    fn visit_path(&mut self, node: &'ast syn::Path) {
+        if let Some(first) = node.segments.first().filter(|s| s.ident == self.name) {
+            self.idents.push(&first.ident);
+        }
+        visit::visit_path(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_package_and_crate_paths() {
+        assert_eq!("secret-counter", secret_name("cw-counter"));
+        assert_eq!("secret-vault", secret_name("vault"));
+        assert_eq!("secret-counter", secret_name("secret-counter"));
+
+        let krate = CrateSources::from_files(
+            "",
+            [(
+                "src/bin/schema.rs",
+                "use cw_counter::msg::QueryMsg;\nfn main() { cw_counter::run::<QueryMsg>(); }\n",
+            )],
+        )
+        .unwrap();
+        let manifest = Manifest::parse("[package]\nname = \"cw-counter\" # the crate\n").unwrap();
+        let migration = rename_package(&krate, &manifest, Some("secret-counter-v2"));
+
+        assert_eq!(
+            "[package]\nname = \"secret-counter-v2\" # the crate\n",
+            migration.files[std::path::Path::new("Cargo.toml")]
+        );
+        assert_eq!(
+            "use secret_counter_v2::msg::QueryMsg;\nfn main() { secret_counter_v2::run::<QueryMsg>(); }\n",
+            migration.files[std::path::Path::new("src/bin/schema.rs")]
+        );
+    }
+}
@@ -0,0 +1,532 @@
+//! Ranks what a CosmWasm contract reveals that its Secret version shouldn't.
+//!
+//! On a public chain every query and event is readable by anyone; on Secret
+//! they're encrypted, but a query still answers whoever asks. The analysis
+//! walks `QueryMsg` and the handlers behind it for queries returning
+//! per-address data without a viewing key or permit, like cw-counter's
+//! `GetUserCount { addr }`, and flags event attributes carrying the sender's
+//! address.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+
+use syn::visit::{self, Visit};
+use syn::{Expr, Item, ItemFn, Pat, Type};
+
+use crate::auth::find_enum;
+use crate::source::{line_of, CrateSources, SourceFile};
+use crate::storage::{find_declarations, StorageKind};
+
+/// Address types; `String` fields count when they're named like an address.
+const ADDRESS_TYPES: &[&str] = &["Addr", "HumanAddr", "CanonicalAddr"];
+const ADDRESS_NAMES: &[&str] = &[
+    "addr",
+    "address",
+    "owner",
+    "account",
+    "user",
+    "sender",
+    "recipient",
+    "spender",
+];
+
+/// Fields that already authenticate a query.
+const CREDENTIALS: &[&str] = &["key", "viewing_key", "permit"];
+
+/// Storage reads that take a key.
+const LOOKUPS: &[&str] = &["load", "may_load", "get", "has", "query"];
+
+/// Storage methods that walk every entry.
+const LISTINGS: &[&str] = &["range", "range_raw", "keys", "keys_raw", "prefix", "iter"];
+
+/// How far into helper functions a query's address is followed.
+const MAX_DEPTH: usize = 3;
+
+const AUTH: &str = "put it behind a viewing key and a query permit: \
+                    `cw2secret auth --query {}` (pathway steps 5b and 5c)";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+        })
+    }
+}
+
+/// One thing the contract discloses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Risk {
+    pub severity: Severity,
+    pub file: PathBuf,
+    pub line: usize,
+    pub message: String,
+    /// The Secret mechanism that keeps it private.
+    pub mechanism: String,
+}
+
+impl fmt::Display for Risk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}:{}: [{}] {}",
+            self.file.display(),
+            self.line,
+            self.severity,
+            self.message
+        )?;
+        write!(f, "    = help: {}", self.mechanism)
+    }
+}
+
+/// Analyzes a contract crate, most severe risks first.
+pub fn analyze_privacy(krate: &CrateSources) -> Vec<Risk> {
+    let mut risks = vec![];
+    let fns = functions(krate);
+    let keyed_by_address: HashSet<String> = find_declarations(krate)
+        .into_iter()
+        .filter(|d| d.kind == StorageKind::Map)
+        .filter(|d| {
+            d.key.as_deref().is_some_and(|key| {
+                ADDRESS_TYPES.iter().any(|t| key.contains(t)) || key.contains("String")
+            })
+        })
+        .map(|d| d.name)
+        .collect();
+
+    if let Some((msg_file, query_msg)) = find_enum(krate, "QueryMsg") {
+        let mut arms = Arms::default();
+        for file in &krate.files {
+            arms.visit_file(&file.ast);
+        }
+        for variant in &query_msg.variants {
+            let fields: Vec<_> = variant.fields.iter().collect();
+            if fields.iter().any(|f| {
+                f.ident
+                    .as_ref()
+                    .is_some_and(|i| CREDENTIALS.contains(&i.to_string().as_str()))
+            }) {
+                continue;
+            }
+            let name = variant.ident.to_string();
+            let addresses: Vec<String> = fields
+                .iter()
+                .filter(|f| is_address(f.ident.as_ref(), &f.ty))
+                .filter_map(|f| f.ident.as_ref().map(|i| i.to_string()))
+                .collect();
+            let handlers = arms.arms.get(&name).map(Vec::as_slice).unwrap_or_default();
+            let mechanism = AUTH.replace("{}", &name);
+
+            if !addresses.is_empty() {
+                let stored = handlers.iter().any(|arm| {
+                    let tainted = bindings(&arm.pat, &addresses);
+                    let mut flow = Flow::new(&fns, tainted, &keyed_by_address, 0);
+                    flow.visit_expr(&arm.body);
+                    flow.lookup
+                });
+                let fields = quoted(&addresses);
+                let (severity, message) = if stored {
+                    (
+                        Severity::High,
+                        format!(
+                            "`QueryMsg::{}` returns data stored under {} to anyone",
+                            name, fields
+                        ),
+                    )
+                } else {
+                    (
+                        Severity::Medium,
+                        format!(
+                            "`QueryMsg::{}` takes {} without authenticating the caller",
+                            name, fields
+                        ),
+                    )
+                };
+                risks.push(Risk {
+                    severity,
+                    file: msg_file.path.clone(),
+                    line: line_of(&variant.ident),
+                    message,
+                    mechanism,
+                });
+            } else if handlers.iter().any(|arm| {
+                let mut flow = Flow::new(&fns, HashSet::new(), &keyed_by_address, 0);
+                flow.visit_expr(&arm.body);
+                flow.listing
+            }) {
+                risks.push(Risk {
+                    severity: Severity::High,
+                    file: msg_file.path.clone(),
+                    line: line_of(&variant.ident),
+                    message: format!("`QueryMsg::{}` lists every address's data", name),
+                    mechanism: format!(
+                        "return only the caller's own entries and {}",
+                        mechanism.replacen("put it", "put them", 1)
+                    ),
+                });
+            }
+        }
+    }
+
+    for file in &krate.files {
+        let mut events = Events {
+            file,
+            risks: vec![],
+        };
+        events.visit_file(&file.ast);
+        risks.extend(events.risks);
+    }
+
+    risks.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| (&a.file, a.line).cmp(&(&b.file, b.line)))
+    });
+    risks
+}
+
+fn is_address(name: Option<&syn::Ident>, ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    let Some(last) = path.path.segments.last() else {
+        return false;
+    };
+    ADDRESS_TYPES.iter().any(|t| last.ident == t)
+        || (last.ident == "String"
+            && name.is_some_and(|n| ADDRESS_NAMES.iter().any(|a| n.to_string().contains(a))))
+}
+
+fn quoted(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|n| format!("`{}`", n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn is_test(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg")
+            && quote::ToTokens::to_token_stream(&attr.meta)
+                .to_string()
+                .contains("test")
+    })
+}
+
+/// Every non-test function in the crate by name, module functions included.
+fn functions(krate: &CrateSources) -> HashMap<String, &ItemFn> {
+    fn collect<'a>(items: &'a [Item], fns: &mut HashMap<String, &'a ItemFn>) {
+        for item in items {
+            match item {
+                Item::Fn(f) if !is_test(&f.attrs) => {
+                    fns.entry(f.sig.ident.to_string()).or_insert(f);
+                }
+                Item::Mod(m) if !is_test(&m.attrs) => {
+                    if let Some((_, items)) = &m.content {
+                        collect(items, fns);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut fns = HashMap::new();
+    for file in &krate.files {
+        collect(&file.ast.items, &mut fns);
+    }
+    fns
+}
+
+/// The names a `QueryMsg::Variant { addr, .. }` pattern binds `fields` to.
+fn bindings(pat: &Pat, fields: &[String]) -> HashSet<String> {
+    let Pat::Struct(s) = pat else {
+        return HashSet::new();
+    };
+    s.fields
+        .iter()
+        .filter_map(|f| {
+            let syn::Member::Named(field) = &f.member else {
+                return None;
+            };
+            if !fields.contains(&field.to_string()) {
+                return None;
+            }
+            match &*f.pat {
+                Pat::Ident(i) => Some(i.ident.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Match arms handling each `QueryMsg` variant, outside tests.
+#[derive(Default)]
+struct Arms<'ast> {
+    arms: HashMap<String, Vec<&'ast syn::Arm>>,
+}
+
+impl<'ast> Visit<'ast> for Arms<'ast> {
+    fn visit_item_mod(&mut self, node: &'ast syn::ItemMod) {
+        if !is_test(&node.attrs) {
+            visit::visit_item_mod(self, node);
+        }
+    }
+
+    fn visit_arm(&mut self, node: &'ast syn::Arm) {
+        let path = match &node.pat {
+            Pat::Struct(s) => Some(&s.path),
+            Pat::TupleStruct(t) => Some(&t.path),
+            Pat::Path(p) => Some(&p.path),
+            _ => None,
+        };
+        if let Some(path) = path {
+            let segments = &path.segments;
+            if segments.len() >= 2 && segments[segments.len() - 2].ident == "QueryMsg" {
+                let variant = segments.last().unwrap().ident.to_string();
+                self.arms.entry(variant).or_default().push(node);
+            }
+        }
+        visit::visit_arm(self, node);
+    }
+}
+
+/// Follows a query's address through locals and helper calls to see whether
+/// it ends up as a storage key, and whether the handler lists a map keyed
+/// by address.
+struct Flow<'a, 'ast> {
+    fns: &'a HashMap<String, &'ast ItemFn>,
+    tainted: HashSet<String>,
+    keyed_by_address: &'a HashSet<String>,
+    depth: usize,
+    lookup: bool,
+    listing: bool,
+}
+
+impl<'a, 'ast> Flow<'a, 'ast> {
+    fn new(
+        fns: &'a HashMap<String, &'ast ItemFn>,
+        tainted: HashSet<String>,
+        keyed_by_address: &'a HashSet<String>,
+        depth: usize,
+    ) -> Self {
+        Flow {
+            fns,
+            tainted,
+            keyed_by_address,
+            depth,
+            lookup: false,
+            listing: false,
+        }
+    }
+
+    fn mentions(&self, expr: &Expr, names: &HashSet<String>) -> bool {
+        struct Idents<'n>(&'n HashSet<String>, bool);
+        impl<'n, 'ast> Visit<'ast> for Idents<'n> {
+            fn visit_ident(&mut self, ident: &'ast syn::Ident) {
+                self.1 |= self.0.contains(&ident.to_string());
+            }
+        }
+        let mut idents = Idents(names, false);
+        idents.visit_expr(expr);
+        idents.1
+    }
+
+    /// Follows a call into `callee`, tainting the parameters given a tainted argument.
+    fn follow(&mut self, callee: &str, args: &[&Expr]) {
+        if self.depth >= MAX_DEPTH {
+            return;
+        }
+        let Some(func) = self.fns.get(callee) else {
+            return;
+        };
+        let params = func.sig.inputs.iter().filter_map(|arg| match arg {
+            syn::FnArg::Typed(p) => match &*p.pat {
+                Pat::Ident(i) => Some(i.ident.to_string()),
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        });
+        let tainted = params
+            .zip(args)
+            .filter(|(_, arg)| self.mentions(arg, &self.tainted))
+            .map(|(param, _)| param)
+            .collect();
+        let mut inner = Flow::new(self.fns, tainted, self.keyed_by_address, self.depth + 1);
+        inner.visit_block(&func.block);
+        self.lookup |= inner.lookup;
+        self.listing |= inner.listing;
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for Flow<'a, 'ast> {
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        visit::visit_local(self, node);
+        let tainted = node
+            .init
+            .as_ref()
+            .is_some_and(|init| self.mentions(&init.expr, &self.tainted));
+        if let (true, Pat::Ident(i)) = (tainted, &node.pat) {
+            self.tainted.insert(i.ident.to_string());
+        }
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        let method = node.method.to_string();
+        if LOOKUPS.contains(&method.as_str())
+            && node.args.iter().any(|a| self.mentions(a, &self.tainted))
+        {
+            self.lookup = true;
+        }
+        if LISTINGS.contains(&method.as_str())
+            && self.mentions(&node.receiver, self.keyed_by_address)
+        {
+            self.listing = true;
+        }
+        visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let Expr::Path(p) = &*node.func {
+            if let Some(last) = p.path.segments.last() {
+                let args: Vec<_> = node.args.iter().collect();
+                self.follow(&last.ident.to_string(), &args);
+            }
+        }
+        visit::visit_expr_call(self, node);
+    }
+}
+
+/// `add_attribute`s whose value is the sender's address.
+struct Events<'a> {
+    file: &'a SourceFile,
+    risks: Vec<Risk>,
+}
+
+impl<'a, 'ast> Visit<'ast> for Events<'a> {
+    fn visit_item_mod(&mut self, node: &'ast syn::ItemMod) {
+        if !is_test(&node.attrs) {
+            visit::visit_item_mod(self, node);
+        }
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        let sender = |expr: &Expr| match expr {
+            Expr::Field(f) => matches!(&f.member, syn::Member::Named(i) if i == "sender"),
+            Expr::Path(p) => p.path.is_ident("sender"),
+            Expr::MethodCall(m) => {
+                matches!(&*m.receiver, Expr::Field(f) if matches!(&f.member, syn::Member::Named(i) if i == "sender"))
+                    || matches!(&*m.receiver, Expr::Path(p) if p.path.is_ident("sender"))
+            }
+            _ => false,
+        };
+        if node.method == "add_attribute" && node.args.len() == 2 && sender(&node.args[1]) {
+            self.risks.push(Risk {
+                severity: Severity::Low,
+                file: self.file.path.clone(),
+                line: line_of(&node.method),
+                message: format!(
+                    "event attribute {} carries `{}`",
+                    self.file.text_of(&node.args[0]),
+                    self.file.text_of(&node.args[1])
+                ),
+                mechanism: "Secret encrypts attributes for the transaction's sender; keep it \
+                            out of `add_attribute_plaintext`, or drop it if no client reads it"
+                    .to_string(),
+            });
+        }
+        visit::visit_expr_method_call(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn risks(krate: &CrateSources) -> Vec<(Severity, String, usize)> {
+        analyze_privacy(krate)
+            .into_iter()
+            .map(|r| (r.severity, r.file.display().to_string(), r.line))
+            .collect()
+    }
+
+    #[test]
+    fn secret_counter_closes_the_user_count_leak() {
+        let contracts = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../contracts");
+        let cw = CrateSources::load(contracts.join("cw-counter")).unwrap();
+        let secret = CrateSources::load(contracts.join("secret-counter")).unwrap();
+
+        let found = analyze_privacy(&cw);
+        assert_eq!(
+            "src/msg.rs:21: [high] `QueryMsg::GetUserCount` returns data stored under `addr` to anyone",
+            found[0].to_string().lines().next().unwrap()
+        );
+        assert_eq!(
+            vec![
+                (Severity::High, "src/msg.rs".to_string(), 21),
+                (Severity::Low, "src/contract.rs".to_string(), 30),
+            ],
+            risks(&cw)
+        );
+        assert_eq!(
+            vec![(Severity::Low, "src/contract.rs".to_string(), 35)],
+            risks(&secret)
+        );
+    }
+
+    #[test]
+    fn flags_listings_and_untraced_addresses() {
+        let krate = CrateSources::from_files(
+            "",
+            [
+                (
+                    "src/msg.rs",
+                    r#"pub enum QueryMsg {
+    Balance { owner_address: String },
+    Users {},
+    Config {},
+}
+"#,
+                ),
+                (
+                    "src/contract.rs",
+                    r#"use cw_storage_plus::Map;
+
+pub const BALANCES: Map<&Addr, Uint128> = Map::new("balances");
+
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Balance { owner_address } => to_binary(&deps.api.addr_validate(&owner_address)?),
+        QueryMsg::Users {} => to_binary(&users(deps)?),
+        QueryMsg::Config {} => to_binary(&CONFIG.load(deps.storage)?),
+    }
+}
+
+fn users(deps: Deps) -> StdResult<Vec<Addr>> {
+    BALANCES.keys(deps.storage, None, None, Order::Ascending).collect()
+}
+"#,
+                ),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                (Severity::High, "src/msg.rs".to_string(), 3),
+                (Severity::Medium, "src/msg.rs".to_string(), 2),
+            ],
+            risks(&krate)
+        );
+    }
+}
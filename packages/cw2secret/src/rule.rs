@@ -0,0 +1,32 @@
+//! Rewrite rules defined outside this crate.
+//!
+//! A downstream crate implements [`Rule`] for conventions only its contracts
+//! follow and registers it with [`MigrateOptions::rule`]; `migrate_crate` runs
+//! it after the built-in rules, on their output, and reports its
+//! transformations alongside theirs. Rules build their result with a
+//! [`Recorder`], as the built-in ones do.
+//!
+//! [`MigrateOptions::rule`]: crate::migrate::MigrateOptions::rule
+//! [`Recorder`]: crate::transform::Recorder
+
+use std::fmt;
+
+use crate::config::Config;
+use crate::manifest::Manifest;
+use crate::source::CrateSources;
+use crate::transform::Migration;
+
+pub trait Rule: Send + Sync {
+    /// Name reported with each transformation, e.g. `storage`.
+    fn name(&self) -> &'static str;
+
+    /// Rewrites the crate as the rules before left it. Changes to files the
+    /// configuration skips are discarded.
+    fn apply(&self, krate: &CrateSources, manifest: &Manifest, config: &Config) -> Migration;
+}
+
+impl fmt::Debug for dyn Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
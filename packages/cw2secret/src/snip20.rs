@@ -0,0 +1,1227 @@
+//! Translates `cw20` integrations into SNIP-20, the token standard on Secret.
+//!
+//! A `Cw20ExecuteMsg` sent to a token with `WasmMsg::Execute` or
+//! `wasm_execute` becomes the `secret_toolkit::snip20` message building it,
+//! like `transfer_msg`, which takes the token's code hash: from a parameter
+//! added next to the one holding the token's address, or from a field added
+//! next to the field holding it. `ExecuteMsg::Receive(Cw20ReceiveMsg)` gets a
+//! `Snip20ReceiveMsg` defined next to it, and `instantiate` registers the
+//! contract with the token it's given, as a SNIP-20 token only calls
+//! contracts that did. Everything else from `cw20` is reported.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use syn::visit::{self, Visit};
+
+use crate::code_hash::{
+    add_param, has_param, insert_field, param_of, report_callers, typed_param, CONTRACT_PARAMS,
+};
+use crate::imports::{
+    find_use, import, indentation, local_names, rewrite_use, use_leaves, UseFinder,
+};
+use crate::source::{line_of, range_of, CrateSources, Edit, SourceFile};
+use crate::transform::{Migration, Recorder};
+
+pub const RULE: &str = "snip20";
+
+/// `Cw20ExecuteMsg` variants with a `secret_toolkit::snip20` counterpart, and
+/// its arguments up to the padding: a field of the variant, `Some(field)` for
+/// an optional one, or `None` for a memo, which `cw20` doesn't have.
+const MESSAGES: &[(&str, &str, &[&str])] = &[
+    ("Transfer", "transfer_msg", &["recipient", "amount", "None"]),
+    (
+        "Send",
+        "send_msg",
+        &["contract", "amount", "Some(msg)", "None"],
+    ),
+    (
+        "TransferFrom",
+        "transfer_from_msg",
+        &["owner", "recipient", "amount", "None"],
+    ),
+    (
+        "SendFrom",
+        "send_from_msg",
+        &["owner", "contract", "amount", "Some(msg)", "None"],
+    ),
+    ("Burn", "burn_msg", &["amount", "None"]),
+    ("BurnFrom", "burn_from_msg", &["owner", "amount", "None"]),
+    ("Mint", "mint_msg", &["recipient", "amount", "None"]),
+    (
+        "IncreaseAllowance",
+        "increase_allowance_msg",
+        &["spender", "amount", "expires"],
+    ),
+    (
+        "DecreaseAllowance",
+        "decrease_allowance_msg",
+        &["spender", "amount", "expires"],
+    ),
+];
+
+/// Names that may be left unused once the messages they built are replaced.
+const STD_NAMES: &[&str] = &["CosmosMsg", "WasmMsg", "to_binary", "to_json_binary"];
+
+const BLOCK_SIZE: &str = "\
+/// SNIP-20 messages are padded to a multiple of this many bytes, so their
+/// length doesn't give away which one was sent.
+const BLOCK_SIZE: usize = 256;
+
+";
+
+pub fn translate_cw20(krate: &CrateSources) -> Migration {
+    let mut rec = Recorder::new(RULE);
+    let receiver = find_receiver(krate);
+    // (struct, field) pairs holding a token's address, which get its code hash
+    let mut holders = BTreeSet::new();
+    let mut threaded = BTreeSet::new();
+    let mut functions: BTreeMap<PathBuf, BTreeSet<&str>> = BTreeMap::new();
+
+    if let Some(receiver) = &receiver {
+        define_receive_msg(receiver, &mut rec);
+        register_receive(krate, receiver, &mut holders, &mut functions, &mut rec);
+    }
+
+    for file in &krate.files {
+        let mut sites = Sites::default();
+        sites.visit_file(&file.ast);
+        let mut replaced = vec![];
+        for send in &sites.sends {
+            if let Some(function) =
+                translate_send(krate, file, send, &mut holders, &mut threaded, &mut rec)
+            {
+                functions
+                    .entry(file.path.clone())
+                    .or_default()
+                    .insert(function);
+                replaced.push(range_of(send.node));
+            }
+        }
+        for message in &sites.others {
+            rec.skipped(
+                &file.path,
+                line_of(*message),
+                format!("`{}`", path_text(&message.path)),
+                "only messages sent with `WasmMsg::Execute` or `wasm_execute` are translated; \
+                 build it with `secret_toolkit::snip20`",
+            );
+        }
+        if receiver.is_some() {
+            translate_receives(file, &sites, &mut rec);
+        }
+        rewrite_imports(
+            file,
+            &sites,
+            &replaced,
+            functions.get(&file.path),
+            receiver.as_ref(),
+            &mut rec,
+        );
+    }
+
+    add_hash_fields(krate, &holders, &mut rec);
+    for (function, method) in &threaded {
+        report_callers(krate, function, *method, &mut rec);
+    }
+    rec.finish(krate)
+}
+
+/// The `Receive(Cw20ReceiveMsg)` variant of `ExecuteMsg`.
+struct Receiver<'a> {
+    file: &'a SourceFile,
+    execute: &'a syn::ItemEnum,
+    variant: &'a syn::Variant,
+}
+
+fn find_receiver(krate: &CrateSources) -> Option<Receiver<'_>> {
+    krate.files.iter().find_map(|file| {
+        file.ast.items.iter().find_map(|item| {
+            let syn::Item::Enum(execute) = item else {
+                return None;
+            };
+            if execute.ident != "ExecuteMsg" {
+                return None;
+            }
+            let variant = execute.variants.iter().find(|v| match &v.fields {
+                syn::Fields::Unnamed(fields) => {
+                    fields.unnamed.len() == 1 && type_named(&fields.unnamed[0].ty, "Cw20ReceiveMsg")
+                }
+                _ => false,
+            })?;
+            Some(Receiver {
+                file,
+                execute,
+                variant,
+            })
+        })
+    })
+}
+
+/// Defines `Snip20ReceiveMsg` after `ExecuteMsg`, with the same derives.
+fn define_receive_msg(receiver: &Receiver, rec: &mut Recorder) {
+    let file = receiver.file;
+    if local_names(&file.ast).contains("Snip20ReceiveMsg") {
+        return;
+    }
+    let attrs: Vec<_> = receiver
+        .execute
+        .attrs
+        .iter()
+        .filter(|a| !a.path().is_ident("doc"))
+        .map(|a| format!("{}\n", file.text_of(a)))
+        .collect();
+    let definition = format!(
+        "\n\n/// What a SNIP-20 token sends with `Send` and `SendFrom`: `sender` sent the \
+         tokens,\n/// from the account of `from`.\n{}pub struct Snip20ReceiveMsg {{\n    \
+         pub sender: String,\n    pub from: String,\n    pub amount: Uint128,\n    \
+         pub memo: Option<String>,\n    pub msg: Option<Binary>,\n}}",
+        attrs.concat()
+    );
+    rec.edit(
+        &file.path,
+        Edit::insert(range_of(receiver.execute).end, definition),
+    );
+    rec.applied(
+        &file.path,
+        line_of(receiver.variant),
+        "`Snip20ReceiveMsg` defined for `ExecuteMsg::Receive`",
+    );
+}
+
+/// Has `instantiate` register the contract with the token its message names.
+fn register_receive(
+    krate: &CrateSources,
+    receiver: &Receiver,
+    holders: &mut BTreeSet<(String, String)>,
+    functions: &mut BTreeMap<PathBuf, BTreeSet<&str>>,
+    rec: &mut Recorder,
+) {
+    let skip = |rec: &mut Recorder| {
+        rec.skipped(
+            &receiver.file.path,
+            line_of(receiver.variant),
+            "`Receive` isn't registered with a token",
+            "a SNIP-20 token only sends `Receive` to contracts registered with it; add \
+             `register_receive_msg(env.contract.code_hash, None, BLOCK_SIZE, token_code_hash, \
+             token_addr)?` to a response for every token you accept",
+        );
+    };
+    let token = struct_fields(krate, "InstantiateMsg").and_then(|fields| {
+        let tokens: Vec<_> = fields
+            .into_iter()
+            .filter(|f| {
+                let name = f.ident.as_ref().unwrap().to_string();
+                (name.contains("token") || name.contains("cw20"))
+                    && (type_named(&f.ty, "String") || type_named(&f.ty, "Addr"))
+            })
+            .collect();
+        match tokens[..] {
+            [token] => Some(token),
+            _ => None,
+        }
+    });
+    let instantiate = krate.files.iter().find_map(|file| {
+        file.ast.items.iter().find_map(|item| match item {
+            syn::Item::Fn(f) if f.sig.ident == "instantiate" => {
+                let msg = typed_param_of(&f.sig, "InstantiateMsg")?;
+                Some((file, f, msg))
+            }
+            _ => None,
+        })
+    });
+    let (Some(token), Some((file, function, msg))) = (token, instantiate) else {
+        skip(rec);
+        return;
+    };
+    let (Some(env), Some(first), Some(response)) = (
+        env_param(&function.sig),
+        function.block.stmts.first(),
+        response_new(&function.block),
+    ) else {
+        skip(rec);
+        return;
+    };
+
+    let field = token.ident.as_ref().unwrap().to_string();
+    let hash = hash_name(&field);
+    holders.insert(("InstantiateMsg".to_string(), field.clone()));
+    let address = if type_named(&token.ty, "Addr") {
+        "to_string"
+    } else {
+        "clone"
+    };
+    let env_name = match env.to_string().as_str() {
+        "_env" => {
+            rec.edit(&file.path, Edit::replace(range_of(env), "env"));
+            "env".to_string()
+        }
+        name => name.to_string(),
+    };
+    let indent = indentation(&file.text, range_of(first).start);
+    let register = [
+        format!("{}.contract.code_hash.clone()", env_name),
+        "None".to_string(),
+        "BLOCK_SIZE".to_string(),
+        format!("{}.{}.clone()", msg, hash),
+        format!("{}.{}.{}()", msg, field, address),
+    ];
+    rec.edit(
+        &file.path,
+        Edit::insert(
+            range_of(first).start,
+            format!(
+                "let register = {}?;\n{}",
+                call("register_receive_msg", &register, indent, true),
+                indent
+            ),
+        ),
+    );
+    rec.edit(
+        &file.path,
+        Edit::insert(range_of(response).end, ".add_message(register)"),
+    );
+    rec.applied(
+        &file.path,
+        line_of(&function.sig.ident),
+        format!(
+            "`instantiate` registers for `Receive` with the token in `{}`",
+            field
+        ),
+    );
+    functions
+        .entry(file.path.clone())
+        .or_default()
+        .insert("register_receive_msg");
+}
+
+/// A `Cw20ExecuteMsg` sent to a token.
+struct Send<'ast> {
+    /// The expression building the `CosmosMsg` or `WasmMsg`.
+    node: &'ast syn::Expr,
+    contract: &'ast syn::Expr,
+    funds: &'ast syn::Expr,
+    message: &'ast syn::ExprStruct,
+    /// Whether `node` is a `WasmMsg` rather than a `StdResult` of one.
+    infallible: bool,
+    sig: Option<&'ast syn::Signature>,
+}
+
+fn translate_send(
+    krate: &CrateSources,
+    file: &SourceFile,
+    send: &Send,
+    holders: &mut BTreeSet<(String, String)>,
+    threaded: &mut BTreeSet<(String, bool)>,
+    rec: &mut Recorder,
+) -> Option<&'static str> {
+    let line = line_of(send.node);
+    let description = format!("`{}`", path_text(&send.message.path));
+    let variant = send.message.path.segments.last().unwrap().ident.to_string();
+    let Some((_, function, args)) = MESSAGES.iter().find(|(v, ..)| *v == variant) else {
+        rec.skipped(
+            &file.path,
+            line,
+            description,
+            "SNIP-20 has no equivalent message",
+        );
+        return None;
+    };
+    if !no_funds(send.funds) {
+        rec.skipped(
+            &file.path,
+            line,
+            description,
+            "SNIP-20 messages can't carry native funds; send them with a `BankMsg`",
+        );
+        return None;
+    }
+
+    let field = |name: &str| {
+        send.message.fields.iter().find_map(|f| match &f.member {
+            syn::Member::Named(i) if i == name => Some(&f.expr),
+            _ => None,
+        })
+    };
+    let mut arguments = vec![];
+    for arg in args.iter() {
+        let argument = match *arg {
+            "None" => Some("None".to_string()),
+            "expires" => match field("expires") {
+                Some(syn::Expr::Path(p)) if p.path.is_ident("None") => Some("None".to_string()),
+                _ => {
+                    rec.skipped(
+                        &file.path,
+                        line,
+                        description,
+                        "SNIP-20 allowances expire at a time in seconds, an `Option<u64>`; \
+                         convert the `Expiration` by hand",
+                    );
+                    return None;
+                }
+            },
+            optional if optional.starts_with("Some(") => {
+                let name = &optional["Some(".len()..optional.len() - 1];
+                field(name).map(|e| format!("Some({})", file.text_of(e)))
+            }
+            name => field(name).map(|e| file.text_of(e).to_string()),
+        };
+        let Some(argument) = argument else {
+            rec.skipped(
+                &file.path,
+                line,
+                description,
+                format!(
+                    "`{}` isn't set field by field; translate it by hand",
+                    variant
+                ),
+            );
+            return None;
+        };
+        arguments.push(argument);
+    }
+
+    let hash = code_hash(krate, file, send, holders, threaded, rec)?;
+    let contract = file.text_of(send.contract);
+    let contract = if send.infallible || contract.ends_with(".to_string()") {
+        contract.to_string()
+    } else {
+        format!("{}.into()", contract)
+    };
+    arguments.extend(["None".to_string(), "BLOCK_SIZE".to_string(), hash, contract]);
+
+    let start = range_of(send.node).start;
+    let indent = indentation(&file.text, start);
+    let prefix = &file.text[file.text[..start].rfind('\n').map_or(0, |i| i + 1)..start];
+    let single = call(function, &arguments, indent, false);
+    let wrap = file.text_of(send.node).contains('\n') || prefix.len() + single.len() > 90;
+    let replacement = format!(
+        "{}{}",
+        call(function, &arguments, indent, wrap),
+        if send.infallible { "?" } else { "" }
+    );
+    rec.edit(&file.path, Edit::replace(range_of(send.node), replacement));
+    rec.applied(
+        &file.path,
+        line,
+        format!("{} → `snip20::{}`", description, function),
+    );
+    Some(function)
+}
+
+/// The code hash of the token a message is sent to, from a parameter or a
+/// field next to its address.
+fn code_hash(
+    krate: &CrateSources,
+    file: &SourceFile,
+    send: &Send,
+    holders: &mut BTreeSet<(String, String)>,
+    threaded: &mut BTreeSet<(String, bool)>,
+    rec: &mut Recorder,
+) -> Option<String> {
+    if let Some((sig, param)) = send
+        .sig
+        .and_then(|sig| Some((sig, param_of(sig, send.contract)?)))
+    {
+        let name = hash_name(&param.to_string());
+        if !has_param(sig, &name) {
+            rec.edit(&file.path, add_param(&file.text, sig, param, &name));
+            rec.applied(
+                &file.path,
+                line_of(&sig.ident),
+                format!("`{}` takes a `{}` for `{}`", sig.ident, name, param),
+            );
+            threaded.insert((sig.ident.to_string(), sig.receiver().is_some()));
+        }
+        return Some(name);
+    }
+
+    if let syn::Expr::Field(field) = without_conversions(send.contract) {
+        if let syn::Member::Named(name) = &field.member {
+            let base = file.text_of(&field.base);
+            if let Some(holder) = holder_of(krate, &name.to_string(), base) {
+                holders.insert((holder, name.to_string()));
+                return Some(format!("{}.{}.clone()", base, hash_name(&name.to_string())));
+            }
+        }
+    }
+
+    rec.skipped(
+        &file.path,
+        line_of(send.node),
+        format!("`{}`", path_text(&send.message.path)),
+        format!(
+            "can't tell which token `{}` is; build the message with `secret_toolkit::snip20` \
+             and its code hash by hand",
+            file.text_of(send.contract)
+        ),
+    );
+    None
+}
+
+/// `Snip20ReceiveMsg` for `Cw20ReceiveMsg`, whose `msg` is now optional.
+fn translate_receives(file: &SourceFile, sites: &Sites, rec: &mut Recorder) {
+    for path in &sites.receive_paths {
+        rec.edit(
+            &file.path,
+            Edit::replace(range_of(*path), "Snip20ReceiveMsg"),
+        );
+    }
+    for field in &sites.receive_fields {
+        rec.edit(
+            &file.path,
+            Edit::insert(range_of(*field).end, ".unwrap_or_default()"),
+        );
+        rec.warned(
+            &file.path,
+            line_of(*field),
+            format!("`{}` → `{0}.unwrap_or_default()`", file.text_of(*field)),
+            "a SNIP-20 `Send` may come without a `msg`, which now reads as empty",
+        );
+    }
+    for construction in &sites.receive_structs {
+        build_receive_msg(file, construction, rec);
+    }
+    for pattern in &sites.receive_patterns {
+        rec.skipped(
+            &file.path,
+            line_of(*pattern),
+            "`Cw20ReceiveMsg` destructured",
+            "`Snip20ReceiveMsg` adds `from` and `memo`, and its `msg` is an `Option<Binary>`",
+        );
+    }
+}
+
+/// Fills in what a `Snip20ReceiveMsg` has on top of a `Cw20ReceiveMsg`: `from`
+/// is the `sender`, and there's no `memo`.
+fn build_receive_msg(file: &SourceFile, node: &syn::ExprStruct, rec: &mut Recorder) {
+    let line = line_of(node);
+    let field = |name: &str| {
+        node.fields
+            .iter()
+            .find(|f| matches!(&f.member, syn::Member::Named(i) if i == name))
+    };
+    let (Some(sender), Some(amount), Some(msg), None) =
+        (field("sender"), field("amount"), field("msg"), &node.rest)
+    else {
+        rec.skipped(
+            &file.path,
+            line,
+            "`Cw20ReceiveMsg` constructed",
+            "set `from` and `memo`, and wrap `msg` in `Some`",
+        );
+        return;
+    };
+
+    let value = file.text_of(&sender.expr);
+    let from = match &sender.expr {
+        syn::Expr::Path(_) => format!("from: {}.clone(),", value),
+        _ => format!("from: {},", value),
+    };
+    let start = range_of(sender).start;
+    let separator = if file.text[range_of(node).start..start].contains('\n') {
+        format!("\n{}", indentation(&file.text, start))
+    } else {
+        " ".to_string()
+    };
+    rec.edit(
+        &file.path,
+        Edit::insert(start, format!("{}{}", from, separator)),
+    );
+    rec.edit(
+        &file.path,
+        insert_field(&file.text, node, &node.fields, amount, "memo: None"),
+    );
+    let msg_value = file.text_of(&msg.expr);
+    rec.edit(
+        &file.path,
+        Edit::replace(range_of(msg), format!("msg: Some({})", msg_value)),
+    );
+    rec.applied(
+        &file.path,
+        line,
+        "`Snip20ReceiveMsg` built with `from` and `memo`",
+    );
+}
+
+/// Drops the `cw20` imports nothing needs any more and imports the
+/// replacements, reusing the `cw20` import's place for them.
+fn rewrite_imports(
+    file: &SourceFile,
+    sites: &Sites,
+    replaced: &[Range<usize>],
+    functions: Option<&BTreeSet<&str>>,
+    receiver: Option<&Receiver>,
+    rec: &mut Recorder,
+) {
+    let items = &file.ast.items;
+    let used = |name: &str| {
+        sites.segments.iter().any(|(n, range)| {
+            n == name
+                && !replaced
+                    .iter()
+                    .any(|r| r.start <= range.start && range.end <= r.end)
+        })
+    };
+
+    let defines_receive_msg = receiver.is_some_and(|r| r.file.path == file.path);
+    let std_add: &[&str] = if defines_receive_msg {
+        &["Binary", "Uint128"]
+    } else {
+        &[]
+    };
+    let std_remove: Vec<&str> = STD_NAMES
+        .iter()
+        .copied()
+        .filter(|n| !replaced.is_empty() && !used(n))
+        .collect();
+    match find_use(items, "cosmwasm_std") {
+        Some(std) => {
+            if let Some(edit) = rewrite_use(&file.text, std, &std_remove, std_add) {
+                rec.edit(&file.path, edit);
+            }
+        }
+        None => {
+            if let Some(edit) = import(items, "cosmwasm_std", std_add) {
+                rec.edit(&file.path, edit);
+            }
+        }
+    }
+
+    let mut imports: Vec<(String, Vec<&str>)> = vec![];
+    if let Some(functions) = functions {
+        imports.push((
+            "secret_toolkit::snip20".to_string(),
+            functions.iter().copied().collect(),
+        ));
+    }
+    if let Some(receiver) = receiver.filter(|_| !defines_receive_msg) {
+        if !sites.receive_paths.is_empty() {
+            imports.push((receiver.file.module_path(), vec!["Snip20ReceiveMsg"]));
+        }
+    }
+    // new `use` declarations take the place of a `cw20` one left empty
+    let mut new_uses = vec![];
+    for (module, names) in &imports {
+        match find_use(items, module) {
+            Some(_) => {
+                if let Some(edit) = import(items, module, names) {
+                    rec.edit(&file.path, edit);
+                }
+            }
+            None => new_uses.push((module.as_str(), names.as_slice())),
+        }
+    }
+
+    let mut dropped = vec![];
+    if receiver.is_some() {
+        dropped.push("Cw20ReceiveMsg");
+    }
+    if !used("Cw20ExecuteMsg") {
+        dropped.push("Cw20ExecuteMsg");
+    }
+    let mut uses = UseFinder::default();
+    uses.visit_file(&file.ast);
+    for item in uses.uses {
+        if !matches!(&item.tree, syn::UseTree::Path(p) if p.ident == "cw20") {
+            continue;
+        }
+        let leaves = use_leaves(&item.tree);
+        for leaf in &leaves {
+            let name = leaf.name.as_deref().unwrap_or("*");
+            if !leaf.local().is_some_and(|l| dropped.contains(&l)) {
+                let reason = if name == "Cw20ExecuteMsg" {
+                    "still used by messages that weren't translated"
+                } else {
+                    "SNIP-20 has no direct equivalent; port it by hand"
+                };
+                rec.skipped(
+                    &file.path,
+                    line_of(item),
+                    format!("`cw20::{}`", name),
+                    reason,
+                );
+            }
+        }
+        let emptied = leaves
+            .iter()
+            .all(|l| l.local().is_some_and(|l| dropped.contains(&l)));
+        let top_level = items
+            .iter()
+            .any(|i| matches!(i, syn::Item::Use(u) if std::ptr::eq(u, item)));
+        if top_level && !new_uses.is_empty() {
+            let declarations: Vec<_> = new_uses
+                .drain(..)
+                .map(|(module, names)| format!("use {}::{};", module, tree(names)))
+                .collect();
+            if emptied {
+                rec.edit(
+                    &file.path,
+                    Edit::replace(range_of(item), declarations.join("\n")),
+                );
+                continue;
+            }
+            rec.edit(
+                &file.path,
+                Edit::insert(range_of(item).end, format!("\n{}", declarations.join("\n"))),
+            );
+        }
+        if let Some(edit) = rewrite_use(&file.text, item, &dropped, &[]) {
+            rec.edit(&file.path, edit);
+        }
+    }
+    for (module, names) in new_uses {
+        if let Some(edit) = import(items, module, names) {
+            rec.edit(&file.path, edit);
+        }
+    }
+
+    let defined = items
+        .iter()
+        .any(|item| matches!(item, syn::Item::Const(c) if c.ident == "BLOCK_SIZE"))
+        || local_names(&file.ast).contains("BLOCK_SIZE");
+    if functions.is_some() && !defined {
+        if let Some(first) = items.iter().find(|i| !matches!(i, syn::Item::Use(_))) {
+            rec.edit(&file.path, Edit::insert(range_of(first).start, BLOCK_SIZE));
+        }
+    }
+}
+
+/// Adds a code hash field next to every token address the messages are sent
+/// to, and fills it in from `InstantiateMsg` where the struct is built in
+/// `instantiate`.
+fn add_hash_fields(krate: &CrateSources, holders: &BTreeSet<(String, String)>, rec: &mut Recorder) {
+    let mut holders = holders.clone();
+    if let Some(fields) = struct_fields(krate, "InstantiateMsg") {
+        let given: Vec<_> = holders
+            .iter()
+            .filter(|(_, field)| fields.iter().any(|f| f.ident.as_ref().unwrap() == field))
+            .map(|(_, field)| ("InstantiateMsg".to_string(), field.clone()))
+            .collect();
+        holders.extend(given);
+    }
+
+    for (name, field) in &holders {
+        let hash = hash_name(field);
+        for file in &krate.files {
+            for item in &file.ast.items {
+                let syn::Item::Struct(s) = item else {
+                    continue;
+                };
+                let syn::Fields::Named(fields) = &s.fields else {
+                    continue;
+                };
+                if s.ident != name
+                    || fields
+                        .named
+                        .iter()
+                        .any(|f| f.ident.as_ref().unwrap() == &hash)
+                {
+                    continue;
+                }
+                let Some(address) = fields
+                    .named
+                    .iter()
+                    .find(|f| f.ident.as_ref().unwrap() == field)
+                else {
+                    continue;
+                };
+                let vis = match &address.vis {
+                    syn::Visibility::Inherited => String::new(),
+                    vis => format!("{} ", file.text_of(vis)),
+                };
+                rec.edit(
+                    &file.path,
+                    insert_field(
+                        &file.text,
+                        s,
+                        &fields.named,
+                        address,
+                        &format!("{}{}: String", vis, hash),
+                    ),
+                );
+                rec.applied(
+                    &file.path,
+                    line_of(address),
+                    format!("`{}` gets a `{}` for `{}`", name, hash, field),
+                );
+            }
+        }
+    }
+
+    for file in &krate.files {
+        let mut constructions = Constructions::default();
+        constructions.visit_file(&file.ast);
+        for (node, sig) in constructions.structs {
+            let name = node.path.segments.last().unwrap().ident.to_string();
+            for (_, field) in holders.iter().filter(|(holder, _)| *holder == name) {
+                let hash = hash_name(field);
+                let member = |n: &str| {
+                    node.fields
+                        .iter()
+                        .find(|f| matches!(&f.member, syn::Member::Named(i) if i == n))
+                };
+                let Some(address) = member(field) else {
+                    continue;
+                };
+                if member(&hash).is_some() || node.rest.is_some() {
+                    continue;
+                }
+                let msg = sig.and_then(|sig| typed_param_of(sig, "InstantiateMsg"));
+                let from_msg = name != "InstantiateMsg"
+                    && holders.contains(&("InstantiateMsg".to_string(), field.clone()));
+                match msg.filter(|_| from_msg) {
+                    Some(msg) => {
+                        rec.edit(
+                            &file.path,
+                            insert_field(
+                                &file.text,
+                                node,
+                                &node.fields,
+                                address,
+                                &format!("{}: {}.{}.clone()", hash, msg, hash),
+                            ),
+                        );
+                        rec.applied(
+                            &file.path,
+                            line_of(node),
+                            format!("`{}` gets `{}` from `{}`", name, hash, msg),
+                        );
+                    }
+                    None => rec.skipped(
+                        &file.path,
+                        line_of(node),
+                        format!("`{}` constructed", name),
+                        format!(
+                            "set `{}` to the code hash of the token at `{}`",
+                            hash, field
+                        ),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// `token_code_hash` for `token` or `token_addr`; `code_hash` for names that
+/// just mean "the contract".
+fn hash_name(name: &str) -> String {
+    let stem = ["_addr", "_address", "_contract"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .unwrap_or(name);
+    if CONTRACT_PARAMS.contains(&name) || CONTRACT_PARAMS.contains(&stem) {
+        "code_hash".to_string()
+    } else {
+        format!("{}_code_hash", stem)
+    }
+}
+
+/// The struct whose field `field`, read through `base`, holds a token address:
+/// the only one with such a field, leaving out messages, or the one named
+/// like `base`.
+fn holder_of(krate: &CrateSources, field: &str, base: &str) -> Option<String> {
+    let holders: Vec<String> = krate
+        .files
+        .iter()
+        .flat_map(|f| &f.ast.items)
+        .filter_map(|item| match item {
+            syn::Item::Struct(s) if !s.ident.to_string().ends_with("Msg") => Some(s),
+            _ => None,
+        })
+        .filter(|s| {
+            s.fields.iter().any(|f| {
+                f.ident.as_ref().is_some_and(|i| i == field)
+                    && (type_named(&f.ty, "Addr") || type_named(&f.ty, "String"))
+            })
+        })
+        .map(|s| s.ident.to_string())
+        .collect();
+    match &holders[..] {
+        [holder] => Some(holder.clone()),
+        _ => holders
+            .into_iter()
+            .find(|h| h.to_lowercase() == base.replace('_', "").to_lowercase()),
+    }
+}
+
+fn struct_fields<'a>(krate: &'a CrateSources, name: &str) -> Option<Vec<&'a syn::Field>> {
+    krate
+        .files
+        .iter()
+        .flat_map(|f| &f.ast.items)
+        .find_map(|item| match item {
+            syn::Item::Struct(s) if s.ident == name => match &s.fields {
+                syn::Fields::Named(fields) => Some(fields.named.iter().collect()),
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+/// The name of the parameter of `sig` typed `ty`.
+fn typed_param_of<'a>(sig: &'a syn::Signature, ty: &str) -> Option<&'a syn::Ident> {
+    sig.inputs.iter().find_map(|arg| match arg {
+        syn::FnArg::Typed(p) if type_named(&p.ty, ty) => match &*p.pat {
+            syn::Pat::Ident(i) => Some(&i.ident),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn env_param(sig: &syn::Signature) -> Option<&syn::Ident> {
+    typed_param_of(sig, "Env")
+}
+
+/// The first `Response::new()` in a function body.
+fn response_new(block: &syn::Block) -> Option<&syn::Expr> {
+    struct Finder<'ast>(Option<&'ast syn::Expr>);
+    impl<'ast> Visit<'ast> for Finder<'ast> {
+        fn visit_expr(&mut self, node: &'ast syn::Expr) {
+            if self.0.is_some() {
+                return;
+            }
+            if let syn::Expr::Call(c) = node {
+                if c.args.is_empty() && path_ends(&c.func, &["Response", "new"]) {
+                    self.0 = Some(node);
+                    return;
+                }
+            }
+            visit::visit_expr(self, node);
+        }
+    }
+    let mut finder = Finder(None);
+    finder.visit_block(block);
+    finder.0
+}
+
+/// `function(arg, ...)`, with an argument per line when `wrap` is set.
+fn call(function: &str, arguments: &[String], indent: &str, wrap: bool) -> String {
+    if wrap {
+        let lines: Vec<_> = arguments
+            .iter()
+            .map(|a| format!("{}    {},\n", indent, a))
+            .collect();
+        format!("{}(\n{}{})", function, lines.concat(), indent)
+    } else {
+        format!("{}({})", function, arguments.join(", "))
+    }
+}
+
+fn tree(names: &[&str]) -> String {
+    match names {
+        [name] => name.to_string(),
+        names => format!("{{{}}}", names.join(", ")),
+    }
+}
+
+fn path_text(path: &syn::Path) -> String {
+    let segments: Vec<_> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+    segments[segments.len().saturating_sub(2)..].join("::")
+}
+
+fn type_named(ty: &syn::Type, name: &str) -> bool {
+    matches!(ty, syn::Type::Path(t) if t.path.segments.last().is_some_and(|s| s.ident == name))
+}
+
+fn path_ends(expr: &syn::Expr, names: &[&str]) -> bool {
+    let syn::Expr::Path(p) = expr else {
+        return false;
+    };
+    let segments: Vec<_> = p
+        .path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect();
+    segments.ends_with(&names.iter().map(|n| n.to_string()).collect::<Vec<_>>())
+}
+
+fn is_cw20_execute(path: &syn::Path) -> bool {
+    let n = path.segments.len();
+    n >= 2 && path.segments[n - 2].ident == "Cw20ExecuteMsg"
+}
+
+/// `vec![]`, `Vec::new()` or `[]`.
+fn no_funds(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Macro(m) => m.mac.path.is_ident("vec") && m.mac.tokens.is_empty(),
+        syn::Expr::Call(c) => c.args.is_empty() && path_ends(&c.func, &["Vec", "new"]),
+        syn::Expr::Array(a) => a.elems.is_empty(),
+        _ => false,
+    }
+}
+
+/// `expr` without `.to_string()`, `.clone()`, `.into()` or a borrow.
+fn without_conversions(expr: &syn::Expr) -> &syn::Expr {
+    match expr {
+        syn::Expr::MethodCall(m)
+            if m.args.is_empty()
+                && ["clone", "into", "to_string", "as_str"]
+                    .contains(&m.method.to_string().as_str()) =>
+        {
+            without_conversions(&m.receiver)
+        }
+        syn::Expr::Reference(r) => without_conversions(&r.expr),
+        syn::Expr::Paren(p) => without_conversions(&p.expr),
+        _ => expr,
+    }
+}
+
+/// The `Cw20ExecuteMsg` in `to_binary(&Cw20ExecuteMsg::...)?`.
+fn serialized_message(expr: &syn::Expr) -> Option<&syn::ExprStruct> {
+    let syn::Expr::Try(t) = expr else {
+        return None;
+    };
+    let syn::Expr::Call(c) = &*t.expr else {
+        return None;
+    };
+    let serializer = path_ends(&c.func, &["to_binary"]) || path_ends(&c.func, &["to_json_binary"]);
+    if !serializer || c.args.len() != 1 {
+        return None;
+    }
+    match without_conversions(&c.args[0]) {
+        syn::Expr::Struct(s) if is_cw20_execute(&s.path) => Some(s),
+        _ => None,
+    }
+}
+
+/// `cw20` messages sent and received, and every path segment, to tell which
+/// imports are still needed.
+#[derive(Default)]
+struct Sites<'ast> {
+    sends: Vec<Send<'ast>>,
+    /// `Cw20ExecuteMsg`s built other than for a send the rule translates.
+    others: Vec<&'ast syn::ExprStruct>,
+    receive_paths: Vec<&'ast syn::Path>,
+    /// `msg` of a `Cw20ReceiveMsg` parameter.
+    receive_fields: Vec<&'ast syn::ExprField>,
+    receive_structs: Vec<&'ast syn::ExprStruct>,
+    receive_patterns: Vec<&'ast syn::PatStruct>,
+    segments: Vec<(String, Range<usize>)>,
+    sig: Option<&'ast syn::Signature>,
+}
+
+impl<'ast> Sites<'ast> {
+    fn send(&self, node: &'ast syn::Expr) -> Option<Send<'ast>> {
+        let execute = |s: &'ast syn::ExprStruct| {
+            if !s
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect::<Vec<_>>()
+                .ends_with(&["WasmMsg".to_string(), "Execute".to_string()])
+            {
+                return None;
+            }
+            let field = |name: &str| {
+                s.fields.iter().find_map(|f| match &f.member {
+                    syn::Member::Named(i) if i == name => Some(&f.expr),
+                    _ => None,
+                })
+            };
+            Some(Send {
+                node,
+                contract: field("contract_addr")?,
+                funds: field("funds")?,
+                message: serialized_message(field("msg")?)?,
+                infallible: true,
+                sig: self.sig,
+            })
+        };
+        match node {
+            syn::Expr::Call(c)
+                if c.args.len() == 1 && path_ends(&c.func, &["CosmosMsg", "Wasm"]) =>
+            {
+                match &c.args[0] {
+                    syn::Expr::Struct(s) => execute(s),
+                    _ => None,
+                }
+            }
+            syn::Expr::Struct(s) => execute(s),
+            syn::Expr::Call(c) if c.args.len() == 3 && path_ends(&c.func, &["wasm_execute"]) => {
+                match without_conversions(&c.args[1]) {
+                    syn::Expr::Struct(s) if is_cw20_execute(&s.path) => Some(Send {
+                        node,
+                        contract: &c.args[0],
+                        funds: &c.args[2],
+                        message: s,
+                        infallible: false,
+                        sig: self.sig,
+                    }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn in_send(&self, node: &impl syn::spanned::Spanned) -> bool {
+        let range = range_of(node);
+        self.sends.iter().any(|s| {
+            let outer = range_of(s.node);
+            outer.start <= range.start && range.end <= outer.end
+        })
+    }
+
+    fn receive_param(&self, expr: &syn::Expr) -> bool {
+        let syn::Expr::Path(p) = expr else {
+            return false;
+        };
+        let (Some(sig), Some(ident)) = (self.sig, p.path.get_ident()) else {
+            return false;
+        };
+        typed_param(sig, ident).is_some_and(|p| type_named(&p.ty, "Cw20ReceiveMsg"))
+    }
+}
+
+impl<'ast> Visit<'ast> for Sites<'ast> {
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        let outer = self.sig.replace(&node.sig);
+        visit::visit_item_fn(self, node);
+        self.sig = outer;
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast syn::ImplItemFn) {
+        let outer = self.sig.replace(&node.sig);
+        visit::visit_impl_item_fn(self, node);
+        self.sig = outer;
+    }
+
+    fn visit_item_use(&mut self, _: &'ast syn::ItemUse) {}
+
+    fn visit_expr(&mut self, node: &'ast syn::Expr) {
+        if !self.in_send(node) {
+            if let Some(send) = self.send(node) {
+                self.sends.push(send);
+            }
+        }
+        match node {
+            syn::Expr::Struct(s) if is_cw20_execute(&s.path) && !self.in_send(s) => {
+                self.others.push(s)
+            }
+            syn::Expr::Struct(s) if s.path.segments.last().unwrap().ident == "Cw20ReceiveMsg" => {
+                self.receive_structs.push(s)
+            }
+            syn::Expr::Field(f)
+                if matches!(&f.member, syn::Member::Named(i) if i == "msg")
+                    && self.receive_param(&f.base) =>
+            {
+                self.receive_fields.push(f)
+            }
+            _ => {}
+        }
+        visit::visit_expr(self, node);
+    }
+
+    fn visit_pat_struct(&mut self, node: &'ast syn::PatStruct) {
+        if node.path.segments.last().unwrap().ident == "Cw20ReceiveMsg" {
+            self.receive_patterns.push(node);
+        }
+        visit::visit_pat_struct(self, node);
+    }
+
+    fn visit_path(&mut self, node: &'ast syn::Path) {
+        if node
+            .segments
+            .last()
+            .is_some_and(|s| s.ident == "Cw20ReceiveMsg")
+        {
+            self.receive_paths.push(node);
+        }
+        visit::visit_path(self, node);
+    }
+
+    fn visit_path_segment(&mut self, node: &'ast syn::PathSegment) {
+        self.segments
+            .push((node.ident.to_string(), range_of(&node.ident)));
+        visit::visit_path_segment(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        // macro arguments aren't parsed, so count the identifiers in them too
+        fn idents(tokens: proc_macro2::TokenStream, out: &mut Vec<(String, Range<usize>)>) {
+            for token in tokens {
+                match token {
+                    proc_macro2::TokenTree::Ident(i) => out.push((i.to_string(), range_of(&i))),
+                    proc_macro2::TokenTree::Group(g) => idents(g.stream(), out),
+                    _ => {}
+                }
+            }
+        }
+        idents(node.tokens.clone(), &mut self.segments);
+        visit::visit_macro(self, node);
+    }
+}
+
+/// Struct expressions, with the function they're built in.
+#[derive(Default)]
+struct Constructions<'ast> {
+    structs: Vec<(&'ast syn::ExprStruct, Option<&'ast syn::Signature>)>,
+    sig: Option<&'ast syn::Signature>,
+}
+
+impl<'ast> Visit<'ast> for Constructions<'ast> {
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        let outer = self.sig.replace(&node.sig);
+        visit::visit_item_fn(self, node);
+        self.sig = outer;
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast syn::ImplItemFn) {
+        let outer = self.sig.replace(&node.sig);
+        visit::visit_impl_item_fn(self, node);
+        self.sig = outer;
+    }
+
+    fn visit_expr_struct(&mut self, node: &'ast syn::ExprStruct) {
+        self.structs.push((node, self.sig));
+        visit::visit_expr_struct(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture(side: &str) -> CrateSources {
+        CrateSources::load(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures/cw20")
+                .join(side),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn cw20_flows_become_snip20_messages() {
+        let before = fixture("before");
+        let after = fixture("after");
+        let migration = translate_cw20(&before);
+
+        for file in &after.files {
+            let migrated = migration
+                .files
+                .get(&file.path)
+                .unwrap_or(&before.file(&file.path).unwrap().text);
+            assert_eq!(&file.text, migrated, "{}", file.path.display());
+        }
+
+        let skipped: Vec<_> = migration
+            .skipped()
+            .map(|t| (t.file.to_str().unwrap(), t.line, t.description.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("src/contract.rs", 7, "`cw20::Cw20Coin`"),
+                ("src/contract.rs", 135, "`InstantiateMsg` constructed"),
+            ],
+            skipped
+        );
+        assert_eq!(1, migration.warnings().count());
+    }
+}
@@ -0,0 +1,247 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use syn::spanned::Spanned;
+
+use crate::error::MigrateError;
+
+/// A parsed Rust source file of the crate being migrated.
+pub struct SourceFile {
+    /// Path relative to the crate root, e.g. `src/state.rs`.
+    pub path: PathBuf,
+    pub text: String,
+    pub ast: syn::File,
+}
+
+impl SourceFile {
+    pub fn parse(path: impl Into<PathBuf>, text: impl Into<String>) -> Result<Self, MigrateError> {
+        let path = path.into();
+        let mut text = text.into();
+        // syn strips a leading BOM, which would shift every span we get back
+        if text.starts_with('\u{feff}') {
+            text.drain(..'\u{feff}'.len_utf8());
+        }
+        let ast = syn::parse_file(&text).map_err(|source| MigrateError::Parse {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(SourceFile { path, text, ast })
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> &str {
+        &self.text[range]
+    }
+
+    /// Source text of a syntax node.
+    pub fn text_of(&self, node: &impl Spanned) -> &str {
+        self.slice(range_of(node))
+    }
+
+    /// The `crate::...` path of the module this file defines.
+    pub fn module_path(&self) -> String {
+        let mut segments = vec!["crate".to_string()];
+        let relative = self.path.strip_prefix("src").unwrap_or(&self.path);
+        for component in relative.with_extension("").components() {
+            let name = component.as_os_str().to_string_lossy();
+            if name != "lib" && name != "main" && name != "mod" {
+                segments.push(name.into_owned());
+            }
+        }
+        segments.join("::")
+    }
+}
+
+/// Every Rust source file under a crate's `src/` directory.
+pub struct CrateSources {
+    pub root: PathBuf,
+    pub files: Vec<SourceFile>,
+}
+
+impl CrateSources {
+    pub fn load(root: impl AsRef<Path>) -> Result<Self, MigrateError> {
+        let root = root.as_ref().to_path_buf();
+        let src = root.join("src");
+        if !src.is_dir() {
+            return Err(MigrateError::NotACrate { path: root });
+        }
+
+        let mut paths = vec![];
+        collect_rust_files(&src, &mut paths)?;
+        paths.sort();
+
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            let text = fs::read_to_string(&path).map_err(|e| MigrateError::io(&path, e))?;
+            let relative = path.strip_prefix(&root).unwrap_or(&path).to_path_buf();
+            files.push(SourceFile::parse(relative, text)?);
+        }
+        Ok(CrateSources { root, files })
+    }
+
+    /// Builds a crate from in-memory `(relative path, contents)` pairs.
+    pub fn from_files<P, T>(
+        root: impl Into<PathBuf>,
+        files: impl IntoIterator<Item = (P, T)>,
+    ) -> Result<Self, MigrateError>
+    where
+        P: Into<PathBuf>,
+        T: Into<String>,
+    {
+        let files = files
+            .into_iter()
+            .map(|(path, text)| SourceFile::parse(path, text))
+            .collect::<Result<_, _>>()?;
+        Ok(CrateSources {
+            root: root.into(),
+            files,
+        })
+    }
+
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<&SourceFile> {
+        self.files.iter().find(|f| f.path == path.as_ref())
+    }
+
+    /// The crate as it looks after `changes` (relative path → new contents),
+    /// so rules can be run one after another.
+    pub fn with_changes<'a>(
+        &self,
+        changes: impl IntoIterator<Item = (&'a PathBuf, &'a String)>,
+    ) -> Result<Self, MigrateError> {
+        let mut files: Vec<(PathBuf, String)> = self
+            .files
+            .iter()
+            .map(|f| (f.path.clone(), f.text.clone()))
+            .collect();
+        for (path, text) in changes {
+            match files.iter_mut().find(|(p, _)| p == path) {
+                Some(file) => file.1 = text.clone(),
+                None => files.push((path.clone(), text.clone())),
+            }
+        }
+        CrateSources::from_files(self.root.clone(), files)
+    }
+}
+
+fn collect_rust_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), MigrateError> {
+    let entries = fs::read_dir(dir).map_err(|e| MigrateError::io(dir, e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| MigrateError::io(dir, e))?.path();
+        if path.is_dir() {
+            collect_rust_files(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Byte range of a syntax node within the file it was parsed from.
+pub fn range_of(node: &impl Spanned) -> Range<usize> {
+    node.span().byte_range()
+}
+
+/// 1-based line number of a syntax node.
+pub fn line_of(node: &impl Spanned) -> usize {
+    node.span().start().line
+}
+
+/// A textual replacement of a byte range in a source file.
+///
+/// Rewrites are applied as edits to the original text rather than by printing
+/// the modified syntax tree, so comments and formatting survive the migration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl Edit {
+    pub fn replace(range: Range<usize>, replacement: impl Into<String>) -> Self {
+        Edit {
+            range,
+            replacement: replacement.into(),
+        }
+    }
+
+    pub fn insert(at: usize, text: impl Into<String>) -> Self {
+        Edit::replace(at..at, text)
+    }
+
+    pub fn delete(range: Range<usize>) -> Self {
+        Edit::replace(range, "")
+    }
+}
+
+/// Applies non-overlapping edits to `text`. When two edits overlap, the one
+/// starting first wins and the other is dropped.
+pub fn apply_edits(text: &str, mut edits: Vec<Edit>) -> String {
+    edits.sort_by_key(|e| (e.range.start, e.range.end));
+
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for edit in edits {
+        if edit.range.start < cursor {
+            continue;
+        }
+        out.push_str(&text[cursor..edit.range.start]);
+        out.push_str(&edit.replacement);
+        cursor = edit.range.end;
+    }
+    out.push_str(&text[cursor..]);
+    out
+}
+
+/// Extends `range` backwards over any whitespace preceding it, so deleting the
+/// range doesn't leave a double space behind.
+pub fn with_leading_whitespace(text: &str, range: Range<usize>) -> Range<usize> {
+    let trimmed = text[..range.start].trim_end();
+    trimmed.len()..range.end
+}
+
+/// Extends `range` to the whole lines it touches, trailing newline included,
+/// so deleting it doesn't leave an empty line behind.
+pub fn whole_lines(text: &str, range: Range<usize>) -> Range<usize> {
+    let start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = text[range.end..]
+        .find('\n')
+        .map_or(text.len(), |i| range.end + i + 1);
+    start..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_are_applied_in_order() {
+        let text = "let a = load(x);";
+        let edits = vec![
+            Edit::replace(13..14, "&x"),
+            Edit::replace(8..12, "get"),
+            Edit::insert(15, "?"),
+        ];
+        assert_eq!("let a = get(&x)?;", apply_edits(text, edits));
+    }
+
+    #[test]
+    fn overlapping_edits_are_dropped() {
+        let edits = vec![Edit::replace(0..4, "ab"), Edit::replace(2..6, "cd")];
+        assert_eq!("ab4567", apply_edits("01234567", edits));
+    }
+
+    #[test]
+    fn ranges_extend_to_whole_lines() {
+        let text = "mod a;\n    pub mod b;\nmod c;";
+        assert_eq!(7..22, whole_lines(text, 11..21));
+        assert_eq!(22..28, whole_lines(text, 22..28));
+    }
+
+    #[test]
+    fn spans_map_to_byte_ranges() {
+        let file = SourceFile::parse("src/state.rs", "// é\nconst A: u8 = 1;").unwrap();
+        assert_eq!("const A: u8 = 1;", file.text_of(&file.ast.items[0]));
+        assert_eq!(2, line_of(&file.ast.items[0]));
+        assert_eq!("crate::state", file.module_path());
+    }
+}
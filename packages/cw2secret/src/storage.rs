@@ -0,0 +1,1899 @@
+//! Rewrites `cw_storage_plus::{Item, Map}` into `secret_toolkit::storage::{Item, Keymap}`.
+//!
+//! This automates steps 4a and 4b of the migration pathway: storage
+//! declarations get byte-string namespaces, `Map` becomes `Keymap`, and every
+//! call site is moved over to the `Keymap` API (`load` → `get`,
+//! `save` → `insert`, keys passed by reference). Tuple keys nest keymaps with
+//! `add_suffix`; `IndexedMap`s are left to the `indexed_map` module.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use syn::visit::{self, Visit};
+use syn::{Expr, ExprClosure, ExprMethodCall, GenericArgument, Pat, PathArguments, Type};
+
+use crate::code_hash::calls_of;
+use crate::imports::{indentation, local_names, use_leaves, UseFinder};
+use crate::indexed_map::{
+    migrate_indexed_maps, rewrite_function_imports, rewrite_index_calls, IndexedMap,
+};
+use crate::source::{
+    line_of, range_of, whole_lines, with_leading_whitespace, CrateSources, Edit, SourceFile,
+};
+use crate::transform::{Migration, Recorder};
+
+const RULE: &str = "storage";
+
+/// Methods that behave the same on `Result` and `Option`, so a `Map::load`
+/// feeding into them can become a plain `Keymap::get`.
+const UNWRAPPERS: &[&str] = &[
+    "unwrap_or",
+    "unwrap_or_else",
+    "unwrap_or_default",
+    "unwrap",
+    "expect",
+];
+
+/// `Map` methods that iterate in key order, which `Keymap` can't do.
+const ORDERED_ITERATION: &[&str] = &[
+    "range",
+    "range_raw",
+    "keys",
+    "keys_raw",
+    "prefix",
+    "sub_prefix",
+    "prefix_range",
+    "prefix_range_raw",
+];
+
+/// How `Keymap` iteration differs from `Map::range`.
+pub(crate) const INSERTION_ORDER: &str =
+    "`Keymap` iterates in insertion order, and removing an entry moves the last one into its \
+     place, so entries no longer come sorted by key";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageKind {
+    Item,
+    Map,
+}
+
+/// A `cw_storage_plus` storage constant, e.g. `USER_STATE: Map<Addr, UserState>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageDecl {
+    pub name: String,
+    pub kind: StorageKind,
+    /// Namespace string passed to `new`, when it is a literal.
+    pub namespace: Option<String>,
+    /// Key type as it will be declared on the `Keymap`, e.g. `Addr` for `Map<&Addr, _>`.
+    pub key: Option<String>,
+    /// Value type as written, e.g. `UserState`.
+    pub value: String,
+    /// Module that declares the constant, e.g. `crate::state`.
+    pub module: String,
+    pub file: PathBuf,
+    pub line: usize,
+    pub(crate) key_borrowed: bool,
+    pub(crate) key_is_str: bool,
+    /// Owned types of the leading components of a tuple key, each nesting the
+    /// `Keymap` one level with `add_suffix`, e.g. `Addr` for `Map<(&Addr, u64), _>`.
+    pub(crate) suffixes: Vec<String>,
+    /// The function that built an `IndexedMap`, e.g. `tokens`. Saves and
+    /// removals go through the generated `save_tokens`/`remove_tokens`, which
+    /// keep its indexes in step.
+    pub(crate) indexed: Option<String>,
+}
+
+/// Finds every `cw_storage_plus::{Item, Map}` constant declared in the crate.
+pub fn find_declarations(krate: &CrateSources) -> Vec<StorageDecl> {
+    krate
+        .files
+        .iter()
+        .flat_map(|file| {
+            let imports = storage_imports(&file.ast);
+            let mut finder = DeclFinder {
+                imports: &imports,
+                found: vec![],
+            };
+            finder.visit_file(&file.ast);
+            finder
+                .found
+                .into_iter()
+                .filter_map(|found| describe(file, &found))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Migrates storage declarations and their call sites across the whole crate.
+pub fn migrate_storage(krate: &CrateSources) -> Migration {
+    migrate_storage_with(krate, &BTreeMap::new())
+}
+
+/// Like [`migrate_storage`], also changing the namespaces listed in `keys`
+/// (old → new) on the way, as `[storage.keys]` in `cw2secret.toml` asks.
+pub fn migrate_storage_with(krate: &CrateSources, keys: &BTreeMap<String, String>) -> Migration {
+    let mut rec = Recorder::new(RULE);
+
+    let mut decls = HashMap::new();
+    let mut indexed: Vec<IndexedMap> = vec![];
+    for file in &krate.files {
+        let imports = storage_imports(&file.ast);
+        let mut finder = DeclFinder {
+            imports: &imports,
+            found: vec![],
+        };
+        finder.visit_file(&file.ast);
+        for found in finder.found {
+            if let Some(decl) = rewrite_declaration(file, &found, keys, &mut rec) {
+                decls.insert(decl.name.clone(), decl);
+            }
+        }
+        for map in migrate_indexed_maps(file, &imports, &mut rec) {
+            // call sites name the map by its function: `tokens().load(..)`
+            decls.insert(format!("{}()", map.function), map.decl.clone());
+            indexed.push(map);
+        }
+    }
+
+    let mut paged = vec![];
+    for file in &krate.files {
+        let names = local_names(&file.ast);
+        let mut sites = CallSites {
+            decls: &decls,
+            contexts: HashMap::new(),
+            parents: HashMap::new(),
+            func: None,
+            sites: vec![],
+        };
+        sites.visit_file(&file.ast);
+        let mut removed = vec![];
+        let mut used = rewrite_index_calls(file, &indexed, &mut rec);
+        for site in sites.sites {
+            let decl = &decls[&site.call.receiver_name()];
+            if let Some(function) = &decl.indexed {
+                used.insert(match site.call.method.to_string().as_str() {
+                    method @ ("save" | "remove") => format!("{}_{}", method, function),
+                    _ => decl.name.clone(),
+                });
+            }
+            match decl.kind {
+                StorageKind::Item => rewrite_item_call(file, &names, decl, &site, &mut rec),
+                StorageKind::Map => {
+                    if let Some(page) = rewrite_map_call(file, decl, &site, &mut rec) {
+                        removed.extend(page.removed.iter().cloned());
+                        paged.push(page);
+                    }
+                }
+            }
+        }
+        let declares_indexed = indexed.iter().any(|m| m.decl.file == file.path);
+        rewrite_imports(
+            file,
+            &unused_bounds(file, &removed),
+            declares_indexed,
+            &mut rec,
+        );
+        rewrite_function_imports(file, &indexed, &used, &mut rec);
+    }
+
+    for page in &paged {
+        for file in &krate.files {
+            for call in calls_of(&file.ast, &page.function, page.method) {
+                rec.skipped(
+                    &file.path,
+                    line_of(call),
+                    format!("`{}` call", page.function),
+                    format!(
+                        "`{}` takes a page number instead of `{}` now; pass a `page: Option<u32>`",
+                        page.function, page.param
+                    ),
+                );
+            }
+        }
+    }
+
+    rec.finish(krate)
+}
+
+/// Local names brought in from `cw_storage_plus`, mapped to their original names.
+fn storage_imports(file: &syn::File) -> HashMap<String, String> {
+    let mut uses = UseFinder::default();
+    uses.visit_file(file);
+    uses.uses
+        .iter()
+        .filter(|u| is_storage_plus_use(u))
+        .flat_map(|u| use_leaves(&u.tree))
+        .filter_map(|leaf| Some((leaf.local()?.to_string(), leaf.name?)))
+        .collect()
+}
+
+fn is_storage_plus_use(item: &syn::ItemUse) -> bool {
+    matches!(&item.tree, syn::UseTree::Path(p) if p.ident == "cw_storage_plus")
+}
+
+/// `use cw_storage_plus::{Item, Map};` → `use secret_toolkit::storage::{Item, Keymap};`
+///
+/// Names in `unused`, like a `Bound` only the rewritten range calls needed, are
+/// dropped. When the file's `IndexedMap`s were rewritten, `IndexedMap` becomes
+/// `Keymap` and the index types go.
+fn rewrite_imports(file: &SourceFile, unused: &HashSet<String>, indexed: bool, rec: &mut Recorder) {
+    let mut uses = UseFinder::default();
+    uses.visit_file(&file.ast);
+
+    for item in uses.uses.into_iter().filter(|u| is_storage_plus_use(u)) {
+        let line = line_of(item);
+        let mut mapped = vec![];
+        let mut rest = vec![];
+        let mut dropped = vec![];
+        for leaf in use_leaves(&item.tree) {
+            if leaf.local().is_some_and(|name| unused.contains(name)) {
+                dropped.push(leaf.render(leaf.name.as_deref().unwrap()));
+                continue;
+            }
+            match leaf.name.as_deref() {
+                Some("Item") => mapped.push(leaf.render("Item")),
+                Some("Map") => mapped.push(leaf.render("Keymap")),
+                Some("IndexedMap") if indexed => mapped.push(leaf.render("Keymap")),
+                Some(name @ ("MultiIndex" | "UniqueIndex" | "IndexList" | "Index")) if indexed => {
+                    dropped.push(name.to_string())
+                }
+                Some(name) => {
+                    rec.skipped(
+                        &file.path,
+                        line,
+                        format!("import of `cw_storage_plus::{}`", name),
+                        "no `secret_toolkit::storage` equivalent",
+                    );
+                    rest.push(leaf.render(name));
+                }
+                None => rec.skipped(
+                    &file.path,
+                    line,
+                    "glob import of `cw_storage_plus`",
+                    "import `secret_toolkit::storage::{Item, Keymap}` explicitly",
+                ),
+            }
+        }
+        for name in &dropped {
+            rec.applied(
+                &file.path,
+                line,
+                format!("unused import of `cw_storage_plus::{}` removed", name),
+            );
+        }
+        mapped.dedup();
+        if mapped.is_empty() {
+            if rest.is_empty() && !dropped.is_empty() {
+                rec.edit(
+                    &file.path,
+                    Edit::delete(whole_lines(&file.text, range_of(item))),
+                );
+            } else if !dropped.is_empty() {
+                rec.edit(
+                    &file.path,
+                    Edit::replace(
+                        range_of(&item.tree),
+                        storage_use_tree("cw_storage_plus", &rest),
+                    ),
+                );
+            }
+            continue;
+        }
+
+        rec.edit(
+            &file.path,
+            Edit::replace(
+                range_of(&item.tree),
+                storage_use_tree("secret_toolkit::storage", &mapped),
+            ),
+        );
+        if !rest.is_empty() {
+            let indent = indentation(&file.text, range_of(item).start);
+            let vis = file.text_of(&item.vis);
+            let vis = if vis.is_empty() {
+                String::new()
+            } else {
+                format!("{} ", vis)
+            };
+            rec.edit(
+                &file.path,
+                Edit::insert(
+                    range_of(item).end,
+                    format!(
+                        "\n{}{}use {};",
+                        indent,
+                        vis,
+                        storage_use_tree("cw_storage_plus", &rest)
+                    ),
+                ),
+            );
+        }
+        rec.applied(
+            &file.path,
+            line,
+            format!(
+                "import {} from `secret_toolkit::storage`",
+                mapped.join(", ")
+            ),
+        );
+    }
+}
+
+fn storage_use_tree(module: &str, names: &[String]) -> String {
+    match names {
+        [name] => format!("{}::{}", module, name),
+        names => format!("{}::{{{}}}", module, names.join(", ")),
+    }
+}
+
+/// A `const` or `static` whose type is a `cw_storage_plus` `Item` or `Map`.
+struct FoundDecl<'ast> {
+    ident: &'ast syn::Ident,
+    ty: &'ast syn::TypePath,
+    init: &'ast Expr,
+    kind: StorageKind,
+}
+
+struct DeclFinder<'a, 'ast> {
+    imports: &'a HashMap<String, String>,
+    found: Vec<FoundDecl<'ast>>,
+}
+
+impl<'a, 'ast> DeclFinder<'a, 'ast> {
+    fn check(&mut self, ident: &'ast syn::Ident, ty: &'ast Type, init: &'ast Expr) {
+        let Type::Path(ty) = ty else {
+            return;
+        };
+        if let Some(kind) = storage_kind(&ty.path, self.imports) {
+            self.found.push(FoundDecl {
+                ident,
+                ty,
+                init,
+                kind,
+            });
+        }
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for DeclFinder<'a, 'ast> {
+    fn visit_item_const(&mut self, node: &'ast syn::ItemConst) {
+        self.check(&node.ident, &node.ty, &node.expr);
+    }
+
+    fn visit_item_static(&mut self, node: &'ast syn::ItemStatic) {
+        self.check(&node.ident, &node.ty, &node.expr);
+    }
+}
+
+fn storage_kind(path: &syn::Path, imports: &HashMap<String, String>) -> Option<StorageKind> {
+    let last = path.segments.last()?.ident.to_string();
+    let name = if path.segments.len() == 1 {
+        imports.get(&last)?.as_str()
+    } else if path.segments[0].ident == "cw_storage_plus" {
+        last.as_str()
+    } else {
+        return None;
+    };
+    match name {
+        "Item" => Some(StorageKind::Item),
+        "Map" => Some(StorageKind::Map),
+        _ => None,
+    }
+}
+
+pub(crate) fn generic_types(path: &syn::Path) -> Vec<&Type> {
+    let Some(PathArguments::AngleBracketed(args)) = path.segments.last().map(|s| &s.arguments)
+    else {
+        return vec![];
+    };
+    args.args
+        .iter()
+        .filter_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Owned form of a borrowed `Map` key: `&Addr` → `Addr`, `&str` → `String`.
+pub(crate) fn owned_key(file: &SourceFile, key: &Type) -> (String, bool, bool) {
+    let Type::Reference(reference) = key else {
+        return (file.text_of(key).to_string(), false, false);
+    };
+    match &*reference.elem {
+        Type::Path(p) if p.path.is_ident("str") => ("String".to_string(), true, true),
+        Type::Slice(s) => (format!("Vec<{}>", file.text_of(&*s.elem)), true, false),
+        elem => (file.text_of(elem).to_string(), true, false),
+    }
+}
+
+fn describe(file: &SourceFile, found: &FoundDecl) -> Option<StorageDecl> {
+    let types = generic_types(&found.ty.path);
+    let (key, value) = match (found.kind, types.as_slice()) {
+        (StorageKind::Item, [value]) => (None, value),
+        (StorageKind::Map, [key, value]) => (Some(*key), value),
+        _ => return None,
+    };
+    let (key, suffixes) = match key {
+        Some(Type::Tuple(tuple)) => {
+            let mut elems: Vec<&Type> = tuple.elems.iter().collect();
+            let last = elems.pop()?;
+            let suffixes = elems.into_iter().map(|e| owned_key(file, e).0).collect();
+            (Some(last), suffixes)
+        }
+        key => (key, vec![]),
+    };
+    let (key, key_borrowed, key_is_str) = match key {
+        Some(key) => {
+            let (owned, borrowed, is_str) = owned_key(file, key);
+            (Some(owned), borrowed, is_str)
+        }
+        None => (None, false, false),
+    };
+    let namespace = match found.init {
+        Expr::Call(call) => match call.args.first() {
+            Some(Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s),
+                ..
+            })) => Some(s.value()),
+            _ => None,
+        },
+        _ => None,
+    };
+    Some(StorageDecl {
+        name: found.ident.to_string(),
+        kind: found.kind,
+        namespace,
+        key,
+        value: file.text_of(*value).to_string(),
+        module: file.module_path(),
+        file: file.path.clone(),
+        line: line_of(found.ident),
+        key_borrowed,
+        key_is_str,
+        suffixes,
+        indexed: None,
+    })
+}
+
+fn rewrite_declaration(
+    file: &SourceFile,
+    found: &FoundDecl,
+    keys: &BTreeMap<String, String>,
+    rec: &mut Recorder,
+) -> Option<StorageDecl> {
+    let line = line_of(found.ident);
+    let name = found.ident.to_string();
+
+    let Some(mut decl) = describe(file, found) else {
+        rec.skipped(
+            &file.path,
+            line,
+            format!("`{}` declaration", name),
+            "unrecognised generic arguments",
+        );
+        return None;
+    };
+    if let Some(suffix) = decl
+        .suffixes
+        .iter()
+        .find(|s| suffix_bytes(s, "key").is_none())
+    {
+        rec.skipped(
+            &file.path,
+            line,
+            format!("`{}` with composite key `{}`", name, decl_key(file, found)),
+            format!(
+                "`Keymap` has no composite keys, and a `{}` can't be nested with `add_suffix`",
+                suffix
+            ),
+        );
+        return None;
+    }
+
+    // Type: Map<&Addr, UserState> → Keymap<Addr, UserState>
+    let segments = &found.ty.path.segments;
+    let target = match decl.kind {
+        StorageKind::Item => "Item",
+        StorageKind::Map => "Keymap",
+    };
+    let last = segments.last().unwrap();
+    if segments.len() > 1 {
+        let range = range_of(&segments[0]).start..range_of(&last.ident).end;
+        rec.edit(
+            &file.path,
+            Edit::replace(range, format!("secret_toolkit::storage::{}", target)),
+        );
+    } else if last.ident == "Map" {
+        rec.edit(&file.path, Edit::replace(range_of(&last.ident), target));
+    }
+    if let (Some(key), [key_ty, _]) = (&decl.key, generic_types(&found.ty.path).as_slice()) {
+        if decl.key_borrowed || !decl.suffixes.is_empty() {
+            rec.edit(&file.path, Edit::replace(range_of(*key_ty), key.clone()));
+        }
+    }
+
+    // Constructor: Map::new("user_state") → Keymap::new(b"user_state")
+    let mut renamed = None;
+    if let Expr::Call(call) = found.init {
+        if let Expr::Path(func) = &*call.func {
+            let segments = &func.path.segments;
+            if segments.len() >= 2 {
+                let owner = &segments[segments.len() - 2];
+                if segments.len() > 2 {
+                    let range = range_of(&segments[0]).start..range_of(&owner.ident).end;
+                    rec.edit(
+                        &file.path,
+                        Edit::replace(range, format!("secret_toolkit::storage::{}", target)),
+                    );
+                } else if owner.ident == "Map" {
+                    rec.edit(&file.path, Edit::replace(range_of(&owner.ident), target));
+                }
+            }
+        }
+        match call.args.first() {
+            Some(Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s),
+                ..
+            })) if s.value().is_ascii() => match keys.get(&s.value()) {
+                Some(key) => {
+                    rec.edit(
+                        &file.path,
+                        Edit::replace(range_of(s), format!("b{:?}", key)),
+                    );
+                    renamed = decl.namespace.replace(key.clone());
+                }
+                None => rec.edit(&file.path, Edit::insert(range_of(s).start, "b")),
+            },
+            Some(arg) => {
+                rec.edit(&file.path, Edit::insert(range_of(arg).end, ".as_bytes()"));
+            }
+            None => {}
+        }
+    }
+
+    let migrated = match &decl.key {
+        Some(key) => format!("Keymap<{}, {}>", key, decl.value),
+        None => format!("Item<{}>", decl.value),
+    };
+    let mut description = if decl.suffixes.is_empty() {
+        format!(
+            "`{}` declared as `{}` with a byte-string namespace",
+            name, migrated
+        )
+    } else {
+        format!(
+            "`{}` with composite key `{}` declared as `{}`, nested by `{}` with `add_suffix`",
+            name,
+            decl_key(file, found),
+            migrated,
+            decl.suffixes.join("`, `")
+        )
+    };
+    if let Some(old) = renamed {
+        description += &format!(
+            ", namespace `{}` renamed to `{}`",
+            old,
+            decl.namespace.as_deref().unwrap_or_default()
+        );
+    }
+    rec.applied(&file.path, line, description);
+    Some(decl)
+}
+
+fn decl_key<'f>(file: &'f SourceFile, found: &FoundDecl) -> &'f str {
+    match generic_types(&found.ty.path).as_slice() {
+        [key, _] => file.text_of(*key),
+        _ => "",
+    }
+}
+
+/// `add_suffix` argument for a key component of type `ty`: `addr.as_bytes()`,
+/// `&id.to_be_bytes()`.
+pub(crate) fn suffix_bytes(ty: &str, value: &str) -> Option<String> {
+    match ty {
+        "Addr" | "String" | "str" => Some(format!("{}.as_bytes()", value)),
+        "Vec<u8>" => Some(format!("&{}", value)),
+        "u8" | "u16" | "u32" | "u64" | "u128" | "i8" | "i16" | "i32" | "i64" | "i128" => {
+            Some(format!("&{}.to_be_bytes()", value))
+        }
+        _ => None,
+    }
+}
+
+/// How the value of a storage call is consumed by the surrounding code.
+#[derive(Clone, Debug)]
+enum Context {
+    /// `X.load(..)?`
+    Try {
+        question: std::ops::Range<usize>,
+    },
+    /// `X.load(..).unwrap_or(..)`
+    Method {
+        name: String,
+        ident: std::ops::Range<usize>,
+    },
+    /// `X.remove(..);`
+    Statement,
+    Other,
+}
+
+struct CallSite<'ast> {
+    call: &'ast ExprMethodCall,
+    context: Context,
+    /// The method called on the result, e.g. the `take` in `X.range(..).take(n)`.
+    parent: Option<&'ast ExprMethodCall>,
+    /// The function the call is in.
+    func: Option<(&'ast syn::Signature, &'ast syn::Block)>,
+}
+
+trait ReceiverName {
+    fn receiver_name(&self) -> String;
+}
+
+impl ReceiverName for ExprMethodCall {
+    /// `USER_STATE`, or `tokens()` for a map an `IndexedMap` function builds.
+    fn receiver_name(&self) -> String {
+        let path = match &*self.receiver {
+            Expr::Path(p) => &p.path,
+            Expr::Call(call) if call.args.is_empty() => match &*call.func {
+                Expr::Path(p) => {
+                    return p
+                        .path
+                        .segments
+                        .last()
+                        .map(|s| format!("{}()", s.ident))
+                        .unwrap_or_default()
+                }
+                _ => return String::new(),
+            },
+            _ => return String::new(),
+        };
+        path.segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// `tokens()` → `TOKENS`, keeping a module path like `state::`.
+fn receiver_edit(file: &SourceFile, decl: &StorageDecl, call: &ExprMethodCall) -> Option<Edit> {
+    let Expr::Call(receiver) = &*call.receiver else {
+        return None;
+    };
+    Some(Edit::replace(
+        range_of(receiver),
+        format!("{}{}", module_prefix(file, call), decl.name),
+    ))
+}
+
+/// rustfmt's default `max_width`.
+const MAX_WIDTH: usize = 100;
+
+/// Byte range of the line holding `offset`, without its newline.
+fn line_range(text: &str, offset: usize) -> Range<usize> {
+    let start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let end = text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+    start..end
+}
+
+/// Path before the function in a `state::tokens()` receiver, e.g. `state::`.
+fn module_prefix<'f>(file: &'f SourceFile, call: &ExprMethodCall) -> &'f str {
+    match &*call.receiver {
+        Expr::Call(receiver) => match &*receiver.func {
+            Expr::Path(p) => {
+                let last = p.path.segments.last().unwrap();
+                &file.text[range_of(&p.path).start..range_of(last).start]
+            }
+            _ => "",
+        },
+        _ => "",
+    }
+}
+
+struct CallSites<'a, 'ast> {
+    decls: &'a HashMap<String, StorageDecl>,
+    contexts: HashMap<(usize, usize), Context>,
+    parents: HashMap<(usize, usize), &'ast ExprMethodCall>,
+    func: Option<(&'ast syn::Signature, &'ast syn::Block)>,
+    sites: Vec<CallSite<'ast>>,
+}
+
+impl<'a, 'ast> CallSites<'a, 'ast> {
+    fn storage_call<'e>(&self, expr: &'e Expr) -> Option<&'e ExprMethodCall> {
+        match expr {
+            Expr::MethodCall(call) if self.decls.contains_key(&call.receiver_name()) => Some(call),
+            _ => None,
+        }
+    }
+
+    fn set_context(&mut self, expr: &Expr, context: Context) {
+        if self.storage_call(expr).is_some() {
+            let range = range_of(expr);
+            self.contexts.insert((range.start, range.end), context);
+        }
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for CallSites<'a, 'ast> {
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        let outer = self.func.replace((&node.sig, &node.block));
+        visit::visit_item_fn(self, node);
+        self.func = outer;
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast syn::ImplItemFn) {
+        let outer = self.func.replace((&node.sig, &node.block));
+        visit::visit_impl_item_fn(self, node);
+        self.func = outer;
+    }
+
+    fn visit_stmt(&mut self, node: &'ast syn::Stmt) {
+        if let syn::Stmt::Expr(expr, Some(_)) = node {
+            self.set_context(expr, Context::Statement);
+        }
+        visit::visit_stmt(self, node);
+    }
+
+    fn visit_expr_try(&mut self, node: &'ast syn::ExprTry) {
+        let question = range_of(&node.question_token);
+        self.set_context(&node.expr, Context::Try { question });
+        visit::visit_expr_try(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        if self.decls.contains_key(&node.receiver_name()) {
+            let range = range_of(node);
+            let context = self
+                .contexts
+                .remove(&(range.start, range.end))
+                .unwrap_or(Context::Other);
+            self.sites.push(CallSite {
+                call: node,
+                context,
+                parent: self.parents.remove(&(range.start, range.end)),
+                func: self.func,
+            });
+        }
+        if self.storage_call(&node.receiver).is_some() {
+            let range = range_of(&*node.receiver);
+            self.parents.insert((range.start, range.end), node);
+        }
+        let context = Context::Method {
+            name: node.method.to_string(),
+            ident: range_of(&node.method),
+        };
+        self.set_context(&node.receiver, context);
+        visit::visit_expr_method_call(self, node);
+    }
+}
+
+fn rewrite_map_call(
+    file: &SourceFile,
+    decl: &StorageDecl,
+    site: &CallSite,
+    rec: &mut Recorder,
+) -> Option<Page> {
+    let call = site.call;
+    let method = call.method.to_string();
+    let line = line_of(call);
+    let what = format!("`{}.{}`", decl.name, method);
+    let arity = call.args.len();
+
+    let expected = match method.as_str() {
+        "load" | "may_load" | "has" | "remove" => 2,
+        "save" => 3,
+        "update" => {
+            rec.skipped(
+                &file.path,
+                line,
+                what,
+                "`Keymap` has no `update`; `get` the value, modify it and `insert` it back",
+            );
+            return None;
+        }
+        m if ORDERED_ITERATION.contains(&m) => return rewrite_iteration(file, decl, site, rec),
+        _ => {
+            rec.skipped(&file.path, line, what, "no `Keymap` equivalent");
+            return None;
+        }
+    };
+    if arity != expected {
+        rec.skipped(&file.path, line, what, "unexpected number of arguments");
+        return None;
+    }
+    if let (Some(function), "save" | "remove") = (&decl.indexed, method.as_str()) {
+        rewrite_indexed_write(file, decl, function, call, rec);
+        return None;
+    }
+
+    let mut edits = match key_edits(file, decl, call) {
+        Ok(edits) => edits,
+        Err(reason) => {
+            rec.skipped(&file.path, line, what, reason);
+            return None;
+        }
+    };
+    let rename = |to: &str| Edit::replace(range_of(&call.method), to);
+
+    let description = match (method.as_str(), &site.context) {
+        ("load", context) => {
+            edits.push(rename("get"));
+            match context {
+                Context::Method { name, .. } if UNWRAPPERS.contains(&name.as_str()) => {}
+                Context::Method { name, ident } if name == "is_ok" => {
+                    edits.push(Edit::replace(ident.clone(), "is_some"));
+                }
+                Context::Method { name, ident } if name == "is_err" => {
+                    edits.push(Edit::replace(ident.clone(), "is_none"));
+                }
+                _ => {
+                    let ok_or = format!(
+                        ".ok_or_else(|| cosmwasm_std::StdError::not_found(\"{}\"))",
+                        decl.value
+                    );
+                    // one chain element per line once it's too long, as rustfmt does
+                    let line = line_range(&file.text, range_of(call).start);
+                    let mut newline = String::new();
+                    if line.len() + ok_or.len() > MAX_WIDTH {
+                        let line = &file.text[line];
+                        let indent = &line[..line.len() - line.trim_start().len()];
+                        newline = format!("\n{}    ", indent);
+                        let dot = range_of(&call.dot_token).start;
+                        // e.g. the `.add_suffix(..)` of a tuple key
+                        for edit in &mut edits {
+                            if edit.range == (dot..dot) && edit.replacement.starts_with('.') {
+                                edit.replacement.insert_str(0, &newline);
+                            }
+                        }
+                        edits.push(Edit::insert(dot, newline.clone()));
+                    }
+                    edits.push(Edit::insert(
+                        range_of(call).end,
+                        format!("{}{}", newline, ok_or),
+                    ));
+                }
+            }
+            format!("{} → `{}.get`", what, decl.name)
+        }
+        ("may_load", Context::Try { question }) => {
+            edits.push(rename("get"));
+            edits.push(Edit::delete(question.clone()));
+            format!("{}? → `{}.get`", what, decl.name)
+        }
+        ("may_load", _) => {
+            rec.skipped(
+                &file.path,
+                line,
+                what,
+                "`Keymap::get` returns an `Option` rather than a `StdResult<Option<_>>`",
+            );
+            return None;
+        }
+        ("save", _) => {
+            edits.push(rename("insert"));
+            format!("{} → `{}.insert`", what, decl.name)
+        }
+        ("has", _) => {
+            edits.push(rename("contains"));
+            format!("{} → `{}.contains`", what, decl.name)
+        }
+        ("remove", context) => {
+            // cw-storage-plus' remove can't fail, Keymap's returns a StdResult
+            if matches!(context, Context::Statement) {
+                edits.push(Edit::insert(range_of(call).end, "?"));
+            }
+            format!("{} with the key passed by reference", what)
+        }
+        _ => unreachable!(),
+    };
+
+    edits.extend(receiver_edit(file, decl, call));
+    for edit in edits {
+        rec.edit(&file.path, edit);
+    }
+    rec.applied(&file.path, line, description);
+    None
+}
+
+/// `tokens().save(storage, key, &value)` → `save_tokens(storage, &key, &value)`,
+/// which updates the indexes too.
+fn rewrite_indexed_write(
+    file: &SourceFile,
+    decl: &StorageDecl,
+    function: &str,
+    call: &ExprMethodCall,
+    rec: &mut Recorder,
+) {
+    let helper = format!("{}_{}", call.method, function);
+    let key = &call.args[1];
+    // the generated functions take a `String` key as `&str`, like the `IndexedMap`
+    let key = match key_edit(file, decl, key) {
+        Some(edit) if !decl.key_is_str => edit.replacement,
+        _ => file.text_of(key).to_string(),
+    };
+    let mut args = vec![file.text_of(&call.args[0]).to_string(), key];
+    args.extend(
+        call.args
+            .iter()
+            .skip(2)
+            .map(|a| file.text_of(a).to_string()),
+    );
+    rec.edit(
+        &file.path,
+        Edit::replace(
+            range_of(call),
+            format!(
+                "{}{}({})",
+                module_prefix(file, call),
+                helper,
+                args.join(", ")
+            ),
+        ),
+    );
+    rec.applied(
+        &file.path,
+        line_of(call),
+        format!("`{}().{}` → `{}`", function, call.method, helper),
+    );
+}
+
+/// A paginated `range` turned into `Keymap::paging`, whose function now takes
+/// a page number.
+struct Page {
+    function: String,
+    method: bool,
+    /// The start-bound parameter replaced by `page`.
+    param: String,
+    /// Source the rewrite deleted or replaced.
+    removed: Vec<Range<usize>>,
+}
+
+/// `X.range(storage, None, None, order)` → `X.iter(storage)?`, and
+/// `X.range(storage, start, None, order).take(limit)` → `X.paging(storage, page, limit)?`.
+fn rewrite_iteration(
+    file: &SourceFile,
+    decl: &StorageDecl,
+    site: &CallSite,
+    rec: &mut Recorder,
+) -> Option<Page> {
+    let call = site.call;
+    let method = call.method.to_string();
+    let line = line_of(call);
+    let what = format!("`{}.{}`", decl.name, method);
+
+    let keys = match method.as_str() {
+        "range" | "keys" if !decl.suffixes.is_empty() => {
+            rec.skipped(
+                &file.path,
+                line,
+                what,
+                "keymaps nested with `add_suffix` can't be iterated together; iterate a `prefix`",
+            );
+            return None;
+        }
+        "prefix" if !decl.suffixes.is_empty() => {
+            rewrite_prefix_iteration(file, decl, site, rec);
+            return None;
+        }
+        "range" => false,
+        "keys" => true,
+        "range_raw" | "keys_raw" => {
+            rec.skipped(
+                &file.path,
+                line,
+                what,
+                "`Keymap` has no raw iteration; use `iter` or `paging` and serialize the keys",
+            );
+            return None;
+        }
+        _ => {
+            rec.skipped(
+                &file.path,
+                line,
+                what,
+                "`Keymap` has no prefixes; nest keymaps with `add_suffix`, or use `iter` or `paging`",
+            );
+            return None;
+        }
+    };
+    let args: Vec<&Expr> = call.args.iter().collect();
+    let [storage, min, max, order] = args[..] else {
+        rec.skipped(&file.path, line, what, "unexpected number of arguments");
+        return None;
+    };
+    if !is_none(max) {
+        rec.skipped(
+            &file.path,
+            line,
+            what,
+            "`Keymap` can't bound iteration; use `iter` and filter the entries",
+        );
+        return None;
+    }
+    let Some(descending) = literal_order(order) else {
+        rec.skipped(&file.path, line, what, RUNTIME_ORDER);
+        return None;
+    };
+    if !returns_result(site) {
+        rec.skipped(&file.path, line, what, NO_RESULT);
+        return None;
+    }
+    let storage = file.text_of(storage);
+
+    if is_none(min) {
+        let iter = if keys { "iter_keys" } else { "iter" };
+        let rev = if descending { ".rev()" } else { "" };
+        rec.edit(
+            &file.path,
+            Edit::replace(
+                range_of(&call.method).start..range_of(call).end,
+                format!("{}({})?{}", iter, storage, rev),
+            ),
+        );
+        if let Some(edit) = receiver_edit(file, decl, call) {
+            rec.edit(&file.path, edit);
+        }
+        rec.warned(
+            &file.path,
+            line,
+            format!("{} → `{}.{}`", what, decl.name, iter),
+            INSERTION_ORDER,
+        );
+        return None;
+    }
+
+    let Some(take) = site
+        .parent
+        .filter(|p| p.method == "take" && p.args.len() == 1)
+    else {
+        rec.skipped(
+            &file.path,
+            line,
+            what,
+            "`Keymap` pages by index rather than from a key; limit the entries with `take` so it can become `paging`",
+        );
+        return None;
+    };
+    let Some(bound) = site
+        .func
+        .and_then(|(sig, block)| start_bound(sig, block, min))
+    else {
+        rec.skipped(
+            &file.path,
+            line,
+            what,
+            "the start bound doesn't come from an `Option` parameter; use `paging` with a page number",
+        );
+        return None;
+    };
+    let (sig, _) = site.func.unwrap();
+
+    let size = match &take.args[0] {
+        Expr::Lit(lit) => file.text_of(lit).to_string(),
+        arg => format!("{} as u32", operand(file, arg)),
+    };
+    let paging = if keys { "paging_keys" } else { "paging" };
+    let chain =
+        file.text[range_of(&*call.receiver).end..range_of(&call.method).start].contains('\n');
+    let separator = if chain {
+        format!(
+            "\n{}",
+            indentation(&file.text, range_of(&take.method).start)
+        )
+    } else {
+        String::new()
+    };
+    let replaced = range_of(&call.method).start..range_of(take).end;
+    rec.edit(
+        &file.path,
+        Edit::replace(
+            replaced.clone(),
+            format!(
+                "{}({}, page.unwrap_or(0), {})?{}.into_iter(){}.map(Ok)",
+                paging, storage, size, separator, separator
+            ),
+        ),
+    );
+    if let Some(edit) = receiver_edit(file, decl, call) {
+        rec.edit(&file.path, edit);
+    }
+    let param = match &*bound.param.pat {
+        Pat::Ident(i) => i.ident.to_string(),
+        _ => unreachable!(),
+    };
+    rec.edit(
+        &file.path,
+        Edit::replace(range_of(bound.param), "page: Option<u32>"),
+    );
+    let mut removed = vec![replaced];
+    for local in &bound.locals {
+        let lines = whole_lines(&file.text, range_of(*local));
+        rec.edit(&file.path, Edit::delete(lines.clone()));
+        removed.push(lines);
+    }
+
+    let mut warning = format!(
+        "pages count entries in `Keymap`'s insertion order rather than starting after a key, so `{}` is a page number now; removals move the last entry into the gap, so entries can be skipped or repeated while the map changes",
+        param
+    );
+    if descending {
+        warning.push_str("; descending order is lost");
+    }
+    rec.warned(
+        &file.path,
+        line,
+        format!("{} from `{}` → `{}.{}`", what, param, decl.name, paging),
+        warning,
+    );
+    Some(Page {
+        function: sig.ident.to_string(),
+        method: sig.receiver().is_some(),
+        param,
+        removed,
+    })
+}
+
+/// `X.prefix(addr).range(storage, None, None, order)` →
+/// `X.add_suffix(addr.as_bytes()).iter(storage)?` on a tuple-keyed map.
+fn rewrite_prefix_iteration(
+    file: &SourceFile,
+    decl: &StorageDecl,
+    site: &CallSite,
+    rec: &mut Recorder,
+) {
+    let call = site.call;
+    let line = line_of(call);
+    let what = format!("`{}.prefix`", decl.name);
+
+    let prefix: Vec<&Expr> = match call.args.first() {
+        Some(Expr::Tuple(tuple)) if decl.suffixes.len() > 1 => tuple.elems.iter().collect(),
+        Some(arg) if call.args.len() == 1 => vec![arg],
+        _ => vec![],
+    };
+    if prefix.len() != decl.suffixes.len() {
+        rec.skipped(
+            &file.path,
+            line,
+            what,
+            "only a prefix naming every nesting `add_suffix` can be rewritten",
+        );
+        return;
+    }
+    let iteration = site.parent.and_then(|parent| {
+        let keys = match parent.method.to_string().as_str() {
+            "range" => false,
+            "keys" => true,
+            _ => return None,
+        };
+        match parent.args.iter().collect::<Vec<_>>()[..] {
+            [storage, min, max, order] if is_none(min) && is_none(max) => {
+                Some((parent, keys, storage, order))
+            }
+            _ => None,
+        }
+    });
+    let Some((parent, keys, storage, order)) = iteration else {
+        rec.skipped(
+            &file.path,
+            line,
+            what,
+            "only `range` and `keys` over a whole prefix can be rewritten; use `iter` or `paging` on the nested keymap",
+        );
+        return;
+    };
+
+    let Some(descending) = literal_order(order) else {
+        rec.skipped(&file.path, line, what, RUNTIME_ORDER);
+        return;
+    };
+    if !returns_result(site) {
+        rec.skipped(&file.path, line, what, NO_RESULT);
+        return;
+    }
+
+    let iter = if keys { "iter_keys" } else { "iter" };
+    let rev = if descending { ".rev()" } else { "" };
+    rec.edit(
+        &file.path,
+        Edit::replace(
+            range_of(&call.method).start..range_of(call).end,
+            add_suffixes(file, decl, &prefix)
+                .strip_prefix('.')
+                .unwrap()
+                .to_string(),
+        ),
+    );
+    rec.edit(
+        &file.path,
+        Edit::replace(
+            range_of(&parent.method).start..range_of(parent).end,
+            format!("{}({})?{}", iter, file.text_of(storage), rev),
+        ),
+    );
+    rec.warned(
+        &file.path,
+        line,
+        format!(
+            "`{}.prefix(..).{}` → `{}.add_suffix(..).{}`",
+            decl.name, parent.method, decl.name, iter
+        ),
+        INSERTION_ORDER,
+    );
+}
+
+const RUNTIME_ORDER: &str = "the `Order` is only known at runtime and `Keymap` iterates in \
+     insertion order; pick `iter` or `iter(..)?.rev()` by hand";
+
+const NO_RESULT: &str = "`Keymap` iteration returns a `StdResult`, and the enclosing function \
+     doesn't return a `Result` to propagate it with `?`";
+
+/// Whether `order` is `Order::Descending`; `None` unless it's spelled out.
+fn literal_order(order: &Expr) -> Option<bool> {
+    let Expr::Path(path) = order else {
+        return None;
+    };
+    match path.path.segments.last()?.ident.to_string().as_str() {
+        "Ascending" => Some(false),
+        "Descending" => Some(true),
+        _ => None,
+    }
+}
+
+/// Whether the function around a call returns a `Result`, e.g. `StdResult<_>`.
+fn returns_result(site: &CallSite) -> bool {
+    let Some((sig, _)) = site.func else {
+        return false;
+    };
+    matches!(&sig.output, syn::ReturnType::Type(_, ty)
+        if matches!(&**ty, syn::Type::Path(t)
+            if t.path.segments.last().is_some_and(|s| s.ident.to_string().ends_with("Result"))))
+}
+
+pub(crate) fn is_none(expr: &Expr) -> bool {
+    matches!(expr, Expr::Path(p) if p.path.is_ident("None"))
+}
+
+/// Where a range's start bound comes from: an `Option` parameter, through
+/// locals used for nothing else.
+struct StartBound<'ast> {
+    param: &'ast syn::PatType,
+    locals: Vec<&'ast syn::Local>,
+}
+
+fn start_bound<'ast>(
+    sig: &'ast syn::Signature,
+    block: &'ast syn::Block,
+    min: &Expr,
+) -> Option<StartBound<'ast>> {
+    let mut names = idents(min);
+    let mut locals: Vec<&syn::Local> = vec![];
+    loop {
+        let more: Vec<_> = block
+            .stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                syn::Stmt::Local(local) => Some(local),
+                _ => None,
+            })
+            .filter(|local| {
+                local_name(local).is_some_and(|n| names.contains(&n))
+                    && !locals.iter().any(|l| std::ptr::eq(*l, *local))
+            })
+            .collect();
+        if more.is_empty() {
+            break;
+        }
+        for local in more {
+            if let Some(init) = &local.init {
+                names.extend(idents(&init.expr));
+            }
+            locals.push(local);
+        }
+    }
+
+    let params: Vec<&syn::PatType> = sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::FnArg::Typed(p) => Some(p),
+            syn::FnArg::Receiver(_) => None,
+        })
+        .filter(|p| {
+            matches!(&*p.pat, Pat::Ident(i) if names.contains(&i.ident.to_string()))
+                && matches!(&*p.ty, Type::Path(t)
+                    if t.path.segments.last().is_some_and(|s| s.ident == "Option"))
+        })
+        .collect();
+    let [param] = params[..] else {
+        return None;
+    };
+
+    // the locals and the parameter mustn't be used anywhere else
+    let mut used = vec![];
+    for local in &locals {
+        used.push(local_name(local)?);
+    }
+    if let Pat::Ident(i) = &*param.pat {
+        used.push(i.ident.to_string());
+    }
+    for name in &used {
+        let within =
+            count_ident(min, name) + locals.iter().map(|l| count_ident(*l, name)).sum::<usize>();
+        if count_ident(block, name) != within {
+            return None;
+        }
+    }
+    Some(StartBound { param, locals })
+}
+
+fn local_name(local: &syn::Local) -> Option<String> {
+    match &local.pat {
+        Pat::Ident(i) => Some(i.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn idents(expr: &Expr) -> HashSet<String> {
+    struct Idents(HashSet<String>);
+    impl<'ast> Visit<'ast> for Idents {
+        fn visit_ident(&mut self, ident: &'ast syn::Ident) {
+            self.0.insert(ident.to_string());
+        }
+    }
+    let mut idents = Idents(HashSet::new());
+    idents.visit_expr(expr);
+    idents.0
+}
+
+fn count_ident<T: quote::ToTokens>(node: &T, name: &str) -> usize {
+    fn count(tokens: proc_macro2::TokenStream, name: &str) -> usize {
+        tokens
+            .into_iter()
+            .map(|token| match token {
+                proc_macro2::TokenTree::Ident(i) => usize::from(i == name),
+                proc_macro2::TokenTree::Group(g) => count(g.stream(), name),
+                _ => 0,
+            })
+            .sum()
+    }
+    count(node.to_token_stream(), name)
+}
+
+/// Local names of `cw_storage_plus::Bound` whose every use is in `removed`.
+fn unused_bounds(file: &SourceFile, removed: &[Range<usize>]) -> HashSet<String> {
+    if removed.is_empty() {
+        return HashSet::new();
+    }
+    struct Uses<'n> {
+        name: &'n str,
+        at: Vec<usize>,
+    }
+    impl<'n, 'ast> Visit<'ast> for Uses<'n> {
+        fn visit_item_use(&mut self, _: &'ast syn::ItemUse) {}
+
+        fn visit_ident(&mut self, ident: &'ast syn::Ident) {
+            if ident == self.name {
+                self.at.push(range_of(ident).start);
+            }
+        }
+    }
+    storage_imports(&file.ast)
+        .into_iter()
+        .filter(|(_, name)| name == "Bound")
+        .map(|(local, _)| local)
+        .filter(|local| {
+            let mut uses = Uses {
+                name: local,
+                at: vec![],
+            };
+            uses.visit_file(&file.ast);
+            uses.at
+                .iter()
+                .all(|at| removed.iter().any(|r| r.contains(at)))
+        })
+        .collect()
+}
+
+fn rewrite_item_call(
+    file: &SourceFile,
+    names: &HashSet<String>,
+    decl: &StorageDecl,
+    site: &CallSite,
+    rec: &mut Recorder,
+) {
+    let call = site.call;
+    let method = call.method.to_string();
+    let line = line_of(call);
+    let what = format!("`{}.{}`", decl.name, method);
+
+    match method.as_str() {
+        "load" | "may_load" | "save" | "remove" => {}
+        "exists" => {
+            rec.edit(&file.path, Edit::insert(range_of(call).start, "!"));
+            rec.edit(
+                &file.path,
+                Edit::replace(range_of(&call.method), "is_empty"),
+            );
+            rec.applied(
+                &file.path,
+                line,
+                format!("{} → `!{}.is_empty`", what, decl.name),
+            );
+        }
+        "update" => match call.args.iter().nth(1) {
+            Some(Expr::Closure(closure)) => {
+                rewrite_update_closure(file, names, decl, closure, rec);
+                rec.applied(
+                    &file.path,
+                    line,
+                    format!(
+                        "{} closure typed as `FnOnce({}) -> StdResult<_>`",
+                        what, decl.value
+                    ),
+                );
+            }
+            _ => rec.skipped(
+                &file.path,
+                line,
+                what,
+                "the update function must return a `StdResult`",
+            ),
+        },
+        _ => rec.skipped(
+            &file.path,
+            line,
+            what,
+            "no `secret_toolkit` `Item` equivalent",
+        ),
+    }
+}
+
+/// `|mut state| -> Result<_, ContractError> { .. }` → `|mut state: State| { .. }`
+///
+/// `secret_toolkit`'s `Item::update` takes an `FnOnce(T) -> StdResult<T>`, so the
+/// explicit error type goes and the argument type has to be spelled out.
+fn rewrite_update_closure(
+    file: &SourceFile,
+    names: &HashSet<String>,
+    decl: &StorageDecl,
+    closure: &ExprClosure,
+    rec: &mut Recorder,
+) {
+    let value = if names.contains(&decl.value)
+        || file.module_path() == decl.module
+        || !is_ident(&decl.value)
+    {
+        decl.value.clone()
+    } else {
+        format!("{}::{}", decl.module, decl.value)
+    };
+    for input in &closure.inputs {
+        if let Pat::Ident(pat) = input {
+            rec.edit(
+                &file.path,
+                Edit::insert(range_of(pat).end, format!(": {}", value)),
+            );
+        }
+    }
+    if let syn::ReturnType::Type(arrow, ty) = &closure.output {
+        let range = range_of(arrow).start..range_of(&**ty).end;
+        rec.edit(
+            &file.path,
+            Edit::delete(with_leading_whitespace(&file.text, range)),
+        );
+    }
+
+    let body = quote::ToTokens::to_token_stream(&closure.body).to_string();
+    if body.contains("ContractError") {
+        rec.skipped(
+            &file.path,
+            line_of(closure),
+            format!("`{}.update` closure returning a custom error", decl.name),
+            "the closure must return `StdResult`; map errors before returning",
+        );
+    }
+}
+
+fn is_ident(ty: &str) -> bool {
+    !ty.is_empty() && ty.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Edits passing `call`'s key the way `Keymap` takes it. A tuple key is split:
+/// `X.load(storage, (&addr, id))` → `X.add_suffix(addr.as_bytes()).get(storage, &id)`.
+fn key_edits(
+    file: &SourceFile,
+    decl: &StorageDecl,
+    call: &ExprMethodCall,
+) -> Result<Vec<Edit>, &'static str> {
+    let key = &call.args[1];
+    if decl.suffixes.is_empty() {
+        return Ok(key_edit(file, decl, key).into_iter().collect());
+    }
+    let Expr::Tuple(tuple) = key else {
+        return Err(
+            "pass the key as a tuple expression so it can be split into `add_suffix` calls",
+        );
+    };
+    let components: Vec<&Expr> = tuple.elems.iter().collect();
+    let Some((last, prefix)) = components.split_last() else {
+        return Err("unexpected number of key components");
+    };
+    if prefix.len() != decl.suffixes.len() {
+        return Err("unexpected number of key components");
+    }
+    let last = match key_edit(file, decl, last) {
+        Some(edit) => edit.replacement,
+        None => file.text_of(*last).to_string(),
+    };
+    Ok(vec![
+        Edit::insert(
+            range_of(&*call.receiver).end,
+            add_suffixes(file, decl, prefix),
+        ),
+        Edit::replace(range_of(key), last),
+    ])
+}
+
+/// `.add_suffix(..)` for each of `components`, the leading parts of a tuple key.
+fn add_suffixes(file: &SourceFile, decl: &StorageDecl, components: &[&Expr]) -> String {
+    decl.suffixes
+        .iter()
+        .zip(components)
+        .map(|(ty, component)| {
+            let value = match without_borrow(component) {
+                // `5.to_be_bytes()` doesn't say how wide the integer is
+                Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(int),
+                    ..
+                }) if int.suffix().is_empty() => format!("{}{}", int.base10_digits(), ty),
+                component => operand(file, component),
+            };
+            format!(".add_suffix({})", suffix_bytes(ty, &value).unwrap())
+        })
+        .collect()
+}
+
+/// `&addr` and `addr.clone()` → `addr`.
+pub(crate) fn without_borrow(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Reference(r) => without_borrow(&r.expr),
+        Expr::MethodCall(m) if m.method == "clone" && m.args.is_empty() => &m.receiver,
+        e => e,
+    }
+}
+
+/// `Keymap` takes keys by reference: `sender.clone()` → `&sender`, `addr` → `&addr`.
+fn key_edit(file: &SourceFile, decl: &StorageDecl, arg: &Expr) -> Option<Edit> {
+    let range = range_of(arg);
+    if decl.key_is_str {
+        let inner = match arg {
+            Expr::Reference(r) => &*r.expr,
+            e => e,
+        };
+        return Some(Edit::replace(
+            range,
+            format!("&{}.to_string()", operand(file, inner)),
+        ));
+    }
+    if decl.key_borrowed {
+        return None;
+    }
+    match arg {
+        Expr::Reference(_) => None,
+        Expr::MethodCall(m) if m.method == "clone" && m.args.is_empty() => Some(Edit::replace(
+            range,
+            format!("&{}", operand(file, &m.receiver)),
+        )),
+        e => Some(Edit::replace(range, format!("&{}", operand(file, e)))),
+    }
+}
+
+/// Source of `expr`, parenthesised unless it binds tighter than a prefix operator.
+pub(crate) fn operand(file: &SourceFile, expr: &Expr) -> String {
+    let text = file.text_of(expr);
+    match expr {
+        Expr::Path(_)
+        | Expr::Field(_)
+        | Expr::MethodCall(_)
+        | Expr::Call(_)
+        | Expr::Lit(_)
+        | Expr::Index(_)
+        | Expr::Macro(_)
+        | Expr::Paren(_) => text.to_string(),
+        _ => format!("({})", text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::Status;
+    use std::path::Path;
+
+    fn contract(name: &str) -> CrateSources {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../contracts")
+            .join(name);
+        CrateSources::load(root).unwrap()
+    }
+
+    fn tokens(text: &str) -> String {
+        let file = syn::parse_file(text).unwrap();
+        quote::ToTokens::to_token_stream(&file).to_string()
+    }
+
+    fn function(text: &str, module: &str, name: &str) -> String {
+        let file = syn::parse_file(text).unwrap();
+        let items = file
+            .items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Mod(m) if m.ident == module => m.content.as_ref().map(|c| &c.1),
+                _ => None,
+            })
+            .unwrap();
+        items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Fn(f) if f.sig.ident == name => {
+                    Some(quote::ToTokens::to_token_stream(f).to_string())
+                }
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn cw_counter_state_matches_secret_counter() {
+        let migration = migrate_storage(&contract("cw-counter"));
+        let secret = contract("secret-counter");
+
+        let state = &migration.files[Path::new("src/state.rs")];
+        let expected = &secret.file("src/state.rs").unwrap().text;
+        assert_eq!(tokens(expected), tokens(state));
+    }
+
+    #[test]
+    fn cw_counter_increment_matches_secret_counter() {
+        let migration = migrate_storage(&contract("cw-counter"));
+        let secret = contract("secret-counter");
+
+        let migrated = &migration.files[Path::new("src/contract.rs")];
+        let expected = &secret.file("src/contract.rs").unwrap().text;
+        assert_eq!(
+            function(expected, "execute", "increment"),
+            function(migrated, "execute", "increment")
+        );
+    }
+
+    #[test]
+    fn propagated_load_keeps_its_error() {
+        let migration = migrate_storage(&contract("cw-counter"));
+        let migrated = &migration.files[Path::new("src/contract.rs")];
+        assert!(migrated.contains(
+            "        let user_state = USER_STATE\n            .get(deps.storage, &addr)\n            .ok_or_else(|| cosmwasm_std::StdError::not_found(\"UserState\"))?;\n"
+        ));
+        assert!(migration.skipped().next().is_none());
+    }
+
+    #[test]
+    fn borrowed_keys_and_unsupported_calls() {
+        let krate = CrateSources::from_files(
+            "",
+            [(
+                "src/lib.rs",
+                r#"
+use cw_storage_plus::{Bound, Map};
+
+pub const NAMES: Map<&str, u64> = Map::new("names");
+pub const OWNERS: Map<&Addr, u64> = Map::new("owners");
+
+fn run(deps: DepsMut, owner: &Addr) -> StdResult<()> {
+    let n = NAMES.may_load(deps.storage, "alice")?;
+    if !OWNERS.has(deps.storage, owner) {
+        OWNERS.remove(deps.storage, owner);
+    }
+    let all: Vec<_> = OWNERS.range(deps.storage, None, None, Order::Ascending).collect();
+    Ok(())
+}
+"#,
+            )],
+        )
+        .unwrap();
+        let migration = migrate_storage(&krate);
+        let lib = &migration.files[Path::new("src/lib.rs")];
+
+        assert!(lib.contains("use secret_toolkit::storage::Keymap;\nuse cw_storage_plus::Bound;"));
+        assert!(lib.contains("NAMES: Keymap<String, u64> = Keymap::new(b\"names\")"));
+        assert!(lib.contains("OWNERS: Keymap<Addr, u64>"));
+        assert!(lib.contains("let n = NAMES.get(deps.storage, &\"alice\".to_string());"));
+        assert!(lib.contains("!OWNERS.contains(deps.storage, owner)"));
+        assert!(lib.contains("OWNERS.remove(deps.storage, owner)?;"));
+
+        assert!(lib.contains("let all: Vec<_> = OWNERS.iter(deps.storage)?.collect();"));
+
+        let skipped: Vec<_> = migration.skipped().map(|t| t.line).collect();
+        assert_eq!(vec![2], skipped);
+        let iter = migration.warnings().next().unwrap();
+        assert_eq!(12, iter.line);
+        assert!(matches!(
+            &iter.status,
+            Status::Warned { warning } if warning.contains("insertion order")
+        ));
+    }
+
+    #[test]
+    fn iteration_needs_a_known_order_and_a_result() {
+        let krate = CrateSources::from_files(
+            "",
+            [(
+                "src/lib.rs",
+                r#"
+use cw_storage_plus::Map;
+
+pub const OWNERS: Map<&Addr, u64> = Map::new("owners");
+
+fn keys(deps: Deps) -> Vec<Addr> {
+    OWNERS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()
+        .unwrap()
+}
+
+fn list(deps: Deps, order: Order) -> StdResult<Vec<(Addr, u64)>> {
+    OWNERS.range(deps.storage, None, None, order).collect()
+}
+
+fn newest(deps: Deps) -> StdResult<Vec<(Addr, u64)>> {
+    OWNERS.range(deps.storage, None, None, Order::Descending).collect()
+}
+"#,
+            )],
+        )
+        .unwrap();
+        let migration = migrate_storage(&krate);
+        let lib = &migration.files[Path::new("src/lib.rs")];
+
+        assert!(lib.contains("OWNERS.iter(deps.storage)?.rev().collect()"));
+        let skipped: Vec<_> = migration
+            .transformations
+            .iter()
+            .filter_map(|t| match &t.status {
+                Status::Skipped { reason } => Some((t.line, reason.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![(7, NO_RESULT), (14, RUNTIME_ORDER)], skipped);
+    }
+
+    #[test]
+    fn tuple_keys_nest_keymaps() {
+        let krate = CrateSources::from_files(
+            "",
+            [(
+                "src/lib.rs",
+                r#"
+use cw_storage_plus::Map;
+
+pub const ALLOWANCES: Map<(&Addr, &Addr), u128> = Map::new("allowances");
+pub const BIDS: Map<(u64, &Addr), Uint128> = Map::new("bids");
+pub const LABELS: Map<(&Addr, u64, &str), bool> = Map::new("labels");
+
+fn run(deps: DepsMut, owner: &Addr, spender: Addr, amount: u128) -> StdResult<()> {
+    ALLOWANCES.save(deps.storage, (owner, &spender), &amount)?;
+    let bid = BIDS.load(deps.storage, (7, owner))?;
+    LABELS.save(deps.storage, (owner, 5, "alice"), &true)?;
+    let key = (owner, &spender);
+    ALLOWANCES.remove(deps.storage, key);
+    let spenders: Vec<_> = ALLOWANCES
+        .prefix(owner)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect();
+    let all: Vec<_> = BIDS.range(deps.storage, None, None, Order::Ascending).collect();
+    Ok(())
+}
+"#,
+            )],
+        )
+        .unwrap();
+        let migration = migrate_storage(&krate);
+        let lib = &migration.files[Path::new("src/lib.rs")];
+
+        assert!(lib.contains("ALLOWANCES: Keymap<Addr, u128> = Keymap::new(b\"allowances\")"));
+        assert!(lib.contains("BIDS: Keymap<Addr, Uint128> = Keymap::new(b\"bids\")"));
+        assert!(lib.contains(
+            "ALLOWANCES.add_suffix(owner.as_bytes()).insert(deps.storage, &spender, &amount)?;"
+        ));
+        assert!(lib.contains(
+            "    let bid = BIDS\n        .add_suffix(&7u64.to_be_bytes())\n        .get(deps.storage, owner)\n        .ok_or_else("
+        ));
+        assert!(lib.contains(
+            "LABELS.add_suffix(owner.as_bytes()).add_suffix(&5u64.to_be_bytes()).insert("
+        ));
+        assert!(lib.contains(
+            "ALLOWANCES\n        .add_suffix(owner.as_bytes())\n        .iter(deps.storage)?\n"
+        ));
+
+        let skipped: Vec<_> = migration.skipped().map(|t| t.line).collect();
+        assert_eq!(vec![13, 18], skipped);
+        assert_eq!(
+            vec![14],
+            migration.warnings().map(|t| t.line).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn range_pagination_becomes_keymap_paging() {
+        let cw = contract("cw-counter");
+        let contract = cw
+            .file("src/contract.rs")
+            .unwrap()
+            .text
+            .replace(
+                "user_count(deps, addr)?),\n",
+                "user_count(deps, addr)?),\n        \
+                 QueryMsg::ListUsers { start_after, limit } => {\n            \
+                 to_binary(&query::list_users(deps, start_after, limit)?)\n        }\n",
+            )
+            .replace(
+                "    use cosmwasm_std::Addr;\n",
+                "    use cosmwasm_std::{Addr, Order};\n    use cw_storage_plus::Bound;\n",
+            )
+            .replace(
+                "    }\n}\n\n#[cfg(test)]",
+                r#"    }
+
+    pub fn list_users(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<(Addr, i32)>> {
+        let limit = limit.unwrap_or(10) as usize;
+        let start = start_after
+            .map(|addr| deps.api.addr_validate(&addr))
+            .transpose()?;
+        USER_STATE
+            .range(deps.storage, start.map(Bound::exclusive), None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(addr, user)| (addr, user.count)))
+            .collect()
+    }
+}
+
+#[cfg(test)]"#,
+            );
+        let krate = CrateSources::from_files(
+            "",
+            [
+                ("src/contract.rs", contract.as_str()),
+                ("src/state.rs", &cw.file("src/state.rs").unwrap().text),
+            ],
+        )
+        .unwrap();
+        let migration = migrate_storage(&krate);
+        let contract = &migration.files[Path::new("src/contract.rs")];
+
+        assert!(contract.contains("    use cosmwasm_std::{Addr, Order};\n\n    use crate::"));
+        assert!(contract.contains(
+            "        limit: Option<u32>,\n    ) -> StdResult<Vec<(Addr, i32)>> {\n        \
+             let limit = limit.unwrap_or(10) as usize;\n        USER_STATE\n            \
+             .paging(deps.storage, page.unwrap_or(0), limit as u32)?\n            \
+             .into_iter()\n            .map(Ok)\n            .map(|item|"
+        ));
+        assert!(contract.contains("deps: Deps,\n        page: Option<u32>,\n"));
+
+        let warned: Vec<_> = migration.warnings().map(|t| t.line).collect();
+        assert_eq!(vec![107], warned);
+        let skipped: Vec<_> = migration
+            .skipped()
+            .map(|t| (t.file.to_str().unwrap(), t.line))
+            .collect();
+        assert_eq!(vec![("src/contract.rs", 73)], skipped);
+        assert!(matches!(
+            &migration.transformations.last().unwrap().status,
+            Status::Skipped { reason } if reason.contains("`list_users` takes a page number instead of `start_after`")
+        ));
+    }
+}
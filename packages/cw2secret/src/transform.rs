@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::manifest::Manifest;
+use crate::source::{apply_edits, CrateSources, Edit};
+
+/// Outcome of a single transformation attempted by the migration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Status {
+    Applied,
+    /// Applied, but the code behaves differently afterwards; `warning` says how.
+    Warned {
+        warning: String,
+    },
+    /// The site needs a manual change; `reason` says what to do.
+    Skipped {
+        reason: String,
+    },
+}
+
+/// One change (or refusal to change) made by a migration rule.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Transformation {
+    /// Name of the rule that produced this entry, e.g. `storage`.
+    pub rule: String,
+    pub file: PathBuf,
+    pub line: usize,
+    pub description: String,
+    #[serde(flatten)]
+    pub status: Status,
+}
+
+impl fmt::Display for Transformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: [{}] {}",
+            self.file.display(),
+            self.line,
+            self.rule,
+            self.description
+        )?;
+        match &self.status {
+            Status::Applied => {}
+            Status::Warned { warning } => write!(f, " (warning: {})", warning)?,
+            Status::Skipped { reason } => write!(f, " (skipped: {})", reason)?,
+        }
+        Ok(())
+    }
+}
+
+/// The result of running a migration rule over a crate.
+#[derive(Debug, Default)]
+pub struct Migration {
+    /// New contents of every file the rule changed, keyed by relative path.
+    pub files: BTreeMap<PathBuf, String>,
+    /// Files the rule deleted.
+    pub removed: BTreeSet<PathBuf>,
+    pub transformations: Vec<Transformation>,
+}
+
+impl Migration {
+    /// Changes made, including those with a warning.
+    pub fn applied(&self) -> impl Iterator<Item = &Transformation> {
+        self.transformations
+            .iter()
+            .filter(|t| !matches!(t.status, Status::Skipped { .. }))
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Transformation> {
+        self.transformations
+            .iter()
+            .filter(|t| matches!(t.status, Status::Warned { .. }))
+    }
+
+    pub fn skipped(&self) -> impl Iterator<Item = &Transformation> {
+        self.transformations
+            .iter()
+            .filter(|t| matches!(t.status, Status::Skipped { .. }))
+    }
+}
+
+/// Collects edits and report entries for a single rule while it walks a crate.
+pub struct Recorder {
+    rule: &'static str,
+    edits: BTreeMap<PathBuf, Vec<Edit>>,
+    transformations: Vec<Transformation>,
+}
+
+impl Recorder {
+    pub fn new(rule: &'static str) -> Self {
+        Recorder {
+            rule,
+            edits: BTreeMap::new(),
+            transformations: vec![],
+        }
+    }
+
+    pub fn edit(&mut self, file: &Path, edit: Edit) {
+        self.edits.entry(file.to_path_buf()).or_default().push(edit);
+    }
+
+    pub fn applied(&mut self, file: &Path, line: usize, description: impl Into<String>) {
+        self.record(file, line, description, Status::Applied);
+    }
+
+    pub fn warned(
+        &mut self,
+        file: &Path,
+        line: usize,
+        description: impl Into<String>,
+        warning: impl Into<String>,
+    ) {
+        let status = Status::Warned {
+            warning: warning.into(),
+        };
+        self.record(file, line, description, status);
+    }
+
+    pub fn skipped(
+        &mut self,
+        file: &Path,
+        line: usize,
+        description: impl Into<String>,
+        reason: impl Into<String>,
+    ) {
+        let status = Status::Skipped {
+            reason: reason.into(),
+        };
+        self.record(file, line, description, status);
+    }
+
+    fn record(&mut self, file: &Path, line: usize, description: impl Into<String>, status: Status) {
+        self.transformations.push(Transformation {
+            rule: self.rule.to_string(),
+            file: file.to_path_buf(),
+            line,
+            description: description.into(),
+            status,
+        });
+    }
+
+    pub fn finish(self, krate: &CrateSources) -> Migration {
+        self.finish_with(krate, None)
+    }
+
+    /// Like [`Recorder::finish`], for rules that also edit `Cargo.toml`.
+    pub fn finish_with_manifest(self, krate: &CrateSources, manifest: &Manifest) -> Migration {
+        self.finish_with(krate, Some(manifest))
+    }
+
+    fn finish_with(self, krate: &CrateSources, manifest: Option<&Manifest>) -> Migration {
+        let mut files = BTreeMap::new();
+        for (path, edits) in self.edits {
+            let text = match (krate.file(&path), manifest) {
+                (Some(file), _) => &file.text,
+                (None, Some(manifest)) if path == manifest.path => &manifest.text,
+                _ => continue,
+            };
+            let rewritten = apply_edits(text, edits);
+            if rewritten != *text {
+                files.insert(path, rewritten);
+            }
+        }
+        Migration {
+            files,
+            removed: BTreeSet::new(),
+            transformations: self.transformations,
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Which Secret forks and `secret-toolkit` release stand in for an upstream
+//! CosmWasm version, as listed in `versions.toml`.
+
+use std::collections::BTreeMap;
+
+use toml_edit::{DocumentMut, InlineTable, Item};
+
+use crate::error::MigrateError;
+
+const BUILTIN: &str = include_str!("../versions.toml");
+
+/// Upstream crates replaced by a fork of the same name.
+pub const FORKED: &[&str] = &["cosmwasm-std", "cosmwasm-storage", "cosmwasm-schema"];
+
+#[derive(Clone, Debug)]
+pub struct VersionMap {
+    pub releases: Vec<Release>,
+    /// Toolkit features on by default.
+    pub toolkit_defaults: Vec<String>,
+    /// `secret_toolkit` module → the feature providing it.
+    pub toolkit_modules: BTreeMap<String, String>,
+    /// Crates pulling upstream `cosmwasm-std` that need a `[patch]` to use the fork.
+    pub patched_dependents: Vec<String>,
+}
+
+/// The Secret counterparts of one range of upstream releases.
+#[derive(Clone, Debug)]
+pub struct Release {
+    /// Upstream `major.minor` versions, e.g. `1.1`.
+    pub upstream: Vec<String>,
+    /// Dependency specs for the forks and `secret-toolkit`, by crate name.
+    pub specs: BTreeMap<String, InlineTable>,
+    /// `cosmwasm-std` features the fork provides.
+    pub std_features: Vec<String>,
+    /// Specs for `[patch.crates-io]`, if the forks can patch crates.io.
+    pub patch: BTreeMap<String, InlineTable>,
+    /// Caveat reported whenever this release is picked.
+    pub note: Option<String>,
+}
+
+impl Default for VersionMap {
+    fn default() -> Self {
+        VersionMap::parse(BUILTIN).expect("built-in versions.toml is valid")
+    }
+}
+
+impl VersionMap {
+    pub fn parse(text: &str) -> Result<Self, MigrateError> {
+        let doc = text
+            .parse::<DocumentMut>()
+            .map_err(|e| MigrateError::Versions(e.to_string()))?;
+        let invalid = |what: &str| MigrateError::Versions(format!("missing or invalid {}", what));
+
+        let mut releases = vec![];
+        for release in doc
+            .get("release")
+            .and_then(Item::as_array_of_tables)
+            .ok_or_else(|| invalid("[[release]]"))?
+        {
+            let mut specs = BTreeMap::new();
+            for name in FORKED.iter().chain(&["secret-toolkit"]) {
+                let spec = release
+                    .get(name)
+                    .and_then(Item::as_inline_table)
+                    .ok_or_else(|| invalid(&format!("`{}` spec", name)))?;
+                specs.insert(name.to_string(), spec.clone());
+            }
+            let mut patch = BTreeMap::new();
+            if let Some(table) = release.get("patch").and_then(Item::as_table) {
+                for (name, spec) in table.iter() {
+                    let spec = spec
+                        .as_inline_table()
+                        .ok_or_else(|| invalid(&format!("`{}` patch", name)))?;
+                    patch.insert(name.to_string(), spec.clone());
+                }
+            }
+            releases.push(Release {
+                upstream: strings(release.get("upstream")).ok_or_else(|| invalid("`upstream`"))?,
+                specs,
+                std_features: strings(release.get("std-features")).unwrap_or_default(),
+                patch,
+                note: release
+                    .get("note")
+                    .and_then(Item::as_str)
+                    .map(str::to_string),
+            });
+        }
+
+        let toolkit = doc.get("toolkit");
+        let toolkit_modules = toolkit
+            .and_then(|t| t.get("modules"))
+            .and_then(Item::as_table_like)
+            .map(|modules| {
+                modules
+                    .iter()
+                    .filter_map(|(module, feature)| {
+                        Some((module.to_string(), feature.as_str()?.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(VersionMap {
+            releases,
+            toolkit_defaults: strings(toolkit.and_then(|t| t.get("default-features")))
+                .unwrap_or_default(),
+            toolkit_modules,
+            patched_dependents: strings(doc.get("dependents").and_then(|d| d.get("patched")))
+                .unwrap_or_default(),
+        })
+    }
+
+    /// The release for an upstream version requirement like `1.1.3` or `^1.0`.
+    pub fn release_for(&self, version: &str) -> Option<&Release> {
+        let version = version.trim_start_matches(|c: char| "^~=<> ".contains(c));
+        let minor: Vec<&str> = version.split('.').take(2).collect();
+        let minor = minor.join(".");
+        self.releases.iter().find(|r| r.upstream.contains(&minor))
+    }
+
+    /// Non-default toolkit features needed for the `secret_toolkit` modules used.
+    pub fn toolkit_features<'a>(&self, modules: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let mut features: Vec<String> = modules
+            .into_iter()
+            .filter_map(|m| self.toolkit_modules.get(m))
+            .filter(|f| !self.toolkit_defaults.contains(f))
+            .cloned()
+            .collect();
+        features.sort();
+        features.dedup();
+        features
+    }
+}
+
+impl Release {
+    /// `name`'s spec as an inline table, with `features` added and
+    /// `default-features = false` kept.
+    pub fn spec(&self, name: &str, default_features: bool, features: &[String]) -> Option<String> {
+        let mut spec = self.specs.get(name)?.clone();
+        if !default_features && !spec.contains_key("default-features") {
+            spec.insert("default-features", false.into());
+        }
+        if !features.is_empty() {
+            spec.insert("features", features.iter().collect());
+        }
+        Some(render(spec))
+    }
+
+    pub fn patch(&self, name: &str) -> Option<String> {
+        self.patch.get(name).cloned().map(render)
+    }
+}
+
+fn render(mut spec: InlineTable) -> String {
+    spec.fmt();
+    spec.to_string().trim().to_string()
+}
+
+fn strings(item: Option<&Item>) -> Option<Vec<String>> {
+    item?
+        .as_array()?
+        .iter()
+        .map(|v| v.as_str().map(str::to_string))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_map_picks_releases_and_features() {
+        let versions = VersionMap::default();
+
+        let tutorial = versions.release_for("1.1.3").unwrap();
+        assert_eq!(
+            Some(r#"{ git = "https://github.com/scrtlabs/cosmwasm/", default-features = false, tag = "v1.1.9-secret" }"#.to_string()),
+            tutorial.spec("cosmwasm-std", true, &[])
+        );
+        assert_eq!(
+            Some(r#"{ git = "https://github.com/scrtlabs/cosmwasm/", tag = "v1.1.9-secret", default-features = false, features = ["staking"] }"#.to_string()),
+            tutorial.spec("cosmwasm-storage", false, &["staking".to_string()])
+        );
+        assert!(tutorial.note.is_none());
+        assert!(versions.release_for("^1.0").is_some());
+
+        let newer = versions.release_for("1.5.0").unwrap();
+        assert!(newer.note.is_some());
+        assert!(newer.patch("cosmwasm-std").is_none());
+        assert!(versions.release_for("2.0.0").is_none());
+
+        assert_eq!(
+            vec!["permit", "viewing-key"],
+            versions.toolkit_features(["storage", "viewing_key", "permit", "permit"])
+        );
+    }
+}
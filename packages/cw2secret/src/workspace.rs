@@ -0,0 +1,579 @@
+//! Migrates the contracts of a Cargo workspace into sibling crates.
+//!
+//! `contracts/cw-counter` is migrated into `contracts/secret-counter`, which
+//! joins the workspace `members`. The target records a hash of every file it
+//! was generated with in `cw2secret.lock`, so a later run after upstream
+//! changes only touches files whose migration changed, and never overwrites a
+//! file edited by hand: that is reported as a conflict with the diff to apply.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use toml_edit::{Array, DocumentMut, Item, Table, Value};
+
+use crate::config::{self, Config};
+use crate::error::MigrateError;
+use crate::manifest::Manifest;
+use crate::migrate::{migrate_crate, MigrateOptions};
+use crate::package::secret_name;
+use crate::source::CrateSources;
+use crate::transform::{Migration, Transformation};
+
+/// Name of the file recording what a target crate was generated from.
+pub const LOCK: &str = "cw2secret.lock";
+
+/// Build output and files that belong to the source crate alone, which aren't
+/// copied into the target.
+const NOT_COPIED: &[&str] = &[
+    "target",
+    "artifacts",
+    ".git",
+    "Cargo.lock",
+    config::FILE,
+    LOCK,
+];
+
+/// A workspace root and its members, relative to it.
+pub struct Workspace {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+    pub members: Vec<PathBuf>,
+}
+
+impl Workspace {
+    /// Reads `[workspace] members` from `root/Cargo.toml`, expanding `dir/*`.
+    pub fn load(root: impl AsRef<Path>) -> Result<Self, MigrateError> {
+        let root = root.as_ref().to_path_buf();
+        let manifest = Manifest::load(&root)?;
+        let patterns = manifest
+            .doc
+            .get("workspace")
+            .and_then(|w| w.get("members"))
+            .and_then(Item::as_array)
+            .ok_or_else(|| MigrateError::Workspace("no `[workspace] members` array".into()))?;
+
+        let mut members = vec![];
+        for pattern in patterns {
+            let pattern = pattern
+                .as_str()
+                .ok_or_else(|| MigrateError::Workspace("`members` must be strings".into()))?;
+            match pattern.strip_suffix("/*") {
+                Some(dir) => {
+                    let entries = fs::read_dir(root.join(dir))
+                        .map_err(|e| MigrateError::io(root.join(dir), e))?;
+                    let mut found = vec![];
+                    for entry in entries {
+                        let entry = entry.map_err(|e| MigrateError::io(root.join(dir), e))?;
+                        if entry.path().join("Cargo.toml").is_file() {
+                            found.push(Path::new(dir).join(entry.file_name()));
+                        }
+                    }
+                    found.sort();
+                    members.extend(found);
+                }
+                None => members.push(PathBuf::from(pattern)),
+            }
+        }
+        Ok(Workspace {
+            root,
+            manifest,
+            members,
+        })
+    }
+
+    /// Members still on upstream `cosmwasm-std`, which are the ones to migrate.
+    /// Crates generated by an earlier run are on the fork, so they're left out.
+    pub fn contracts(&self) -> Result<Vec<PathBuf>, MigrateError> {
+        let mut contracts = vec![];
+        for member in &self.members {
+            let manifest = Manifest::load(self.root.join(member))?;
+            let upstream = manifest
+                .dependency("cosmwasm-std")
+                .is_some_and(|d| d.git.is_none() && d.version.is_some());
+            if upstream {
+                contracts.push(member.clone());
+            }
+        }
+        Ok(contracts)
+    }
+
+    /// The root manifest with every target listed in `members` right after its
+    /// source; `None` if they all are already.
+    pub fn with_members(&self, synced: &[MemberSync]) -> Option<String> {
+        let mut doc = self.manifest.doc.clone();
+        let members = doc
+            .get_mut("workspace")
+            .and_then(|w| w.get_mut("members"))
+            .and_then(Item::as_array_mut)?;
+        let mut changed = false;
+        for sync in synced {
+            let covers = |member: &str, path: &Path| match member.strip_suffix("/*") {
+                Some(dir) => path.parent() == Some(Path::new(dir)),
+                None => Path::new(member) == path,
+            };
+            let listed = |members: &Array, path: &Path| {
+                members
+                    .iter()
+                    .position(|m| m.as_str().is_some_and(|m| covers(m, path)))
+            };
+            if listed(members, &sync.target).is_some() {
+                continue;
+            }
+            let at = listed(members, &sync.source).map_or(members.len(), |i| i + 1);
+            // formatted like the member it displaces, or the last one
+            let mut value = Value::from(slash_path(&sync.target));
+            if let Some(like) = members.get(at).or_else(|| members.get(at.wrapping_sub(1))) {
+                *value.decor_mut() = like.decor().clone();
+            }
+            members.insert_formatted(at, value);
+            changed = true;
+        }
+        changed.then(|| doc.to_string())
+    }
+}
+
+/// What a run does to one file of a target crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    Created,
+    /// Regenerated; the old contents were what the last run generated.
+    Updated,
+    Unchanged,
+    /// No longer generated, and not edited since the last run.
+    Removed,
+    /// Edited or deleted by hand, and the migration has nothing new for it.
+    Kept,
+    /// Edited by hand, and the migration changed too; `diff` turns the file
+    /// into what the migration generates now.
+    Conflict {
+        diff: String,
+    },
+}
+
+/// The plan for migrating one member into its target crate.
+pub struct MemberSync {
+    /// Member directory, relative to the workspace root.
+    pub source: PathBuf,
+    /// Target directory, relative to the workspace root.
+    pub target: PathBuf,
+    pub migration: Migration,
+    /// Status of every file generated now or by the last run.
+    pub files: BTreeMap<PathBuf, FileStatus>,
+    writes: BTreeMap<PathBuf, Vec<u8>>,
+    lock: String,
+}
+
+impl MemberSync {
+    /// Whether writing would change anything in the target.
+    pub fn is_changed(&self) -> bool {
+        self.files.values().any(|s| {
+            matches!(
+                s,
+                FileStatus::Created | FileStatus::Updated | FileStatus::Removed
+            )
+        })
+    }
+
+    /// Transformations in files this run writes, i.e. the ones new since the
+    /// last run.
+    pub fn new_transformations(&self) -> impl Iterator<Item = &Transformation> {
+        self.migration.transformations.iter().filter(|t| {
+            matches!(
+                self.files.get(&t.file),
+                Some(FileStatus::Created | FileStatus::Updated)
+            )
+        })
+    }
+
+    /// Writes created and updated files, deletes removed ones and records the
+    /// lock. Conflicting and hand-edited files are left as they are.
+    pub fn write(&self, root: &Path) -> Result<(), MigrateError> {
+        let target = root.join(&self.target);
+        for (path, status) in &self.files {
+            let path_in_target = target.join(path);
+            match status {
+                FileStatus::Created | FileStatus::Updated => {
+                    if let Some(dir) = path_in_target.parent() {
+                        fs::create_dir_all(dir).map_err(|e| MigrateError::io(dir, e))?;
+                    }
+                    fs::write(&path_in_target, &self.writes[path])
+                        .map_err(|e| MigrateError::io(&path_in_target, e))?;
+                }
+                FileStatus::Removed => {
+                    fs::remove_file(&path_in_target)
+                        .map_err(|e| MigrateError::io(&path_in_target, e))?;
+                }
+                _ => {}
+            }
+        }
+        let lock = target.join(LOCK);
+        fs::write(&lock, &self.lock).map_err(|e| MigrateError::io(&lock, e))
+    }
+}
+
+/// Migrates `member` (relative to the workspace root) and works out which
+/// files of its target to write. `options.config` is replaced by the member's
+/// own `cw2secret.toml`.
+pub fn sync_member(
+    workspace: &Workspace,
+    member: &Path,
+    options: &MigrateOptions,
+) -> Result<MemberSync, MigrateError> {
+    let source_dir = workspace.root.join(member);
+    let krate = CrateSources::load(&source_dir)?;
+    let manifest = Manifest::load(&source_dir)?;
+    let options = MigrateOptions {
+        config: Config::load(&source_dir)?,
+        ..options.clone()
+    };
+    let migration = migrate_crate(&krate, &manifest, &options)?;
+
+    let name = match (&options.name, manifest.package_name()) {
+        (Some(name), _) => name.clone(),
+        (None, Some(name)) => secret_name(name),
+        (None, None) => {
+            return Err(MigrateError::Workspace(format!(
+                "{} has no package name",
+                member.display()
+            )))
+        }
+    };
+    let target = match member.parent() {
+        Some(parent) => parent.join(&name),
+        None => PathBuf::from(&name),
+    };
+    if target == member {
+        return Err(MigrateError::Workspace(format!(
+            "{} would be migrated onto itself",
+            member.display()
+        )));
+    }
+
+    let generated = generate(&source_dir, &migration)?;
+    let target_dir = workspace.root.join(&target);
+    let locked = read_lock(&target_dir)?;
+
+    let mut files = BTreeMap::new();
+    let mut hashes = BTreeMap::new();
+    let paths: BTreeSet<&PathBuf> = generated.keys().chain(locked.keys()).collect();
+    for path in paths {
+        let current = match fs::read(target_dir.join(path)) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(MigrateError::io(target_dir.join(path), e)),
+        };
+        let current_hash = current.as_deref().map(hash);
+        let last = locked.get(path);
+        let status = match generated.get(path) {
+            Some(new) => {
+                let new_hash = hash(new);
+                let status = match (&current_hash, last) {
+                    (Some(current), _) if *current == new_hash => FileStatus::Unchanged,
+                    (Some(current), Some(last)) if current == last => FileStatus::Updated,
+                    (None, None) => FileStatus::Created,
+                    (_, Some(last)) if *last == new_hash => FileStatus::Kept,
+                    _ => FileStatus::Conflict {
+                        diff: file_diff(path, current.as_deref(), new),
+                    },
+                };
+                hashes.insert(path.clone(), new_hash);
+                status
+            }
+            None => match current_hash {
+                Some(current) if Some(&current) == last => FileStatus::Removed,
+                Some(_) => FileStatus::Kept,
+                None => continue,
+            },
+        };
+        files.insert(path.clone(), status);
+    }
+
+    let writes = generated
+        .into_iter()
+        .filter(|(path, _)| {
+            matches!(
+                files.get(path),
+                Some(FileStatus::Created | FileStatus::Updated)
+            )
+        })
+        .collect();
+    Ok(MemberSync {
+        lock: render_lock(member, &target, &hashes),
+        source: member.to_path_buf(),
+        target,
+        migration,
+        files,
+        writes,
+    })
+}
+
+/// Every file of the target crate: the source crate's files with the
+/// migration applied.
+fn generate(
+    source_dir: &Path,
+    migration: &Migration,
+) -> Result<BTreeMap<PathBuf, Vec<u8>>, MigrateError> {
+    let mut paths = vec![];
+    collect_files(source_dir, source_dir, &mut paths)?;
+    let mut files = BTreeMap::new();
+    for path in paths {
+        if migration.removed.contains(&path) {
+            continue;
+        }
+        let bytes = match migration.files.get(&path) {
+            Some(text) => text.clone().into_bytes(),
+            None => {
+                let full = source_dir.join(&path);
+                fs::read(&full).map_err(|e| MigrateError::io(&full, e))?
+            }
+        };
+        files.insert(path, bytes);
+    }
+    for (path, text) in &migration.files {
+        files
+            .entry(path.clone())
+            .or_insert_with(|| text.clone().into_bytes());
+    }
+    Ok(files)
+}
+
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), MigrateError> {
+    let entries = fs::read_dir(dir).map_err(|e| MigrateError::io(dir, e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| MigrateError::io(dir, e))?.path();
+        if path
+            .file_name()
+            .is_some_and(|n| NOT_COPIED.iter().any(|skip| n == *skip))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, out)?;
+        } else {
+            out.push(path.strip_prefix(root).unwrap_or(&path).to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+fn hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn read_lock(target_dir: &Path) -> Result<BTreeMap<PathBuf, String>, MigrateError> {
+    let path = target_dir.join(LOCK);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(MigrateError::io(path, e)),
+    };
+    let invalid = || MigrateError::Workspace(format!("invalid {}", path.display()));
+    let doc = text.parse::<DocumentMut>().map_err(|_| invalid())?;
+    let files = doc
+        .get("files")
+        .and_then(Item::as_table)
+        .ok_or_else(invalid)?;
+    files
+        .iter()
+        .map(|(path, hash)| {
+            let hash = hash.as_str().ok_or_else(invalid)?;
+            Ok((PathBuf::from(path), hash.to_string()))
+        })
+        .collect()
+}
+
+fn render_lock(source: &Path, target: &Path, hashes: &BTreeMap<PathBuf, String>) -> String {
+    let mut doc = DocumentMut::new();
+    doc.decor_mut().set_prefix(format!(
+        "# Generated by `cw2secret workspace` from {}; do not edit.\n\
+         # Hashes of the files as last generated, to tell hand edits from stale output.\n",
+        slash_path(&relative(source, target))
+    ));
+    let mut files = Table::new();
+    for (path, hash) in hashes {
+        files.insert(&slash_path(path), toml_edit::value(hash.as_str()));
+    }
+    doc.insert("files", Item::Table(files));
+    doc.to_string()
+}
+
+/// `source` as seen from `target`, both relative to the same root:
+/// `../cw-counter` from `contracts/secret-counter`.
+fn relative(source: &Path, target: &Path) -> PathBuf {
+    let common = source
+        .components()
+        .zip(target.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut path: PathBuf = target.components().skip(common).map(|_| "..").collect();
+    path.extend(source.components().skip(common));
+    path
+}
+
+fn slash_path(path: &Path) -> String {
+    let parts: Vec<_> = path.iter().map(|p| p.to_string_lossy()).collect();
+    parts.join("/")
+}
+
+fn file_diff(path: &Path, current: Option<&[u8]>, new: &[u8]) -> String {
+    let old = current.map(String::from_utf8_lossy).unwrap_or_default();
+    let new = String::from_utf8_lossy(new);
+    let from = match current {
+        Some(_) => format!("a/{}", slash_path(path)),
+        None => "/dev/null".to_string(),
+    };
+    let to = format!("b/{}", slash_path(path));
+    TextDiff::from_lines(old.as_ref(), new.as_ref())
+        .unified_diff()
+        .header(&from, &to)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    /// A copy of this repo's workspace with `cw-counter` as its only contract.
+    fn scratch_workspace(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("cw2secret-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../contracts/cw-counter");
+        let mut paths = vec![];
+        collect_files(&source, &source, &mut paths).unwrap();
+        for path in paths {
+            let to = root.join("contracts/cw-counter").join(&path);
+            fs::create_dir_all(to.parent().unwrap()).unwrap();
+            fs::copy(source.join(&path), to).unwrap();
+        }
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"contracts/*\", \"packages/helpers\"]\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("packages/helpers")).unwrap();
+        fs::write(
+            root.join("packages/helpers/Cargo.toml"),
+            "[package]\nname = \"helpers\"\n\n[dependencies]\nserde = \"1.0\"\n",
+        )
+        .unwrap();
+        root
+    }
+
+    fn sync_all(root: &Path) -> Vec<MemberSync> {
+        let workspace = Workspace::load(root).unwrap();
+        let synced: Vec<_> = workspace
+            .contracts()
+            .unwrap()
+            .iter()
+            .map(|member| sync_member(&workspace, member, &MigrateOptions::default()).unwrap())
+            .collect();
+        for sync in &synced {
+            sync.write(root).unwrap();
+        }
+        if let Some(manifest) = workspace.with_members(&synced) {
+            fs::write(root.join("Cargo.toml"), manifest).unwrap();
+        }
+        synced
+    }
+
+    #[test]
+    fn reruns_only_apply_what_changed_upstream() {
+        let root = scratch_workspace("rerun");
+        let first = sync_all(&root);
+        assert_eq!(1, first.len());
+        assert_eq!(Path::new("contracts/secret-counter"), first[0].target);
+        assert!(first[0].files.values().all(|s| *s == FileStatus::Created));
+        assert!(!first[0].files.contains_key(Path::new("Cargo.lock")));
+        let target = root.join("contracts/secret-counter");
+        assert!(fs::read_to_string(target.join("Cargo.toml"))
+            .unwrap()
+            .contains("name = \"secret-counter\""));
+        // the glob already covers the target
+        assert_eq!(
+            "[workspace]\nmembers = [\"contracts/*\", \"packages/helpers\"]\n",
+            fs::read_to_string(root.join("Cargo.toml")).unwrap()
+        );
+
+        // the generated crate is on the fork, so only cw-counter is a contract
+        let second = sync_all(&root);
+        assert_eq!(1, second.len());
+        assert!(!second[0].is_changed());
+        assert_eq!(0, second[0].new_transformations().count());
+
+        // upstream changes one file, and the target's README is edited by hand
+        let source_state = root.join("contracts/cw-counter/src/state.rs");
+        let state = fs::read_to_string(&source_state).unwrap();
+        fs::write(&source_state, state.replace("\"user_state\"", "\"users\"")).unwrap();
+        let readme = target.join("README.md");
+        fs::write(&readme, "# Secret counter\n").unwrap();
+        let contract = root.join("contracts/cw-counter/src/contract.rs");
+        let text = fs::read_to_string(&contract).unwrap();
+        fs::write(
+            &contract,
+            text.replace("// version info", "// cw2 version info"),
+        )
+        .unwrap();
+        fs::write(target.join("src/contract.rs"), "// rewritten by hand\n").unwrap();
+
+        let third = sync_all(&root);
+        let files = &third[0].files;
+        assert_eq!(FileStatus::Updated, files[Path::new("src/state.rs")]);
+        assert_eq!(FileStatus::Kept, files[Path::new("README.md")]);
+        let FileStatus::Conflict { diff } = &files[Path::new("src/contract.rs")] else {
+            panic!(
+                "expected a conflict, got {:?}",
+                files[Path::new("src/contract.rs")]
+            );
+        };
+        assert!(diff.starts_with("--- a/src/contract.rs\n+++ b/src/contract.rs\n"));
+        assert!(third[0]
+            .new_transformations()
+            .all(|t| t.file == Path::new("src/state.rs")));
+        assert!(fs::read_to_string(target.join("src/state.rs"))
+            .unwrap()
+            .contains("Keymap::new(b\"users\")"));
+        assert_eq!("# Secret counter\n", fs::read_to_string(&readme).unwrap());
+
+        // the conflict is reported once; the next run has nothing new
+        assert!(sync_all(&root)[0]
+            .files
+            .values()
+            .all(|s| matches!(s, FileStatus::Unchanged | FileStatus::Kept)));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn targets_join_the_members_after_their_source() {
+        let workspace = Workspace {
+            root: PathBuf::new(),
+            manifest: Manifest::parse(
+                "[workspace]\nmembers = [ \"contracts/cw-counter\",\"contracts/secret-counter\"]\n",
+            )
+            .unwrap(),
+            members: vec![],
+        };
+        let sync = MemberSync {
+            source: PathBuf::from("contracts/cw-counter"),
+            target: PathBuf::from("contracts/secret-counter-v2"),
+            migration: Migration::default(),
+            files: BTreeMap::new(),
+            writes: BTreeMap::new(),
+            lock: String::new(),
+        };
+        assert_eq!(
+            "[workspace]\nmembers = [ \"contracts/cw-counter\",\"contracts/secret-counter-v2\",\"contracts/secret-counter\"]\n",
+            workspace.with_members(&[sync]).unwrap()
+        );
+        assert_eq!(
+            "../cw-counter",
+            slash_path(&relative(
+                Path::new("contracts/cw-counter"),
+                Path::new("contracts/secret-counter")
+            ))
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Signs a secret-counter permit offline and prints the `QueryMsg::WithPermit`
+//! to send with it, in place of the web tool in `assets/PermitGenerator.png`.
+//!
+//! ```text
+//! permit-generator --mnemonic-file wallet.txt --permit-name counter \
+//!     --allowed-token secret1contract... --permission user-count
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{ArgGroup, Parser, ValueEnum};
+use thiserror::Error;
+
+use secret_counter::msg::{CounterContractPermissions, QueryMsg, QueryWithPermit};
+use secret_permit_signer::{PermitSigner, SignerError};
+use secret_toolkit::permit::PermitParams;
+
+#[derive(Parser)]
+#[command(
+    name = "permit-generator",
+    version,
+    about = "Signs a secret-counter query permit offline"
+)]
+#[command(group(ArgGroup::new("key").required(true).args(["mnemonic_file", "key_file"])))]
+struct Cli {
+    /// File holding the wallet's BIP-39 mnemonic
+    #[arg(long, value_name = "FILE")]
+    mnemonic_file: Option<PathBuf>,
+    /// Account of the mnemonic to sign with, as in `m/44'/529'/0'/0/{account}`
+    #[arg(long, default_value_t = 0, requires = "mnemonic_file")]
+    account: u32,
+    /// File holding a hex-encoded secp256k1 private key
+    #[arg(long, value_name = "FILE")]
+    key_file: Option<PathBuf>,
+    /// Name of the permit, which is also the name it is revoked by
+    #[arg(long)]
+    permit_name: String,
+    /// Contract address the permit is valid for; repeat for several
+    #[arg(long = "allowed-token", value_name = "ADDRESS", required = true)]
+    allowed_tokens: Vec<String>,
+    /// Chain the permit is signed for
+    #[arg(long, default_value = "secret-4")]
+    chain_id: String,
+    /// Permission the permit grants; repeat for several
+    #[arg(long = "permission", value_enum, required = true)]
+    permissions: Vec<Permission>,
+}
+
+/// `CounterContractPermissions`, as spelled on the command line.
+#[derive(Clone, Copy, ValueEnum)]
+enum Permission {
+    UserCount,
+    Owner,
+}
+
+impl From<Permission> for CounterContractPermissions {
+    fn from(permission: Permission) -> Self {
+        match permission {
+            Permission::UserCount => CounterContractPermissions::UserCount,
+            Permission::Owner => CounterContractPermissions::Owner,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+enum Error {
+    #[error("cannot read {}: {}", .0.display(), .1)]
+    Io(PathBuf, std::io::Error),
+
+    #[error("{}: not a hex-encoded private key", .0.display())]
+    Hex(PathBuf),
+
+    #[error(transparent)]
+    Signer(#[from] SignerError),
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(json) => {
+            println!("{}", json);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<String, Error> {
+    let signer = match (&cli.mnemonic_file, &cli.key_file) {
+        (Some(path), _) => PermitSigner::from_mnemonic(&read(path)?, cli.account)?,
+        (None, Some(path)) => {
+            let key = hex::decode(read(path)?.trim()).map_err(|_| Error::Hex(path.clone()))?;
+            PermitSigner::from_bytes(&key)?
+        }
+        (None, None) => unreachable!("clap requires a key"),
+    };
+    eprintln!("signing as {}", signer.address());
+
+    let msg = permit_query(&signer, cli);
+    Ok(serde_json::to_string_pretty(&msg).expect("query messages serialize"))
+}
+
+/// The query that reads the signer's count with a permit built from `cli`.
+fn permit_query(signer: &PermitSigner, cli: Cli) -> QueryMsg {
+    let permit = signer.sign(PermitParams {
+        allowed_tokens: cli.allowed_tokens,
+        permit_name: cli.permit_name,
+        chain_id: cli.chain_id,
+        permissions: cli.permissions.into_iter().map(Into::into).collect(),
+    });
+    QueryMsg::WithPermit {
+        permit,
+        query: QueryWithPermit::GetUserCount {},
+    }
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_dependencies;
+    use secret_toolkit::permit::validate;
+    use serde_json::{json, Value};
+
+    fn cli(args: &[&str]) -> Cli {
+        let base = ["permit-generator", "--permit-name", "counter"];
+        Cli::try_parse_from(base.iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn prints_a_query_the_contract_accepts() {
+        let signer = PermitSigner::from_seed(b"user");
+        let cli = cli(&[
+            "--key-file",
+            "unused",
+            "--allowed-token",
+            "secret1contract",
+            "--permission",
+            "user-count",
+        ]);
+        let msg = permit_query(&signer, cli);
+
+        let json: Value = serde_json::to_value(&msg).unwrap();
+        let params = &json["with_permit"]["permit"]["params"];
+        assert_eq!("secret-4", params["chain_id"]);
+        assert_eq!("user_count", params["permissions"][0]);
+        assert_eq!(
+            json!({ "get_user_count": {} }),
+            json["with_permit"]["query"]
+        );
+
+        let QueryMsg::WithPermit { permit, .. } = msg else {
+            unreachable!()
+        };
+        let deps = mock_dependencies();
+        let account = validate(
+            deps.as_ref(),
+            "revoked",
+            &permit,
+            "secret1contract".into(),
+            None,
+        );
+        assert_eq!(signer.address(), account.unwrap());
+    }
+
+    #[test]
+    fn takes_exactly_one_key() {
+        let base = ["permit-generator", "--permit-name", "counter"];
+        let args = [
+            "--allowed-token",
+            "secret1contract",
+            "--permission",
+            "owner",
+        ];
+        assert!(Cli::try_parse_from(base.iter().chain(&args)).is_err());
+
+        let both = ["--mnemonic-file", "a", "--key-file", "b"];
+        assert!(Cli::try_parse_from(base.iter().chain(&args).chain(&both)).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+//! `#[derive(AuthenticatedQuery)]` writes the viewing key and permit plumbing
+//! that every Secret contract repeats for its `QueryMsg`.
+//!
+//! ```ignore
+//! #[cw_serde]
+//! #[derive(QueryResponses, AuthenticatedQuery)]
+//! pub enum QueryMsg {
+//!     #[returns(GetCountResponse)]
+//!     GetCount {},
+//!     #[returns(GetUserCountResponse)]
+//!     #[auth(viewing_key, addr = "addr", key = "key")]
+//!     GetUserCount { addr: Addr, key: String },
+//!     #[returns(QueryWithPermit)]
+//!     WithPermit {
+//!         permit: Permit<CounterContractPermissions>,
+//!         query: QueryWithPermit,
+//!     },
+//! }
+//! ```
+//!
+//! generates `QueryMsg::get_validation_params`, as in step 5b of the migration
+//! pathway, and the `QueryWithPermit` enum of step 5c: every `#[auth]` variant
+//! without its address and key fields, keeping its `#[returns]`.
+//!
+//! `addr` and `key` default to `"addr"` and `"key"`. `addr` can be given more
+//! than once for queries a viewing key of either address may authenticate. The
+//! mirror enum can be renamed with `#[auth(permit_query = "...")]` on the enum.
+//! The generated code names `cosmwasm_std` and `cosmwasm_schema`, so both have
+//! to be dependencies of the contract.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, LitStr, Variant};
+
+#[proc_macro_derive(AuthenticatedQuery, attributes(auth))]
+pub fn derive_authenticated_query(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A variant marked `#[auth(viewing_key, ...)]`.
+struct Protected<'a> {
+    variant: &'a Variant,
+    addrs: Vec<Ident>,
+    key: Ident,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "`AuthenticatedQuery` can only be derived for enums",
+        ));
+    };
+
+    let mut permit_query = format_ident!("QueryWithPermit");
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("auth")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("permit_query") {
+                let name: LitStr = meta.value()?.parse()?;
+                permit_query = name.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `permit_query = \"...\"`"))
+            }
+        })?;
+    }
+
+    let mut protected = vec![];
+    for variant in &data.variants {
+        if let Some(attr) = variant.attrs.iter().find(|a| a.path().is_ident("auth")) {
+            protected.push(parse_variant(variant, attr)?);
+        }
+    }
+    if protected.is_empty() {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "no variant is marked `#[auth(viewing_key, ...)]`",
+        ));
+    }
+
+    let validation = validation_params(&input, &protected);
+    let mirror = permit_mirror(&input, &protected, &permit_query);
+    Ok(quote! {
+        #validation
+        #mirror
+    })
+}
+
+fn parse_variant<'a>(variant: &'a Variant, attr: &Attribute) -> syn::Result<Protected<'a>> {
+    let mut viewing_key = false;
+    let mut addrs = vec![];
+    let mut key = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("viewing_key") {
+            viewing_key = true;
+        } else if meta.path.is_ident("addr") {
+            let name: LitStr = meta.value()?.parse()?;
+            addrs.push(name.parse::<Ident>()?);
+        } else if meta.path.is_ident("key") {
+            let name: LitStr = meta.value()?.parse()?;
+            key = Some(name.parse::<Ident>()?);
+        } else {
+            return Err(meta.error("expected `viewing_key`, `addr = \"...\"` or `key = \"...\"`"));
+        }
+        Ok(())
+    })?;
+    if !viewing_key {
+        return Err(syn::Error::new(
+            attr.span(),
+            "missing `viewing_key`: `#[auth(viewing_key, addr = \"...\", key = \"...\")]`",
+        ));
+    }
+    if addrs.is_empty() {
+        addrs.push(format_ident!("addr"));
+    }
+    let key = key.unwrap_or_else(|| format_ident!("key"));
+
+    let Fields::Named(fields) = &variant.fields else {
+        return Err(syn::Error::new(
+            variant.span(),
+            "authenticated queries need named fields, e.g. `GetUserCount { addr: Addr, key: String }`",
+        ));
+    };
+    for name in addrs.iter().chain([&key]) {
+        if !fields.named.iter().any(|f| f.ident.as_ref() == Some(name)) {
+            return Err(syn::Error::new(
+                attr.span(),
+                format!("`{}` has no field `{}`", variant.ident, name),
+            ));
+        }
+    }
+    if addrs.contains(&key) {
+        return Err(syn::Error::new(
+            attr.span(),
+            format!("`{}` can't be both an address and the key", key),
+        ));
+    }
+
+    Ok(Protected {
+        variant,
+        addrs,
+        key,
+    })
+}
+
+/// `get_validation_params`: the addresses whose viewing key may authenticate
+/// the query, validated, and the key it was sent with.
+fn validation_params(input: &DeriveInput, protected: &[Protected]) -> TokenStream2 {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let arms = protected.iter().map(|p| {
+        let variant = &p.variant.ident;
+        let addrs = &p.addrs;
+        let key = &p.key;
+        quote! {
+            Self::#variant { #(#addrs,)* #key, .. } => {
+                let addresses = ::std::vec![#(deps.api.addr_validate(#addrs.as_str())?),*];
+                ::std::result::Result::Ok((addresses, #key.clone()))
+            }
+        }
+    });
+
+    quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            pub fn get_validation_params(
+                &self,
+                deps: ::cosmwasm_std::Deps,
+            ) -> ::cosmwasm_std::StdResult<(::std::vec::Vec<::cosmwasm_std::Addr>, ::std::string::String)> {
+                match self {
+                    #(#arms)*
+                    #[allow(unreachable_patterns)]
+                    _ => ::std::panic!("This query type does not require authentication"),
+                }
+            }
+        }
+    }
+}
+
+/// The query wrapped by a permit: each protected variant minus the fields
+/// the permit itself vouches for.
+fn permit_mirror(
+    input: &DeriveInput,
+    protected: &[Protected],
+    permit_query: &Ident,
+) -> TokenStream2 {
+    let vis = &input.vis;
+    let mut responses = false;
+    let variants: Vec<_> = protected
+        .iter()
+        .map(|p| {
+            let attrs: Vec<_> = p
+                .variant
+                .attrs
+                .iter()
+                .filter(|a| a.path().is_ident("returns") || a.path().is_ident("doc"))
+                .collect();
+            responses |= attrs.iter().any(|a| a.path().is_ident("returns"));
+            let ident = &p.variant.ident;
+            let fields = p.variant.fields.iter().filter(|f| {
+                f.ident
+                    .as_ref()
+                    .is_some_and(|i| *i != p.key && !p.addrs.contains(i))
+            });
+            quote! {
+                #(#attrs)*
+                #ident { #(#fields),* },
+            }
+        })
+        .collect();
+    let derive = responses.then(|| quote!(#[derive(::cosmwasm_schema::QueryResponses)]));
+
+    quote! {
+        #[::cosmwasm_schema::cw_serde]
+        #derive
+        #vis enum #permit_query {
+            #(#variants)*
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn expand_error(input: DeriveInput) -> String {
+        expand(input).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn expands_secret_counter_query_msg() {
+        let input: DeriveInput = parse_quote! {
+            #[derive(QueryResponses)]
+            pub enum QueryMsg {
+                // GetCount returns the current count as a json-encoded number
+                #[returns(GetCountResponse)]
+                GetCount {},
+                #[returns(GetUserCountResponse)]
+                #[auth(viewing_key, addr = "addr", key = "key")]
+                GetUserCount { addr: Addr, key: String },
+                #[returns(QueryWithPermit)]
+                WithPermit {
+                    permit: Permit<CounterContractPermissions>,
+                    query: QueryWithPermit,
+                },
+            }
+        };
+
+        let expected = quote! {
+            impl QueryMsg {
+                pub fn get_validation_params(
+                    &self,
+                    deps: ::cosmwasm_std::Deps,
+                ) -> ::cosmwasm_std::StdResult<(::std::vec::Vec<::cosmwasm_std::Addr>, ::std::string::String)> {
+                    match self {
+                        Self::GetUserCount { addr, key, .. } => {
+                            let addresses = ::std::vec![deps.api.addr_validate(addr.as_str())?];
+                            ::std::result::Result::Ok((addresses, key.clone()))
+                        }
+                        #[allow(unreachable_patterns)]
+                        _ => ::std::panic!("This query type does not require authentication"),
+                    }
+                }
+            }
+
+            #[::cosmwasm_schema::cw_serde]
+            #[derive(::cosmwasm_schema::QueryResponses)]
+            pub enum QueryWithPermit {
+                #[returns(GetUserCountResponse)]
+                GetUserCount {},
+            }
+        };
+        assert_eq!(expected.to_string(), expand(input).unwrap().to_string());
+    }
+
+    #[test]
+    fn keeps_other_fields_and_accepts_several_addresses() {
+        let input: DeriveInput = parse_quote! {
+            #[auth(permit_query = "PermitQuery")]
+            pub enum QueryMsg {
+                #[auth(viewing_key, addr = "owner", addr = "spender", key = "viewing_key")]
+                Allowance { owner: String, spender: String, viewing_key: String },
+                #[auth(viewing_key)]
+                TransferHistory { addr: String, key: String, page: Option<u32> },
+            }
+        };
+
+        let expanded = expand(input).unwrap().to_string();
+        let validation = quote! {
+            Self::Allowance { owner, spender, viewing_key, .. } => {
+                let addresses = ::std::vec![
+                    deps.api.addr_validate(owner.as_str())?,
+                    deps.api.addr_validate(spender.as_str())?
+                ];
+                ::std::result::Result::Ok((addresses, viewing_key.clone()))
+            }
+        };
+        assert!(expanded.contains(&validation.to_string()));
+
+        // no `#[returns]`, so no `QueryResponses` for the mirror either
+        let mirror = quote! {
+            #[::cosmwasm_schema::cw_serde]
+            pub enum PermitQuery {
+                Allowance {},
+                TransferHistory { page: Option<u32> },
+            }
+        };
+        assert!(expanded.ends_with(&mirror.to_string()));
+    }
+
+    #[test]
+    fn rejects_misuse() {
+        assert_eq!(
+            "`AuthenticatedQuery` can only be derived for enums",
+            expand_error(parse_quote!(
+                struct QueryMsg {}
+            ))
+        );
+        assert_eq!(
+            "no variant is marked `#[auth(viewing_key, ...)]`",
+            expand_error(parse_quote!(
+                enum QueryMsg {
+                    GetCount {},
+                }
+            ))
+        );
+        assert_eq!(
+            "`GetUserCount` has no field `address`",
+            expand_error(parse_quote!(
+                enum QueryMsg {
+                    #[auth(viewing_key, addr = "address")]
+                    GetUserCount { addr: Addr, key: String },
+                }
+            ))
+        );
+        assert_eq!(
+            "missing `viewing_key`: `#[auth(viewing_key, addr = \"...\", key = \"...\")]`",
+            expand_error(parse_quote!(
+                enum QueryMsg {
+                    #[auth(addr = "addr")]
+                    GetUserCount { addr: Addr, key: String },
+                }
+            ))
+        );
+        assert!(expand_error(parse_quote!(
+            enum QueryMsg {
+                #[auth(viewing_key)]
+                GetUserCount(Addr, String),
+            }
+        ))
+        .starts_with("authenticated queries need named fields"));
+    }
+}
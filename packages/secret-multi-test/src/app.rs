@@ -0,0 +1,732 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail};
+use cosmwasm_std::testing::{mock_env, MockApi};
+use cosmwasm_std::{
+    from_slice, to_binary, Addr, AllBalanceResponse, Api, BalanceResponse, BankMsg, BankQuery,
+    Binary, BlockInfo, Coin, ContractInfo, ContractResult, CosmosMsg, Deps, DepsMut, Empty, Env,
+    Event, MessageInfo, Querier, QuerierResult, QuerierWrapper, QueryRequest, Reply, ReplyOn,
+    Response, Storage, SubMsgResponse, SubMsgResult, SystemError, SystemResult, TransactionInfo,
+    WasmMsg, WasmQuery,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::bank::Bank;
+use crate::contracts::Contract;
+use crate::store::Store;
+use crate::AnyResult;
+
+/// A code upload: the id to instantiate it from, and the hash every message
+/// to its instances has to carry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredCode {
+    pub code_id: u64,
+    pub code_hash: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AppResponse {
+    pub events: Vec<Event>,
+    pub data: Option<Binary>,
+}
+
+/// The modules messages are routed to besides contracts.
+#[derive(Default)]
+pub struct Router {
+    pub bank: Bank,
+}
+
+pub struct AppBuilder {
+    block: BlockInfo,
+}
+
+impl Default for AppBuilder {
+    fn default() -> Self {
+        AppBuilder {
+            block: mock_env().block,
+        }
+    }
+}
+
+impl AppBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_block(mut self, block: BlockInfo) -> Self {
+        self.block = block;
+        self
+    }
+
+    /// Builds the app, letting `init_fn` set up genesis state such as
+    /// balances.
+    pub fn build<F>(self, init_fn: F) -> App
+    where
+        F: FnOnce(&mut Router, &dyn Api, &mut dyn Storage),
+    {
+        let mut block = self.block;
+        if block.random.is_none() {
+            block.random = Some(block_random(&block));
+        }
+        let mut app = App {
+            router: Router::default(),
+            api: MockApi::default(),
+            block,
+            codes: vec![],
+            state: State::default(),
+        };
+        app.init_modules(init_fn);
+        app
+    }
+}
+
+#[derive(Clone)]
+struct ContractData {
+    code_id: u64,
+    label: String,
+    /// `None` while the contract is executing.
+    storage: Option<Store>,
+}
+
+/// Everything a failed transaction has to roll back.
+#[derive(Clone, Default)]
+struct State {
+    bank: Store,
+    contracts: BTreeMap<Addr, ContractData>,
+}
+
+pub struct App {
+    router: Router,
+    api: MockApi,
+    block: BlockInfo,
+    codes: Vec<Box<dyn Contract<Empty>>>,
+    state: State,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        AppBuilder::new().build(|_, _, _| {})
+    }
+}
+
+impl App {
+    pub fn new<F>(init_fn: F) -> Self
+    where
+        F: FnOnce(&mut Router, &dyn Api, &mut dyn Storage),
+    {
+        AppBuilder::new().build(init_fn)
+    }
+
+    pub fn init_modules<F, T>(&mut self, init_fn: F) -> T
+    where
+        F: FnOnce(&mut Router, &dyn Api, &mut dyn Storage) -> T,
+    {
+        init_fn(&mut self.router, &self.api, &mut self.state.bank)
+    }
+
+    pub fn wrap(&self) -> QuerierWrapper<'_> {
+        QuerierWrapper::new(self)
+    }
+
+    pub fn block_info(&self) -> BlockInfo {
+        self.block.clone()
+    }
+
+    /// Changes the block, e.g. with `next_block`. A new height gets a new
+    /// `env.block.random` unless `action` sets one.
+    pub fn update_block<F: Fn(&mut BlockInfo)>(&mut self, action: F) {
+        let before = self.block.clone();
+        action(&mut self.block);
+        if self.block.height != before.height && self.block.random == before.random {
+            self.block.random = Some(block_random(&self.block));
+        }
+    }
+
+    /// Uploads a contract. Its hash stands in for the SHA-256 of the wasm
+    /// the chain would compute.
+    pub fn store_code(&mut self, code: Box<dyn Contract<Empty>>) -> StoredCode {
+        self.codes.push(code);
+        let code_id = self.codes.len() as u64;
+        StoredCode {
+            code_id,
+            code_hash: code_hash(code_id),
+        }
+    }
+
+    fn dispatch(&mut self, sender: Addr, msg: CosmosMsg) -> AnyResult<AppResponse> {
+        match msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                code_hash,
+                msg,
+                funds,
+            }) => {
+                let contract = Addr::unchecked(contract_addr);
+                self.check_code_hash(&contract, &code_hash)?;
+                self.router
+                    .bank
+                    .send(&mut self.state.bank, &sender, &contract, &funds)?;
+                let info = MessageInfo { sender, funds };
+                let res = self.call(&contract, |code, deps, env| {
+                    code.execute(deps, env, info, msg.to_vec())
+                })?;
+                let mut response = self.handle_response(&contract, res)?;
+                response.events.insert(
+                    0,
+                    Event::new("execute").add_attribute("_contract_address", &contract),
+                );
+                Ok(response)
+            }
+            CosmosMsg::Wasm(WasmMsg::Instantiate {
+                code_id,
+                code_hash: hash,
+                msg,
+                funds,
+                label,
+            }) => {
+                if code_id == 0 || code_id > self.codes.len() as u64 {
+                    bail!("no code with id {}", code_id);
+                }
+                if hash != code_hash(code_id) {
+                    bail!("code hash {:?} doesn't match code {}", hash, code_id);
+                }
+                if self.state.contracts.values().any(|c| c.label == label) {
+                    bail!("label {:?} is already taken", label);
+                }
+                let contract = Addr::unchecked(format!("contract{}", self.state.contracts.len()));
+                self.state.contracts.insert(
+                    contract.clone(),
+                    ContractData {
+                        code_id,
+                        label,
+                        storage: Some(Store::default()),
+                    },
+                );
+                self.router
+                    .bank
+                    .send(&mut self.state.bank, &sender, &contract, &funds)?;
+                let info = MessageInfo { sender, funds };
+                let res = self.call(&contract, |code, deps, env| {
+                    code.instantiate(deps, env, info, msg.to_vec())
+                })?;
+                let mut response = self.handle_response(&contract, res)?;
+                response.data = Some(instantiate_response_data(&contract, response.data));
+                response.events.insert(
+                    0,
+                    Event::new("instantiate")
+                        .add_attribute("_contract_address", &contract)
+                        .add_attribute("code_id", code_id.to_string()),
+                );
+                Ok(response)
+            }
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                let recipient = Addr::unchecked(to_address);
+                self.router
+                    .bank
+                    .send(&mut self.state.bank, &sender, &recipient, &amount)?;
+                Ok(AppResponse {
+                    events: vec![Event::new("transfer")
+                        .add_attribute("recipient", recipient)
+                        .add_attribute("sender", sender)
+                        .add_attribute("amount", coins_to_string(&amount))],
+                    data: None,
+                })
+            }
+            other => bail!("unsupported message: {:?}", other),
+        }
+    }
+
+    /// Runs `f` against `contract`'s code with its storage checked out.
+    fn call<R>(
+        &mut self,
+        contract: &Addr,
+        f: impl FnOnce(&dyn Contract<Empty>, DepsMut, Env) -> AnyResult<R>,
+    ) -> AnyResult<R> {
+        let data = self
+            .state
+            .contracts
+            .get_mut(contract)
+            .ok_or_else(|| anyhow!("no contract at {}", contract))?;
+        let code_id = data.code_id;
+        let mut storage = data
+            .storage
+            .take()
+            .ok_or_else(|| anyhow!("contract {} is already executing", contract))?;
+
+        let env = self.env(contract, code_id);
+        let res = f(
+            self.codes[code_id as usize - 1].as_ref(),
+            DepsMut {
+                storage: &mut storage,
+                api: &self.api,
+                querier: QuerierWrapper::new(&*self),
+            },
+            env,
+        );
+
+        self.state.contracts.get_mut(contract).unwrap().storage = Some(storage);
+        res
+    }
+
+    fn handle_response(&mut self, contract: &Addr, res: Response) -> AnyResult<AppResponse> {
+        let mut events = vec![];
+        if !res.attributes.is_empty() {
+            events.push(
+                Event::new("wasm")
+                    .add_attribute("_contract_address", contract)
+                    .add_attributes(res.attributes),
+            );
+        }
+        events.extend(res.events.into_iter().map(|e| {
+            Event::new(format!("wasm-{}", e.ty))
+                .add_attribute("_contract_address", contract)
+                .add_attributes(e.attributes)
+        }));
+        let mut data = res.data;
+        for sub in res.messages {
+            // a submessage that fails and is replied to only loses its own changes
+            let snapshot = self.state.clone();
+            let result = match (self.dispatch(contract.clone(), sub.msg), &sub.reply_on) {
+                (Ok(res), ReplyOn::Success | ReplyOn::Always) => {
+                    events.extend(res.events.iter().cloned());
+                    SubMsgResult::Ok(SubMsgResponse {
+                        events: res.events,
+                        data: res.data,
+                    })
+                }
+                (Ok(res), _) => {
+                    events.extend(res.events);
+                    continue;
+                }
+                (Err(e), ReplyOn::Error | ReplyOn::Always) => {
+                    self.state = snapshot;
+                    SubMsgResult::Err(e.to_string())
+                }
+                (Err(e), _) => return Err(e),
+            };
+            let mode = match result {
+                SubMsgResult::Ok(_) => "handle_success",
+                SubMsgResult::Err(_) => "handle_failure",
+            };
+            let reply = Reply { id: sub.id, result };
+            let res = self.call(contract, |code, deps, env| code.reply(deps, env, reply))?;
+            let response = self.handle_response(contract, res)?;
+            events.push(
+                Event::new("reply")
+                    .add_attribute("_contract_address", contract)
+                    .add_attribute("mode", mode),
+            );
+            events.extend(response.events);
+            if response.data.is_some() {
+                data = response.data;
+            }
+        }
+        Ok(AppResponse { events, data })
+    }
+
+    fn check_code_hash(&self, contract: &Addr, hash: &str) -> AnyResult<()> {
+        let data = self
+            .state
+            .contracts
+            .get(contract)
+            .ok_or_else(|| anyhow!("no contract at {}", contract))?;
+        if hash != code_hash(data.code_id) {
+            bail!("code hash {:?} doesn't match contract {}", hash, contract);
+        }
+        Ok(())
+    }
+
+    fn env(&self, contract: &Addr, code_id: u64) -> Env {
+        Env {
+            block: self.block.clone(),
+            transaction: Some(TransactionInfo { index: 0 }),
+            contract: ContractInfo {
+                address: contract.clone(),
+                code_hash: code_hash(code_id),
+            },
+        }
+    }
+
+    fn query(&self, request: QueryRequest<Empty>) -> AnyResult<Binary> {
+        let bank = &self.router.bank;
+        match request {
+            QueryRequest::Bank(BankQuery::Balance { address, denom }) => {
+                let amount = bank.balance(&self.state.bank, &Addr::unchecked(address), &denom)?;
+                Ok(to_binary(&BalanceResponse { amount })?)
+            }
+            QueryRequest::Bank(BankQuery::AllBalances { address }) => {
+                let amount = bank.balances(&self.state.bank, &Addr::unchecked(address))?;
+                Ok(to_binary(&AllBalanceResponse { amount })?)
+            }
+            QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr,
+                code_hash,
+                msg,
+            }) => {
+                let contract = Addr::unchecked(contract_addr);
+                self.check_code_hash(&contract, &code_hash)?;
+                let data = &self.state.contracts[&contract];
+                let storage = data.storage.as_ref().ok_or_else(|| {
+                    anyhow!("contract {} is executing and can't be queried", contract)
+                })?;
+                let deps = Deps {
+                    storage,
+                    api: &self.api,
+                    querier: QuerierWrapper::new(self),
+                };
+                self.codes[data.code_id as usize - 1].query(
+                    deps,
+                    self.env(&contract, data.code_id),
+                    msg.to_vec(),
+                )
+            }
+            other => bail!("unsupported query: {:?}", other),
+        }
+    }
+}
+
+impl Querier for App {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(request) => request,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: e.to_string(),
+                    request: bin_request.into(),
+                })
+            }
+        };
+        match self.query(request) {
+            Ok(data) => SystemResult::Ok(ContractResult::Ok(data)),
+            Err(e) => SystemResult::Ok(ContractResult::Err(e.to_string())),
+        }
+    }
+}
+
+/// Sending messages, with every message a transaction of its own: if it fails
+/// nothing it did is kept.
+pub trait Executor {
+    fn execute(&mut self, sender: Addr, msg: CosmosMsg) -> AnyResult<AppResponse>;
+
+    /// Instantiates `code`. Secret contracts have no admin, so unlike
+    /// `cw-multi-test` there is no admin argument.
+    fn instantiate_contract<T: Serialize, U: Into<String>>(
+        &mut self,
+        code: &StoredCode,
+        sender: Addr,
+        init_msg: &T,
+        send_funds: &[Coin],
+        label: U,
+    ) -> AnyResult<ContractInfo> {
+        let msg = WasmMsg::Instantiate {
+            code_id: code.code_id,
+            code_hash: code.code_hash.clone(),
+            msg: to_binary(init_msg)?,
+            funds: send_funds.to_vec(),
+            label: label.into(),
+        };
+        let res = self.execute(sender, msg.into())?;
+        let address = res
+            .events
+            .iter()
+            .filter(|e| e.ty == "instantiate")
+            .flat_map(|e| &e.attributes)
+            .find(|a| a.key == "_contract_address")
+            .map(|a| Addr::unchecked(&a.value))
+            .ok_or_else(|| anyhow!("no contract address in the instantiate events"))?;
+        Ok(ContractInfo {
+            address,
+            code_hash: code.code_hash.clone(),
+        })
+    }
+
+    fn execute_contract<T: Serialize>(
+        &mut self,
+        sender: Addr,
+        contract: &ContractInfo,
+        msg: &T,
+        send_funds: &[Coin],
+    ) -> AnyResult<AppResponse> {
+        let msg = WasmMsg::Execute {
+            contract_addr: contract.address.to_string(),
+            code_hash: contract.code_hash.clone(),
+            msg: to_binary(msg)?,
+            funds: send_funds.to_vec(),
+        };
+        self.execute(sender, msg.into())
+    }
+}
+
+impl Executor for App {
+    fn execute(&mut self, sender: Addr, msg: CosmosMsg) -> AnyResult<AppResponse> {
+        let snapshot = self.state.clone();
+        let res = self.dispatch(sender, msg);
+        if res.is_err() {
+            self.state = snapshot;
+        }
+        res
+    }
+}
+
+fn code_hash(code_id: u64) -> String {
+    format!("{:x}", Sha256::digest(format!("code {}", code_id)))
+}
+
+/// `env.block.random` for a block: the same for every message in it, and
+/// different at every height.
+fn block_random(block: &BlockInfo) -> Binary {
+    let seed = format!("random {} {}", block.chain_id, block.height);
+    Binary(Sha256::digest(seed).to_vec())
+}
+
+/// The protobuf `MsgInstantiateContractResponse` the chain returns as the data
+/// of an instantiation, which a `reply` parses to learn the new address.
+fn instantiate_response_data(contract: &Addr, data: Option<Binary>) -> Binary {
+    let mut out = vec![];
+    protobuf_field(&mut out, 1, contract.as_bytes());
+    if let Some(data) = data {
+        protobuf_field(&mut out, 2, &data);
+    }
+    Binary(out)
+}
+
+/// Appends a length-delimited protobuf field.
+fn protobuf_field(out: &mut Vec<u8>, field: u8, bytes: &[u8]) {
+    out.push((field << 3) | 2);
+    let mut len = bytes.len();
+    while len >= 0x80 {
+        out.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+    out.extend_from_slice(bytes);
+}
+
+fn coins_to_string(coins: &[Coin]) -> String {
+    coins
+        .iter()
+        .map(Coin::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ContractWrapper;
+    use cosmwasm_std::{coins, StdError, StdResult, SubMsg};
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum Msg {
+        Set {
+            value: u32,
+        },
+        Fail {},
+        /// Sets 5, then sends `target` a `Set` or a `Fail` as a submessage.
+        Call {
+            target: ContractInfo,
+            fail: bool,
+            reply_on: ReplyOn,
+        },
+    }
+
+    fn execute(deps: DepsMut, _env: Env, _info: MessageInfo, msg: Msg) -> StdResult<Response> {
+        match msg {
+            Msg::Set { value } => {
+                deps.storage.set(b"value", &value.to_be_bytes());
+                Ok(Response::new().add_attribute("action", "set"))
+            }
+            Msg::Fail {} => Err(StdError::generic_err("failed")),
+            Msg::Call {
+                target,
+                fail,
+                reply_on,
+            } => {
+                deps.storage.set(b"value", &5u32.to_be_bytes());
+                let msg = match fail {
+                    true => Msg::Fail {},
+                    false => Msg::Set { value: 9 },
+                };
+                let msg = WasmMsg::Execute {
+                    contract_addr: target.address.to_string(),
+                    code_hash: target.code_hash,
+                    msg: to_binary(&msg)?,
+                    funds: vec![],
+                };
+                Ok(Response::new().add_submessage(SubMsg {
+                    id: 7,
+                    msg: msg.into(),
+                    gas_limit: None,
+                    reply_on,
+                }))
+            }
+        }
+    }
+
+    /// Records 100 for a submessage that succeeded, 200 for one that failed.
+    fn reply(deps: DepsMut, _env: Env, reply: Reply) -> StdResult<Response> {
+        let value: u32 = match reply.result {
+            SubMsgResult::Ok(_) => 100,
+            SubMsgResult::Err(_) => 200,
+        };
+        deps.storage.set(b"value", &value.to_be_bytes());
+        Ok(Response::new().set_data(Binary::from(b"replied")))
+    }
+
+    fn query(deps: Deps, env: Env, _msg: Empty) -> StdResult<Binary> {
+        let value = deps.storage.get(b"value").unwrap_or_default();
+        to_binary(&(Binary(value), env.contract.code_hash))
+    }
+
+    fn setup() -> (App, ContractInfo) {
+        let mut app = App::new(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &Addr::unchecked("user"), coins(10, "uscrt"))
+                .unwrap();
+        });
+        let code = app.store_code(Box::new(
+            ContractWrapper::new(execute, execute, query).with_reply(reply),
+        ));
+        let contract = app
+            .instantiate_contract(
+                &code,
+                Addr::unchecked("user"),
+                &Msg::Set { value: 1 },
+                &[],
+                "first",
+            )
+            .unwrap();
+        (app, contract)
+    }
+
+    #[test]
+    fn calls_carry_the_code_hash() {
+        let (mut app, contract) = setup();
+        assert_eq!(64, contract.code_hash.len());
+
+        app.execute_contract(
+            Addr::unchecked("user"),
+            &contract,
+            &Msg::Set { value: 7 },
+            &coins(3, "uscrt"),
+        )
+        .unwrap();
+        let (value, hash): (Binary, String) = app
+            .wrap()
+            .query_wasm_smart(&contract.code_hash, &contract.address, &Empty {})
+            .unwrap();
+        assert_eq!(7u32.to_be_bytes().to_vec(), value.0);
+        assert_eq!(contract.code_hash, hash);
+        let balance = app
+            .wrap()
+            .query_balance(&contract.address, "uscrt")
+            .unwrap();
+        assert_eq!(3, balance.amount.u128());
+
+        let wrong = ContractInfo {
+            code_hash: "00".repeat(32),
+            ..contract
+        };
+        let err = app
+            .execute_contract(Addr::unchecked("user"), &wrong, &Msg::Set { value: 8 }, &[])
+            .unwrap_err();
+        assert!(err.to_string().contains("doesn't match"), "{}", err);
+    }
+
+    #[test]
+    fn labels_are_unique_and_failures_roll_back() {
+        let (mut app, contract) = setup();
+        let code = StoredCode {
+            code_id: 1,
+            code_hash: contract.code_hash.clone(),
+        };
+        let msg = Msg::Set { value: 2 };
+        let user = Addr::unchecked("user");
+        app.instantiate_contract(&code, user.clone(), &msg, &[], "first")
+            .unwrap_err();
+
+        app.execute_contract(user.clone(), &contract, &Msg::Fail {}, &coins(5, "uscrt"))
+            .unwrap_err();
+        let balance = app.wrap().query_balance(&user, "uscrt").unwrap();
+        assert_eq!(10, balance.amount.u128());
+    }
+
+    fn value(app: &App, contract: &ContractInfo) -> u32 {
+        let (value, _): (Binary, String) = app
+            .wrap()
+            .query_wasm_smart(&contract.code_hash, &contract.address, &Empty {})
+            .unwrap();
+        u32::from_be_bytes(value.0.try_into().unwrap())
+    }
+
+    #[test]
+    fn submessages_reply_and_roll_back_on_their_own() {
+        let (mut app, first) = setup();
+        let code = StoredCode {
+            code_id: 1,
+            code_hash: first.code_hash.clone(),
+        };
+        let user = Addr::unchecked("user");
+        let second = app
+            .instantiate_contract(&code, user.clone(), &Msg::Set { value: 1 }, &[], "second")
+            .unwrap();
+        let call = |fail, reply_on| Msg::Call {
+            target: second.clone(),
+            fail,
+            reply_on,
+        };
+
+        let res = app
+            .execute_contract(user.clone(), &first, &call(false, ReplyOn::Success), &[])
+            .unwrap();
+        assert_eq!((100, 9), (value(&app, &first), value(&app, &second)));
+        assert_eq!(Some(Binary::from(b"replied")), res.data);
+        assert!(res.events.iter().any(|e| e.ty == "reply"));
+
+        // the failed submessage is rolled back alone, and the reply sees the error
+        app.execute_contract(user.clone(), &second, &Msg::Set { value: 1 }, &[])
+            .unwrap();
+        app.execute_contract(user.clone(), &first, &call(true, ReplyOn::Error), &[])
+            .unwrap();
+        assert_eq!((200, 1), (value(&app, &first), value(&app, &second)));
+
+        // without a reply the failure fails the whole transaction
+        app.execute_contract(user.clone(), &first, &call(true, ReplyOn::Success), &[])
+            .unwrap_err();
+        assert_eq!(200, value(&app, &first));
+    }
+
+    #[test]
+    fn instantiate_data_holds_the_address() {
+        let mut app = App::default();
+        let code = app.store_code(Box::new(ContractWrapper::new(execute, execute, query)));
+        let res = app
+            .execute(
+                Addr::unchecked("user"),
+                WasmMsg::Instantiate {
+                    code_id: code.code_id,
+                    code_hash: code.code_hash,
+                    msg: to_binary(&Msg::Set { value: 1 }).unwrap(),
+                    funds: vec![],
+                    label: "counter".to_string(),
+                }
+                .into(),
+            )
+            .unwrap();
+        assert_eq!(b"\x0a\x09contract0".to_vec(), res.data.unwrap().0);
+    }
+    #[test]
+    fn every_block_has_its_own_random() {
+        let mut app = App::default();
+        let random = app.block_info().random;
+        assert!(random.is_some());
+        app.update_block(|block| block.time = block.time.plus_seconds(5));
+        assert_eq!(random, app.block_info().random);
+        app.update_block(|block| block.height += 1);
+        assert_ne!(random, app.block_info().random);
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::bail;
+use cosmwasm_std::{from_slice, to_vec, Addr, Coin, Storage, Uint128};
+
+use crate::AnyResult;
+
+const BALANCES: &[u8] = b"balances/";
+
+/// Native token balances, kept in the app's own storage.
+#[derive(Clone, Debug, Default)]
+pub struct Bank {}
+
+impl Bank {
+    /// Sets the balance of `account`, replacing whatever it held.
+    pub fn init_balance(
+        &self,
+        storage: &mut dyn Storage,
+        account: &Addr,
+        amount: Vec<Coin>,
+    ) -> AnyResult<()> {
+        let amount: Vec<Coin> = amount.into_iter().filter(|c| !c.amount.is_zero()).collect();
+        storage.set(&key(account), &to_vec(&amount)?);
+        Ok(())
+    }
+
+    pub fn balances(&self, storage: &dyn Storage, account: &Addr) -> AnyResult<Vec<Coin>> {
+        match storage.get(&key(account)) {
+            Some(data) => Ok(from_slice(&data)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn balance(&self, storage: &dyn Storage, account: &Addr, denom: &str) -> AnyResult<Coin> {
+        let amount = self
+            .balances(storage, account)?
+            .into_iter()
+            .find(|c| c.denom == denom)
+            .map_or(Uint128::zero(), |c| c.amount);
+        Ok(Coin {
+            denom: denom.to_string(),
+            amount,
+        })
+    }
+
+    pub(crate) fn send(
+        &self,
+        storage: &mut dyn Storage,
+        from: &Addr,
+        to: &Addr,
+        amount: &[Coin],
+    ) -> AnyResult<()> {
+        let mut from_balance = self.balances(storage, from)?;
+        for coin in amount {
+            let Some(held) = from_balance.iter_mut().find(|c| c.denom == coin.denom) else {
+                bail!("{} has no {} to send", from, coin.denom);
+            };
+            held.amount = held.amount.checked_sub(coin.amount).map_err(|_| {
+                anyhow::anyhow!("{} cannot send {}: balance is {}", from, coin, held)
+            })?;
+        }
+        self.init_balance(storage, from, from_balance)?;
+
+        let mut to_balance = self.balances(storage, to)?;
+        for coin in amount {
+            match to_balance.iter_mut().find(|c| c.denom == coin.denom) {
+                Some(held) => held.amount += coin.amount,
+                None => to_balance.push(coin.clone()),
+            }
+        }
+        self.init_balance(storage, to, to_balance)
+    }
+}
+
+fn key(account: &Addr) -> Vec<u8> {
+    [BALANCES, account.as_bytes()].concat()
+}
@@ -0,0 +1,118 @@
+use std::fmt::{Debug, Display};
+
+use anyhow::{anyhow, bail};
+use cosmwasm_std::{from_slice, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Reply, Response};
+use serde::de::DeserializeOwned;
+
+use crate::AnyResult;
+
+/// A contract as the harness calls it: raw JSON messages in, responses out.
+pub trait Contract<T = Empty> {
+    fn execute(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: Vec<u8>,
+    ) -> AnyResult<Response<T>>;
+
+    fn instantiate(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: Vec<u8>,
+    ) -> AnyResult<Response<T>>;
+
+    fn query(&self, deps: Deps, env: Env, msg: Vec<u8>) -> AnyResult<Binary>;
+
+    /// Handles the result of a submessage sent with a `ReplyOn` other than `Never`.
+    fn reply(&self, _deps: DepsMut, _env: Env, msg: Reply) -> AnyResult<Response<T>> {
+        bail!("no reply entry point to handle submessage {}", msg.id)
+    }
+}
+
+type ContractFn<M, E> = fn(DepsMut, Env, MessageInfo, M) -> Result<Response, E>;
+type QueryFn<M, E> = fn(Deps, Env, M) -> Result<Binary, E>;
+type ReplyFn<E> = fn(DepsMut, Env, Reply) -> Result<Response, E>;
+
+/// Wraps a contract's entry points, e.g.
+/// `ContractWrapper::new(contract::execute, contract::instantiate, contract::query)`.
+pub struct ContractWrapper<T1, T2, T3, E1, E2, E3, E4 = anyhow::Error> {
+    execute_fn: ContractFn<T1, E1>,
+    instantiate_fn: ContractFn<T2, E2>,
+    query_fn: QueryFn<T3, E3>,
+    reply_fn: Option<ReplyFn<E4>>,
+}
+
+impl<T1, T2, T3, E1, E2, E3> ContractWrapper<T1, T2, T3, E1, E2, E3> {
+    pub fn new(
+        execute_fn: ContractFn<T1, E1>,
+        instantiate_fn: ContractFn<T2, E2>,
+        query_fn: QueryFn<T3, E3>,
+    ) -> Self {
+        ContractWrapper {
+            execute_fn,
+            instantiate_fn,
+            query_fn,
+            reply_fn: None,
+        }
+    }
+}
+
+impl<T1, T2, T3, E1, E2, E3, E4> ContractWrapper<T1, T2, T3, E1, E2, E3, E4> {
+    /// Adds the contract's `reply` entry point, e.g. `.with_reply(contract::reply)`.
+    pub fn with_reply<E>(self, reply_fn: ReplyFn<E>) -> ContractWrapper<T1, T2, T3, E1, E2, E3, E> {
+        ContractWrapper {
+            execute_fn: self.execute_fn,
+            instantiate_fn: self.instantiate_fn,
+            query_fn: self.query_fn,
+            reply_fn: Some(reply_fn),
+        }
+    }
+}
+
+impl<T1, T2, T3, E1, E2, E3, E4> Contract<Empty> for ContractWrapper<T1, T2, T3, E1, E2, E3, E4>
+where
+    T1: DeserializeOwned,
+    T2: DeserializeOwned,
+    T3: DeserializeOwned,
+    E1: Display + Debug + Send + Sync + 'static,
+    E2: Display + Debug + Send + Sync + 'static,
+    E3: Display + Debug + Send + Sync + 'static,
+    E4: Display + Debug + Send + Sync + 'static,
+{
+    fn execute(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: Vec<u8>,
+    ) -> AnyResult<Response> {
+        let msg: T1 = from_slice(&msg)?;
+        (self.execute_fn)(deps, env, info, msg).map_err(|e| anyhow!(e))
+    }
+
+    fn instantiate(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: Vec<u8>,
+    ) -> AnyResult<Response> {
+        let msg: T2 = from_slice(&msg)?;
+        (self.instantiate_fn)(deps, env, info, msg).map_err(|e| anyhow!(e))
+    }
+
+    fn query(&self, deps: Deps, env: Env, msg: Vec<u8>) -> AnyResult<Binary> {
+        let msg: T3 = from_slice(&msg)?;
+        (self.query_fn)(deps, env, msg).map_err(|e| anyhow!(e))
+    }
+
+    fn reply(&self, deps: DepsMut, env: Env, msg: Reply) -> AnyResult<Response> {
+        match self.reply_fn {
+            Some(reply_fn) => reply_fn(deps, env, msg).map_err(|e| anyhow!(e)),
+            None => bail!("no reply entry point to handle submessage {}", msg.id),
+        }
+    }
+}
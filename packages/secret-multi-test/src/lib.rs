@@ -0,0 +1,18 @@
+//! An in-process test harness for Secret Network contracts, shaped after
+//! `cw-multi-test` so tests written against it port with few edits.
+//!
+//! The differences follow the Secret runtime: [`App::store_code`] returns a
+//! [`StoredCode`] carrying the code hash, contracts are instantiated from it
+//! without an admin, and every call into a contract must present the hash of
+//! its code. Labels are unique per chain, as on Secret.
+
+mod app;
+mod bank;
+mod contracts;
+mod store;
+
+pub use crate::app::{App, AppBuilder, AppResponse, Executor, Router, StoredCode};
+pub use crate::bank::Bank;
+pub use crate::contracts::{Contract, ContractWrapper};
+
+pub type AnyResult<T> = anyhow::Result<T>;
@@ -0,0 +1,246 @@
+//! Signs SNIP-24 query permits the way a wallet does, so tests can build
+//! permits for any parameters instead of pasting a signature made in Keplr.
+//!
+//! ```ignore
+//! let signer = PermitSigner::from_seed(b"user");
+//! let permit = signer.sign(PermitParams {
+//!     allowed_tokens: vec![env.contract.address.to_string()],
+//!     permit_name: "counter".to_string(),
+//!     chain_id: "secret-4".to_string(),
+//!     permissions: vec![CounterContractPermissions::UserCount],
+//! });
+//! // `secret_toolkit::permit::validate` returns `signer.address()`
+//! ```
+
+use bech32::{ToBase32, Variant};
+use bip32::{DerivationPath, XPrv};
+use bip39::Mnemonic;
+use cosmwasm_std::{to_binary, Binary, StdError};
+use k256::ecdsa::signature::Signer;
+use k256::ecdsa::{Signature, SigningKey};
+use ripemd::Ripemd160;
+use secret_toolkit::permit::{
+    Permissions, Permit, PermitParams, PermitSignature, PubKey, SignedPermit,
+};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Bech32 prefix of Secret Network accounts.
+pub const SECRET_HRP: &str = "secret";
+
+/// BIP-44 coin type of Secret Network, as registered in SLIP-44.
+pub const SECRET_COIN_TYPE: u32 = 529;
+
+/// Amino type of the public key in a permit signature.
+pub const PUB_KEY_TYPE: &str = "tendermint/PubKeySecp256k1";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SignerError {
+    #[error("invalid secp256k1 private key")]
+    InvalidKey,
+
+    #[error("invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+
+    #[error("{0}")]
+    Std(#[from] StdError),
+}
+
+/// A secp256k1 account that signs permits.
+#[derive(Clone)]
+pub struct PermitSigner {
+    key: SigningKey,
+}
+
+impl PermitSigner {
+    /// The account whose private key is `secret`.
+    pub fn from_bytes(secret: &[u8]) -> Result<Self, SignerError> {
+        let key = SigningKey::from_slice(secret).map_err(|_| SignerError::InvalidKey)?;
+        Ok(PermitSigner { key })
+    }
+
+    /// Account `index` of a BIP-39 mnemonic, derived along
+    /// `m/44'/529'/0'/0/{index}` as Keplr and `secretcli` do.
+    pub fn from_mnemonic(phrase: &str, index: u32) -> Result<Self, SignerError> {
+        let invalid = |e: &dyn std::fmt::Display| SignerError::InvalidMnemonic(e.to_string());
+        let mnemonic = Mnemonic::parse_normalized(phrase.trim()).map_err(|e| invalid(&e))?;
+        let path: DerivationPath = format!("m/44'/{}'/0'/0/{}", SECRET_COIN_TYPE, index)
+            .parse()
+            .map_err(|e| invalid(&e))?;
+        let key = XPrv::derive_from_path(mnemonic.to_seed(""), &path).map_err(|e| invalid(&e))?;
+        Ok(PermitSigner {
+            key: key.private_key().clone(),
+        })
+    }
+
+    /// A test account derived from `seed`: the same seed always gives the
+    /// same key, different seeds give different accounts.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let mut secret = Sha256::digest(seed);
+        // a digest outside the curve order is astronomically unlikely, but
+        // hashing again keeps this infallible
+        loop {
+            if let Ok(signer) = PermitSigner::from_bytes(&secret) {
+                return signer;
+            }
+            secret = Sha256::digest(secret);
+        }
+    }
+
+    /// The compressed public key, as wallets put it in a permit.
+    pub fn public_key(&self) -> Binary {
+        let point = self.key.verifying_key().to_encoded_point(true);
+        Binary::from(point.as_bytes())
+    }
+
+    /// The account's `secret1...` address.
+    pub fn address(&self) -> String {
+        self.address_with_prefix(SECRET_HRP)
+    }
+
+    /// The account's address on a chain with another bech32 prefix.
+    pub fn address_with_prefix(&self, hrp: &str) -> String {
+        address(&self.public_key(), hrp)
+    }
+
+    /// Signs `params` as a wallet signs a permit.
+    pub fn sign<P: Permissions>(&self, params: PermitParams<P>) -> Permit<P> {
+        let signature: Signature = self
+            .key
+            .sign(&sign_doc(&params).expect("permit params serialize"));
+        Permit {
+            params,
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: PUB_KEY_TYPE.to_string(),
+                    value: self.public_key(),
+                },
+                signature: Binary::from(signature.to_bytes().as_slice()),
+            },
+        }
+    }
+}
+
+/// The bech32 address of the account holding `pub_key`:
+/// `ripemd160(sha256(pub_key))`, as the Cosmos SDK derives it.
+pub fn address(pub_key: &[u8], hrp: &str) -> String {
+    let hash = Ripemd160::digest(Sha256::digest(pub_key));
+    bech32::encode(hrp, hash.to_base32(), Variant::Bech32).expect("valid bech32 prefix")
+}
+
+/// The bytes a wallet signs for `params`: the Amino JSON sign doc of a
+/// `query_permit` message with zero fee, account number and sequence.
+/// `secret_toolkit::permit::validate` checks the signature against their
+/// SHA-256 hash.
+pub fn sign_doc<P: Permissions>(params: &PermitParams<P>) -> Result<Vec<u8>, StdError> {
+    Ok(to_binary(&SignedPermit::from_params(params))?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_dependencies;
+    use cosmwasm_std::Api;
+    use secret_toolkit::permit::validate;
+
+    const CONTRACT: &str = "secret1contract";
+
+    fn params(name: &str) -> PermitParams<String> {
+        PermitParams {
+            allowed_tokens: vec![CONTRACT.to_string()],
+            permit_name: name.to_string(),
+            chain_id: "secret-4".to_string(),
+            permissions: vec!["user_count".to_string()],
+        }
+    }
+
+    #[test]
+    fn validate_accepts_signed_permits() {
+        let deps = mock_dependencies();
+        let signer = PermitSigner::from_seed(b"user");
+        assert!(signer.address().starts_with("secret1"));
+        assert_ne!(
+            PermitSigner::from_seed(b"other").address(),
+            signer.address()
+        );
+
+        let permit = signer.sign(params("counter"));
+        let account = validate(
+            deps.as_ref(),
+            "revoked",
+            &permit,
+            CONTRACT.to_string(),
+            None,
+        );
+        assert_eq!(signer.address(), account.unwrap());
+
+        // a signature only covers the params it was made for
+        let mut forged = permit;
+        forged.params.permit_name = "another".to_string();
+        validate(
+            deps.as_ref(),
+            "revoked",
+            &forged,
+            CONTRACT.to_string(),
+            None,
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn sign_doc_matches_a_wallet_signature() {
+        // signed in Keplr for `secret12eqwlqwcu2cundyg8ytnuhjnr29fh3zgt68602`
+        let pub_key = Binary::from_base64("Ar1oSw8miosG4fJqucfb8c+HpHfr5dSvyGC5kQG9hIUy").unwrap();
+        let signature = Binary::from_base64(
+            "2pZXHXJZJw8s5q3ju0z9d5Njh77GtgEgLzdXcNHVg91H93MpuTHWPy99MQCUaw5O2dRE44G2GWQDqCGTwlO47w==",
+        )
+        .unwrap();
+        let params = PermitParams {
+            allowed_tokens: vec!["cosmos2contract".to_string()],
+            ..params("CounterPermit")
+        };
+
+        assert_eq!(
+            "secret12eqwlqwcu2cundyg8ytnuhjnr29fh3zgt68602",
+            address(&pub_key, SECRET_HRP)
+        );
+        let hash = Sha256::digest(sign_doc(&params).unwrap());
+        let deps = mock_dependencies();
+        assert!(deps
+            .api
+            .secp256k1_verify(&hash, &signature, &pub_key)
+            .unwrap());
+    }
+
+    #[test]
+    fn derives_accounts_from_a_mnemonic() {
+        const PHRASE: &str = "abandon abandon abandon abandon abandon abandon \
+                              abandon abandon abandon abandon abandon about";
+        let first = PermitSigner::from_mnemonic(PHRASE, 0).unwrap();
+        let second = PermitSigner::from_mnemonic(PHRASE, 1).unwrap();
+        assert_eq!(
+            first.address(),
+            PermitSigner::from_mnemonic(&format!("{}\n", PHRASE), 0)
+                .unwrap()
+                .address()
+        );
+        assert_ne!(first.address(), second.address());
+
+        let err = PermitSigner::from_mnemonic("abandon about", 0)
+            .err()
+            .unwrap();
+        assert!(matches!(err, SignerError::InvalidMnemonic(_)));
+    }
+
+    #[test]
+    fn rejects_invalid_private_keys() {
+        assert_eq!(
+            Some(SignerError::InvalidKey),
+            PermitSigner::from_bytes(&[0; 32]).err()
+        );
+        assert_eq!(
+            Some(SignerError::InvalidKey),
+            PermitSigner::from_bytes(&[0xff; 32]).err()
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! Evidence of what a Secret contract puts in storage.
+//!
+//! Secret encrypts values, but a node still sees which entries a transaction
+//! touches, and anything a contract puts in a key before encryption decides
+//! how linkable those entries are. [`Inspector`] runs messages against a
+//! [`RecordingStorage`], then lists the keys containing given secrets (raw
+//! addresses, counts) and reports the access pattern of each message type.
+//!
+//! ```ignore
+//! let mut inspector = Inspector::new();
+//! inspector.run("increment", |deps| execute(deps.as_mut(), env, info, msg));
+//! let leaks = inspector.leaks(&[("alice", b"secret1...")]);
+//! println!("{}", inspector.report(&secrets));
+//! ```
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::marker::PhantomData;
+
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{Empty, Order, OwnedDeps, Record, Storage};
+
+/// One storage call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read {
+        key: Vec<u8>,
+        found: bool,
+    },
+    Write {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Remove {
+        key: Vec<u8>,
+    },
+    Range {
+        start: Option<Vec<u8>>,
+        end: Option<Vec<u8>>,
+    },
+}
+
+impl Access {
+    /// The key read, written or removed; ranges have none.
+    pub fn key(&self) -> Option<&[u8]> {
+        match self {
+            Access::Read { key, .. } | Access::Write { key, .. } | Access::Remove { key } => {
+                Some(key)
+            }
+            Access::Range { .. } => None,
+        }
+    }
+}
+
+/// `MockStorage` that logs every call made through it.
+#[derive(Default)]
+pub struct RecordingStorage {
+    storage: MockStorage,
+    log: RefCell<Vec<Access>>,
+}
+
+impl RecordingStorage {
+    /// The calls since the last `take_log`.
+    pub fn take_log(&self) -> Vec<Access> {
+        self.log.take()
+    }
+}
+
+impl Storage for RecordingStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let value = self.storage.get(key);
+        self.log.borrow_mut().push(Access::Read {
+            key: key.to_vec(),
+            found: value.is_some(),
+        });
+        value
+    }
+
+    fn range<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        self.log.borrow_mut().push(Access::Range {
+            start: start.map(<[u8]>::to_vec),
+            end: end.map(<[u8]>::to_vec),
+        });
+        // every key the iteration returns is read as much as a `get` reads it
+        Box::new(self.storage.range(start, end, order).inspect(|(key, _)| {
+            self.log.borrow_mut().push(Access::Read {
+                key: key.clone(),
+                found: true,
+            })
+        }))
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.log.get_mut().push(Access::Write {
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+        self.storage.set(key, value)
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.log
+            .get_mut()
+            .push(Access::Remove { key: key.to_vec() });
+        self.storage.remove(key)
+    }
+}
+
+pub type InspectedDeps = OwnedDeps<RecordingStorage, MockApi, MockQuerier, Empty>;
+
+/// A key that holds a secret.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Leak {
+    /// Name the secret was given, e.g. `alice`.
+    pub secret: String,
+    /// Message during which the key was accessed.
+    pub message: String,
+    /// The key, rendered as in [`Inspector::report`].
+    pub key: String,
+}
+
+/// Runs messages against recording storage and keeps each one's accesses.
+pub struct Inspector {
+    pub deps: InspectedDeps,
+    messages: Vec<(String, Vec<Access>)>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Inspector::new()
+    }
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Inspector {
+            deps: OwnedDeps {
+                storage: RecordingStorage::default(),
+                api: MockApi::default(),
+                querier: MockQuerier::default(),
+                custom_query_type: PhantomData,
+            },
+            messages: vec![],
+        }
+    }
+
+    /// Runs one message, recording its accesses under `message`, the message
+    /// type it is reported as.
+    pub fn run<T>(&mut self, message: &str, run: impl FnOnce(&mut InspectedDeps) -> T) -> T {
+        self.deps.storage.take_log();
+        let result = run(&mut self.deps);
+        let log = self.deps.storage.take_log();
+        self.messages.push((message.to_string(), log));
+        result
+    }
+
+    /// Every accessed key containing one of `secrets`, given as name and raw
+    /// bytes, once per message type.
+    pub fn leaks(&self, secrets: &[(&str, &[u8])]) -> Vec<Leak> {
+        let mut leaks = vec![];
+        for (message, log) in &self.messages {
+            for key in log.iter().filter_map(Access::key) {
+                for (name, secret) in secrets {
+                    if contains(key, secret) {
+                        leaks.push(Leak {
+                            secret: name.to_string(),
+                            message: message.clone(),
+                            key: render(key, secrets),
+                        });
+                    }
+                }
+            }
+        }
+        leaks.sort();
+        leaks.dedup();
+        leaks
+    }
+
+    /// Per message type: how often it ran, its reads, writes and removals per
+    /// run, and the keys it touched with `secrets` replaced by their names.
+    pub fn report(&self, secrets: &[(&str, &[u8])]) -> String {
+        let mut by_type: BTreeMap<&str, Vec<&[Access]>> = BTreeMap::new();
+        for (message, log) in &self.messages {
+            by_type.entry(message).or_default().push(log);
+        }
+
+        let mut report = String::new();
+        for (message, runs) in by_type {
+            let count = |f: fn(&Access) -> bool| {
+                runs.iter()
+                    .map(|log| log.iter().filter(|a| f(a)).count())
+                    .sum::<usize>() as f64
+                    / runs.len() as f64
+            };
+            writeln!(
+                report,
+                "{} (x{}): {:.1} reads, {:.1} writes, {:.1} removes per run",
+                message,
+                runs.len(),
+                count(|a| matches!(a, Access::Read { .. } | Access::Range { .. })),
+                count(|a| matches!(a, Access::Write { .. })),
+                count(|a| matches!(a, Access::Remove { .. })),
+            )
+            .unwrap();
+
+            let mut keys: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+            for access in runs.iter().flat_map(|log| log.iter()) {
+                let (key, op) = match access {
+                    Access::Read { key, .. } => (render(key, secrets), "read"),
+                    Access::Write { key, .. } => (render(key, secrets), "write"),
+                    Access::Remove { key } => (render(key, secrets), "remove"),
+                    Access::Range { start, .. } => (
+                        render(start.as_deref().unwrap_or_default(), secrets),
+                        "range",
+                    ),
+                };
+                keys.entry(key).or_default().insert(op);
+            }
+            for (key, ops) in keys {
+                let ops: Vec<_> = ops.into_iter().collect();
+                writeln!(report, "  {:<10} {}", ops.join("+"), key).unwrap();
+            }
+        }
+        report
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty() && haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// `key` as text: secrets as `<name>`, printable ASCII as is, other bytes as
+/// `\xNN`.
+fn render(key: &[u8], secrets: &[(&str, &[u8])]) -> String {
+    let mut text = String::new();
+    let mut i = 0;
+    'bytes: while i < key.len() {
+        for (name, secret) in secrets {
+            if !secret.is_empty() && key[i..].starts_with(secret) {
+                write!(text, "<{}>", name).unwrap();
+                i += secret.len();
+                continue 'bytes;
+            }
+        }
+        match key[i] {
+            b @ 0x20..=0x7e if b != b'\\' => text.push(b as char),
+            b => write!(text, "\\x{:02x}", b).unwrap(),
+        }
+        i += 1;
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_secrets_in_keys_but_not_values() {
+        let mut inspector = Inspector::new();
+        inspector.run("store", |deps| {
+            deps.storage.set(b"balance\x00alice", b"100");
+            deps.storage.set(b"total", b"alice");
+        });
+        inspector.run("load", |deps| deps.storage.get(b"balance\x00alice"));
+
+        let secrets: &[(&str, &[u8])] = &[("alice", b"alice"), ("amount", b"100")];
+        let leaks = inspector.leaks(secrets);
+        let keys: Vec<_> = leaks
+            .iter()
+            .map(|l| (l.message.as_str(), l.key.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("load", "balance\\x00<alice>"),
+                ("store", "balance\\x00<alice>")
+            ],
+            keys
+        );
+
+        assert_eq!(
+            "load (x1): 1.0 reads, 0.0 writes, 0.0 removes per run\n  \
+               read       balance\\x00<alice>\n\
+             store (x1): 0.0 reads, 2.0 writes, 0.0 removes per run\n  \
+               write      balance\\x00<alice>\n  \
+               write      total\n",
+            inspector.report(secrets)
+        );
+    }
+
+    #[test]
+    fn ranges_read_every_key_they_return() {
+        let mut inspector = Inspector::new();
+        inspector.run("store", |deps| {
+            deps.storage.set(b"balance\x00alice", b"100");
+            deps.storage.set(b"balance\x00bob", b"5");
+        });
+        let total = inspector.run("sum", |deps| {
+            deps.storage
+                .range(Some(b"balance"), None, Order::Ascending)
+                .count()
+        });
+        assert_eq!(2, total);
+
+        let secrets: &[(&str, &[u8])] = &[("alice", b"alice"), ("bob", b"bob")];
+        let sum: Vec<_> = inspector
+            .leaks(secrets)
+            .into_iter()
+            .filter(|l| l.message == "sum")
+            .map(|l| l.key)
+            .collect();
+        assert_eq!(vec!["balance\\x00<alice>", "balance\\x00<bob>"], sum);
+    }
+}
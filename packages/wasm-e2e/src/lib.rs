@@ -0,0 +1,111 @@
+//! Runs a compiled contract in `cosmwasm-vm` on the VM's mock backend,
+//! recording the gas each call takes.
+//!
+//! The rlib tests call the contract's Rust functions directly; this runs the
+//! wasm a chain would run, so a stale artifact or a difference between the
+//! native and wasm builds shows up as a failing comparison.
+
+use std::fmt::Write;
+
+use cosmwasm_std::testing::{mock_env, mock_info};
+use cosmwasm_std::{Binary, ContractResult, Empty, Env, Response};
+use cosmwasm_vm::testing::{mock_instance_with_gas_limit, MockApi, MockQuerier, MockStorage};
+use cosmwasm_vm::{call_execute, call_instantiate, call_query, Instance};
+use serde::Serialize;
+
+/// Gas a mock instance starts with; far above what a single call needs.
+pub const GAS_LIMIT: u64 = 500_000_000_000;
+
+/// Gas one call took, in CosmWasm gas units (not Cosmos SDK gas).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GasUse {
+    pub call: String,
+    pub gas: u64,
+}
+
+pub struct WasmContract {
+    instance: Instance<MockApi, MockStorage, MockQuerier>,
+    env: Env,
+    gas: Vec<GasUse>,
+}
+
+impl WasmContract {
+    pub fn load(wasm: &[u8]) -> Self {
+        WasmContract {
+            instance: mock_instance_with_gas_limit(wasm, GAS_LIMIT),
+            env: mock_env(),
+            gas: vec![],
+        }
+    }
+
+    pub fn instantiate<M: Serialize>(
+        &mut self,
+        sender: &str,
+        msg: &M,
+    ) -> ContractResult<Response<Empty>> {
+        let (env, info, msg) = (self.env.clone(), mock_info(sender, &[]), to_vec(msg));
+        self.metered(label("instantiate", &msg), |instance| {
+            call_instantiate(instance, &env, &info, &msg)
+        })
+    }
+
+    pub fn execute<M: Serialize>(
+        &mut self,
+        sender: &str,
+        msg: &M,
+    ) -> ContractResult<Response<Empty>> {
+        let (env, info, msg) = (self.env.clone(), mock_info(sender, &[]), to_vec(msg));
+        self.metered(label("execute", &msg), |instance| {
+            call_execute(instance, &env, &info, &msg)
+        })
+    }
+
+    pub fn query<M: Serialize>(&mut self, msg: &M) -> ContractResult<Binary> {
+        let (env, msg) = (self.env.clone(), to_vec(msg));
+        self.metered(label("query", &msg), |instance| {
+            call_query(instance, &env, &msg)
+        })
+    }
+
+    /// Every call so far, in order.
+    pub fn gas(&self) -> &[GasUse] {
+        &self.gas
+    }
+
+    /// A table of the gas each call took.
+    pub fn gas_report(&self) -> String {
+        let width = self.gas.iter().map(|g| g.call.len()).max().unwrap_or(0);
+        let mut report = String::new();
+        for GasUse { call, gas } in &self.gas {
+            writeln!(report, "{:<width$}  {:>12}", call, gas, width = width).unwrap();
+        }
+        report
+    }
+
+    fn metered<T>(
+        &mut self,
+        call: String,
+        run: impl FnOnce(&mut Instance<MockApi, MockStorage, MockQuerier>) -> cosmwasm_vm::VmResult<T>,
+    ) -> T {
+        let before = self.instance.get_gas_left();
+        let result = run(&mut self.instance).unwrap_or_else(|e| panic!("{}: {}", call, e));
+        let gas = before - self.instance.get_gas_left();
+        self.gas.push(GasUse { call, gas });
+        result
+    }
+}
+
+fn to_vec<M: Serialize>(msg: &M) -> Vec<u8> {
+    cosmwasm_std::to_vec(msg).expect("messages serialize")
+}
+
+/// `execute increment`: the entry point and the message variant.
+fn label(entry_point: &str, msg: &[u8]) -> String {
+    let variant = serde_json::from_slice::<serde_json::Value>(msg)
+        .ok()
+        .and_then(|v| v.as_object()?.keys().next().cloned());
+    match variant {
+        Some(variant) if entry_point != "instantiate" => format!("{} {}", entry_point, variant),
+        _ => entry_point.to_string(),
+    }
+}
@@ -0,0 +1,97 @@
+//! cw-counter's artifact against its native build: the same messages give the
+//! same responses, and each call's gas is reported.
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier};
+use cosmwasm_std::{Addr, ContractResult, MemoryStorage, OwnedDeps};
+use cw_counter::contract::{execute, instantiate, query};
+use cw_counter::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use wasm_e2e::WasmContract;
+
+static WASM: &[u8] = include_bytes!("../../../contracts/cw-counter/artifacts/cw-counter.wasm");
+
+type Native = OwnedDeps<MemoryStorage, MockApi, MockQuerier>;
+
+/// Instantiates both builds with `count`.
+fn counters(count: i32) -> (Native, WasmContract) {
+    let msg = InstantiateMsg { count };
+    let mut native = mock_dependencies();
+    let expected = instantiate(
+        native.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        msg.clone(),
+    );
+
+    let mut wasm = WasmContract::load(WASM);
+    let res = wasm.instantiate("creator", &msg);
+    assert_eq!(ContractResult::Ok(expected.unwrap()), res);
+    (native, wasm)
+}
+
+fn increment(native: &mut Native, wasm: &mut WasmContract, sender: &str) {
+    let msg = ExecuteMsg::Increment {};
+    let expected = execute(
+        native.as_mut(),
+        mock_env(),
+        mock_info(sender, &[]),
+        msg.clone(),
+    );
+    assert_eq!(
+        ContractResult::Ok(expected.unwrap()),
+        wasm.execute(sender, &msg)
+    );
+}
+
+fn assert_same_query(native: &Native, wasm: &mut WasmContract, msg: QueryMsg) {
+    let expected = query(native.as_ref(), mock_env(), msg.clone());
+    match (expected, wasm.query(&msg)) {
+        (Ok(expected), ContractResult::Ok(res)) => assert_eq!(expected, res, "{:?}", msg),
+        (Err(expected), ContractResult::Err(err)) => assert_eq!(expected.to_string(), err),
+        (expected, res) => panic!("{:?}: native {:?}, wasm {:?}", msg, expected, res),
+    }
+}
+
+#[test]
+fn proper_initialization() {
+    let (native, mut wasm) = counters(17);
+    assert_same_query(&native, &mut wasm, QueryMsg::GetCount {});
+}
+
+#[test]
+fn increment_counts_per_user() {
+    let (mut native, mut wasm) = counters(17);
+    for sender in ["alice", "bob", "alice"] {
+        increment(&mut native, &mut wasm, sender);
+    }
+
+    assert_same_query(&native, &mut wasm, QueryMsg::GetCount {});
+    for user in ["alice", "bob", "carol"] {
+        let addr = Addr::unchecked(user);
+        assert_same_query(&native, &mut wasm, QueryMsg::GetUserCount { addr });
+    }
+}
+
+#[test]
+fn reports_gas_per_message() {
+    let (mut native, mut wasm) = counters(0);
+    increment(&mut native, &mut wasm, "alice");
+    // a second increment updates the user's entry instead of creating it
+    increment(&mut native, &mut wasm, "alice");
+    assert_same_query(&native, &mut wasm, QueryMsg::GetCount {});
+    let addr = Addr::unchecked("alice");
+    assert_same_query(&native, &mut wasm, QueryMsg::GetUserCount { addr });
+
+    println!("{}", wasm.gas_report());
+    let calls: Vec<_> = wasm.gas().iter().map(|g| g.call.as_str()).collect();
+    assert_eq!(
+        vec![
+            "instantiate",
+            "execute increment",
+            "execute increment",
+            "query get_count",
+            "query get_user_count"
+        ],
+        calls
+    );
+    assert!(wasm.gas().iter().all(|g| g.gas > 0));
+}